@@ -0,0 +1,81 @@
+//! Measures how ray queries scale with the number of spheres in the scene.
+//!
+//! Run with `cargo bench --bench bvh`.
+
+use std::time::Instant;
+use tinyraytracer::{Material, Scene, Sphere, Vec3f};
+
+const RAYS: usize = 200_000;
+
+/// xorshift64*, good enough to scatter spheres reproducibly.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> f32 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        (self.0.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    fn in_cube(&mut self, half: f32) -> Vec3f {
+        Vec3f::new(
+            (self.next() * 2f32 - 1f32) * half,
+            (self.next() * 2f32 - 1f32) * half,
+            (self.next() * 2f32 - 1f32) * half,
+        )
+    }
+}
+
+fn main() {
+    println!(
+        "{:>9} {:>12} {:>16} {:>16}",
+        "spheres", "build (ms)", "primary (Mray/s)", "shadow (Mray/s)"
+    );
+    for &count in &[10, 100, 1_000, 10_000, 100_000, 1_000_000] {
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        // Keep the fraction of the volume covered by spheres roughly constant.
+        let half = 50f32;
+        let radius = half * 0.5 / (count as f32).cbrt();
+        let mut scene = Scene::new();
+        for _ in 0..count {
+            let center = rng.in_cube(half) + Vec3f::new(0.0, 0.0, -2f32 * half);
+            scene.add(Sphere::new(center, radius, Material::default()));
+        }
+        let rays = (0..RAYS)
+            .map(|_| {
+                (
+                    Vec3f::default(),
+                    (rng.in_cube(0.5) + Vec3f::new(0.0, 0.0, -1.0)).normalize(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let start = Instant::now();
+        scene.occluded(Vec3f::default(), Vec3f::new(0.0, 0.0, -1.0), 0f32);
+        let build = start.elapsed();
+
+        let start = Instant::now();
+        let hits = rays
+            .iter()
+            .filter(|(orig, dir)| scene.intersect(*orig, *dir).is_some())
+            .count();
+        let primary = start.elapsed();
+
+        let start = Instant::now();
+        let blocked = rays
+            .iter()
+            .filter(|(orig, dir)| scene.occluded(*orig, *dir, 500f32))
+            .count();
+        let shadow = start.elapsed();
+        assert_eq!(hits, blocked);
+
+        println!(
+            "{:>9} {:>12.1} {:>16.2} {:>16.2}",
+            count,
+            build.as_secs_f64() * 1e3,
+            RAYS as f64 / primary.as_secs_f64() / 1e6,
+            RAYS as f64 / shadow.as_secs_f64() / 1e6,
+        );
+    }
+}
@@ -0,0 +1,168 @@
+//! Physically based scattering: the GGX (Trowbridge-Reitz) metallic-roughness reflection model
+//! and smooth dielectrics.
+//!
+//! Directions point away from the surface, and the normal is on the same side as the viewer.
+
+use crate::geometry::Vec3f;
+use crate::sampling;
+use std::f32::consts::PI;
+
+/// Smallest GGX width, which keeps perfectly smooth surfaces from producing infinite highlights.
+const MIN_ALPHA: f32 = 1e-3;
+
+/// Reflectance at normal incidence of dielectrics, which covers most non-metals.
+const DIELECTRIC_F0: f32 = 0.04;
+
+/// A diffuse base layer under a GGX specular layer with Smith masking and Schlick's Fresnel.
+///
+/// Metals have no diffuse layer and tint their reflections with `base_color`.
+///
+/// ```
+/// use tinyraytracer::bsdf::Microfacet;
+/// use tinyraytracer::Vec3f;
+///
+/// let gold = Microfacet::new(Vec3f::new(1.0, 0.78, 0.34), 1.0, 0.3);
+/// let n = Vec3f::new(0.0, 0.0, 1.0);
+/// let wo = Vec3f::new(0.6, 0.0, 0.8);
+///
+/// // Light is reflected most strongly around the mirror direction.
+/// let mirror = gold.evaluate(n, wo, Vec3f::new(-0.6, 0.0, 0.8));
+/// let off = gold.evaluate(n, wo, Vec3f::new(0.0, 0.6, 0.8));
+/// assert!(mirror.0[0] > 10.0 * off.0[0]);
+/// // Nothing is reflected from below the surface.
+/// assert_eq!(gold.evaluate(n, wo, Vec3f::new(0.0, 0.6, -0.8)), Vec3f::default());
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Microfacet {
+    pub base_color: Vec3f,
+    pub metallic: f32,
+    /// GGX width, the square of the perceptual roughness.
+    pub alpha: f32,
+}
+
+impl Microfacet {
+    pub fn new(base_color: Vec3f, metallic: f32, roughness: f32) -> Self {
+        Self {
+            base_color,
+            metallic,
+            alpha: (roughness * roughness).max(MIN_ALPHA),
+        }
+    }
+
+    /// Reflectance at normal incidence.
+    fn f0(&self) -> Vec3f {
+        let dielectric = Vec3f::new(DIELECTRIC_F0, DIELECTRIC_F0, DIELECTRIC_F0);
+        dielectric * (1f32 - self.metallic) + self.base_color * self.metallic
+    }
+
+    /// Schlick's approximation of the Fresnel reflectance at incidence angle `cos`.
+    pub fn fresnel(&self, cos: f32) -> Vec3f {
+        let f0 = self.f0();
+        let weight = (1f32 - cos.clamp(0f32, 1f32)).powi(5);
+        f0 + (Vec3f::new(1.0, 1.0, 1.0) - f0) * weight
+    }
+
+    /// The GGX distribution of microfacet normals.
+    fn distribution(&self, cos_h: f32) -> f32 {
+        let a2 = self.alpha * self.alpha;
+        let d = cos_h * cos_h * (a2 - 1f32) + 1f32;
+        a2 / (PI * d * d)
+    }
+
+    /// Smith's masking function for one direction.
+    fn masking(&self, cos: f32) -> f32 {
+        let a2 = self.alpha * self.alpha;
+        2f32 * cos / (cos + (a2 + (1f32 - a2) * cos * cos).sqrt())
+    }
+
+    /// Probability of sampling the specular rather than the diffuse layer.
+    fn specular_probability(&self, cos_o: f32) -> f32 {
+        let f = self.fresnel(cos_o).max_component();
+        f + (1f32 - f) * self.metallic
+    }
+
+    /// The BSDF for light arriving from `wi` and leaving towards `wo`.
+    pub fn evaluate(&self, n: Vec3f, wo: Vec3f, wi: Vec3f) -> Vec3f {
+        let (cos_o, cos_i) = (n * wo, n * wi);
+        if cos_o <= 0f32 || cos_i <= 0f32 {
+            return Vec3f::default();
+        }
+        let h = (wo + wi).normalize();
+        let fresnel = self.fresnel(wo * h);
+        let specular = self.distribution(n * h) * self.masking(cos_o) * self.masking(cos_i)
+            / (4f32 * cos_o * cos_i);
+        let diffuse = (Vec3f::new(1.0, 1.0, 1.0) - fresnel).component_mul(self.base_color)
+            * ((1f32 - self.metallic) / PI);
+        fresnel * specular + diffuse
+    }
+
+    /// The density with which [`sample`](Self::sample) picks `wi`.
+    pub fn pdf(&self, n: Vec3f, wo: Vec3f, wi: Vec3f) -> f32 {
+        let (cos_o, cos_i) = (n * wo, n * wi);
+        if cos_o <= 0f32 || cos_i <= 0f32 {
+            return 0f32;
+        }
+        let h = (wo + wi).normalize();
+        let specular = self.distribution(n * h) * (n * h) / (4f32 * (wo * h));
+        let p = self.specular_probability(cos_o);
+        p * specular + (1f32 - p) * cos_i / PI
+    }
+
+    /// Samples an incoming direction for `wo`, returning it with the BSDF times the cosine
+    /// divided by the density. `choice` picks the layer and `u` the direction within it.
+    pub fn sample(&self, n: Vec3f, wo: Vec3f, choice: f32, u: [f32; 2]) -> Option<(Vec3f, Vec3f)> {
+        let cos_o = n * wo;
+        if cos_o <= 0f32 {
+            return None;
+        }
+        let wi = if choice < self.specular_probability(cos_o) {
+            let a2 = self.alpha * self.alpha;
+            let cos = ((1f32 - u[0]) / (1f32 + (a2 - 1f32) * u[0])).sqrt();
+            let sin = (1f32 - cos * cos).max(0f32).sqrt();
+            let phi = 2f32 * PI * u[1];
+            let (t, b) = n.basis();
+            let h = t * (sin * phi.cos()) + b * (sin * phi.sin()) + n * cos;
+            (-wo).reflect(h)
+        } else {
+            sampling::cosine_hemisphere(n, u)
+        };
+        let pdf = self.pdf(n, wo, wi);
+        if pdf <= 0f32 {
+            return None;
+        }
+        Some((wi, self.evaluate(n, wo, wi) * ((n * wi) / pdf)))
+    }
+}
+
+/// The fraction of unpolarized light reflected by a smooth dielectric interface, from the exact
+/// Fresnel equations.
+///
+/// `cos_i` is the cosine of the incidence angle and `eta` the ratio of the refractive index
+/// behind the surface to that in front of it. Total internal reflection yields `1`.
+///
+/// ```
+/// use tinyraytracer::bsdf::fresnel_dielectric;
+///
+/// // Glass reflects 4% head-on and everything at grazing angles.
+/// assert!((fresnel_dielectric(1.0, 1.5) - 0.04).abs() < 1e-6);
+/// assert!(fresnel_dielectric(0.01, 1.5) > 0.9);
+/// // Leaving glass beyond the critical angle of about 42 degrees.
+/// assert_eq!(fresnel_dielectric(0.5, 1.0 / 1.5), 1.0);
+/// ```
+pub fn fresnel_dielectric(cos_i: f32, eta: f32) -> f32 {
+    let cos_i = cos_i.clamp(0f32, 1f32);
+    let sin2_t = (1f32 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1f32 {
+        return 1f32;
+    }
+    let cos_t = (1f32 - sin2_t).sqrt();
+    let s = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    let p = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    (s * s + p * p) / 2f32
+}
+
+/// The fraction of light left after travelling `distance` through a medium with the given
+/// absorption coefficients, by the Beer-Lambert law.
+pub fn transmittance(absorption: Vec3f, distance: f32) -> Vec3f {
+    Vec3f(absorption.0.map(|a| (-a * distance).exp()))
+}
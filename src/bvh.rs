@@ -0,0 +1,351 @@
+//! Bounding volume hierarchy over arbitrary primitives.
+//!
+//! The tree is built top-down with a binned surface area heuristic and stored as a flat array of
+//! nodes in depth-first order: the left child of an interior node immediately follows it, the
+//! index of the right child is stored in the node.
+
+use crate::geometry::{Aabb, Vec3f};
+
+const BINS: usize = 16;
+const MAX_LEAF_SIZE: usize = 4;
+const TRAVERSAL_COST: f32 = 1f32;
+const INTERSECTION_COST: f32 = 1f32;
+
+#[derive(Debug, Clone, Copy)]
+struct Node {
+    bounds: Aabb,
+    /// First primitive of a leaf, or the right child of an interior node.
+    offset: u32,
+    /// Number of primitives of a leaf, `0` for interior nodes.
+    count: u32,
+    /// Split axis of an interior node.
+    axis: u8,
+}
+
+/// A bounding volume hierarchy referring to primitives by their index.
+#[derive(Debug, Clone, Default)]
+pub struct Bvh {
+    nodes: Vec<Node>,
+    indices: Vec<u32>,
+}
+
+#[derive(Clone, Copy)]
+struct Bin {
+    bounds: Aabb,
+    count: usize,
+}
+
+struct Builder<'a> {
+    bounds: &'a [Aabb],
+    centroids: Vec<Vec3f>,
+    nodes: Vec<Node>,
+}
+
+impl<'a> Builder<'a> {
+    fn build(&mut self, indices: &mut [u32], offset: usize) -> usize {
+        let bounds = indices
+            .iter()
+            .fold(Aabb::empty(), |b, &i| b.union(self.bounds[i as usize]));
+        let node = self.nodes.len();
+        self.nodes.push(Node {
+            bounds,
+            offset: offset as u32,
+            count: indices.len() as u32,
+            axis: 0,
+        });
+        if indices.len() <= MAX_LEAF_SIZE {
+            return node;
+        }
+
+        let centroid_bounds = indices
+            .iter()
+            .fold(Aabb::empty(), |b, &i| b.grow(self.centroids[i as usize]));
+        let extent = centroid_bounds.max - centroid_bounds.min;
+        let axis = (0..3)
+            .max_by(|&a, &b| extent.0[a].total_cmp(&extent.0[b]))
+            .unwrap_or(0);
+        if extent.0[axis] <= 0f32 {
+            return node;
+        }
+
+        let lo = centroid_bounds.min.0[axis];
+        let scale = BINS as f32 / extent.0[axis];
+        let bin_of = |c: Vec3f| (((c.0[axis] - lo) * scale) as usize).min(BINS - 1);
+        let mut bins = [Bin {
+            bounds: Aabb::empty(),
+            count: 0,
+        }; BINS];
+        for &i in indices.iter() {
+            let bin = &mut bins[bin_of(self.centroids[i as usize])];
+            bin.bounds = bin.bounds.union(self.bounds[i as usize]);
+            bin.count += 1;
+        }
+
+        // Sweep from the right to get the cost of every right-hand side, then from the left.
+        let mut right_area = [0f32; BINS];
+        let mut right_count = [0usize; BINS];
+        let mut acc = Aabb::empty();
+        let mut count = 0;
+        for b in (1..BINS).rev() {
+            acc = acc.union(bins[b].bounds);
+            count += bins[b].count;
+            right_area[b] = acc.surface_area();
+            right_count[b] = count;
+        }
+        let mut best = (f32::INFINITY, 0);
+        let mut acc = Aabb::empty();
+        let mut count = 0;
+        for b in 0..BINS - 1 {
+            acc = acc.union(bins[b].bounds);
+            count += bins[b].count;
+            let cost =
+                acc.surface_area() * count as f32 + right_area[b + 1] * right_count[b + 1] as f32;
+            if cost < best.0 {
+                best = (cost, b + 1);
+            }
+        }
+
+        let leaf_cost = INTERSECTION_COST * indices.len() as f32;
+        let split_cost =
+            TRAVERSAL_COST + INTERSECTION_COST * best.0 / bounds.surface_area().max(f32::EPSILON);
+        if split_cost >= leaf_cost && indices.len() <= 4 * MAX_LEAF_SIZE {
+            return node;
+        }
+
+        let mut mid = 0;
+        for k in 0..indices.len() {
+            if bin_of(self.centroids[indices[k] as usize]) < best.1 {
+                indices.swap(k, mid);
+                mid += 1;
+            }
+        }
+        if mid == 0 || mid == indices.len() {
+            mid = indices.len() / 2;
+        }
+
+        let (left, right) = indices.split_at_mut(mid);
+        self.build(left, offset);
+        let right_node = self.build(right, offset + mid);
+        self.nodes[node] = Node {
+            bounds,
+            offset: right_node as u32,
+            count: 0,
+            axis: axis as u8,
+        };
+        node
+    }
+}
+
+impl Bvh {
+    /// Builds a hierarchy over primitives with the given bounding boxes.
+    pub fn new(bounds: &[Aabb]) -> Self {
+        let mut indices = (0..bounds.len() as u32).collect::<Vec<_>>();
+        let mut builder = Builder {
+            bounds,
+            centroids: bounds.iter().map(Aabb::centroid).collect(),
+            nodes: Vec::with_capacity(2 * bounds.len() / MAX_LEAF_SIZE + 1),
+        };
+        if !bounds.is_empty() {
+            builder.build(&mut indices, 0);
+        }
+        Self {
+            nodes: builder.nodes,
+            indices,
+        }
+    }
+
+    /// Bounds of all primitives in the hierarchy.
+    pub fn bounds(&self) -> Aabb {
+        self.nodes.first().map_or(Aabb::empty(), |n| n.bounds)
+    }
+
+    /// Finds the nearest primitive hit by the ray closer than `t_max`.
+    ///
+    /// `hit` is called with a primitive index and the current maximum distance and returns the
+    /// distance to the primitive if it is hit within that range.
+    pub fn intersect<F>(&self, orig: Vec3f, dir: Vec3f, t_max: f32, hit: F) -> Option<(usize, f32)>
+    where
+        F: FnMut(usize, f32) -> Option<f32>,
+    {
+        self.traverse(orig, dir, t_max, false, hit)
+    }
+
+    /// Checks whether any primitive is hit by the ray closer than `t_max`.
+    pub fn occluded<F>(&self, orig: Vec3f, dir: Vec3f, t_max: f32, hit: F) -> bool
+    where
+        F: FnMut(usize, f32) -> Option<f32>,
+    {
+        self.traverse(orig, dir, t_max, true, hit).is_some()
+    }
+
+    fn traverse<F>(
+        &self,
+        orig: Vec3f,
+        dir: Vec3f,
+        mut t_max: f32,
+        any: bool,
+        mut hit: F,
+    ) -> Option<(usize, f32)>
+    where
+        F: FnMut(usize, f32) -> Option<f32>,
+    {
+        if self.nodes.is_empty() {
+            return None;
+        }
+        let inv_dir = Vec3f::new(1f32 / dir.0[0], 1f32 / dir.0[1], 1f32 / dir.0[2]);
+        let mut nearest = None;
+        // Deep trees spill over into a heap-allocated stack.
+        let mut stack = [0usize; 64];
+        let mut overflow = Vec::new();
+        let mut top = 0;
+        let mut node = 0;
+        loop {
+            let n = &self.nodes[node];
+            if n.bounds.ray_intersect(orig, inv_dir, t_max).is_some() {
+                if n.count > 0 {
+                    let start = n.offset as usize;
+                    for &i in &self.indices[start..start + n.count as usize] {
+                        if let Some(t) = hit(i as usize, t_max) {
+                            if t < t_max {
+                                t_max = t;
+                                nearest = Some((i as usize, t));
+                                if any {
+                                    return nearest;
+                                }
+                            }
+                        }
+                    }
+                } else {
+                    // Visit the child on the near side of the split plane first.
+                    let (first, second) = if dir.0[n.axis as usize] < 0f32 {
+                        (n.offset as usize, node + 1)
+                    } else {
+                        (node + 1, n.offset as usize)
+                    };
+                    if top < stack.len() {
+                        stack[top] = second;
+                        top += 1;
+                    } else {
+                        overflow.push(second);
+                    }
+                    node = first;
+                    continue;
+                }
+            }
+            node = match overflow.pop() {
+                Some(next) => next,
+                None if top > 0 => {
+                    top -= 1;
+                    stack[top]
+                }
+                None => return nearest,
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::ray_triangle_intersect;
+    use crate::sampling::Rng;
+
+    fn random_point(rng: &mut Rng, scale: f32) -> Vec3f {
+        Vec3f::new(
+            rng.next_f32() - 0.5,
+            rng.next_f32() - 0.5,
+            rng.next_f32() - 0.5,
+        ) * scale
+    }
+
+    /// Triangles with corners near `center(i)`.
+    fn soup(
+        count: usize,
+        rng: &mut Rng,
+        center: impl Fn(usize, &mut Rng) -> Vec3f,
+    ) -> Vec<[Vec3f; 3]> {
+        (0..count)
+            .map(|i| {
+                let c = center(i, rng);
+                [0, 1, 2].map(|_| c + random_point(rng, 2.0))
+            })
+            .collect()
+    }
+
+    /// Asserts that the hierarchy finds the same nearest triangle as testing every one of them,
+    /// for rays from all around the triangles.
+    fn assert_matches_brute_force(triangles: &[[Vec3f; 3]], rng: &mut Rng) {
+        let bounds = triangles
+            .iter()
+            .map(|t| t.iter().fold(Aabb::empty(), |b, &p| b.grow(p)))
+            .collect::<Vec<_>>();
+        let bvh = Bvh::new(&bounds);
+        let hit = |orig, dir, i: usize, t_max: f32| {
+            ray_triangle_intersect(orig, dir, triangles[i])
+                .map(|(t, _)| t)
+                .filter(|&t| t < t_max)
+        };
+        for _ in 0..2000 {
+            let orig = random_point(rng, 30.0);
+            // Towards the centroid of a random triangle, passing it by a little.
+            let target = triangles[rng.next_u32() as usize % triangles.len()]
+                .iter()
+                .fold(Vec3f::default(), |sum, &p| sum + p)
+                * (1.0 / 3.0)
+                + random_point(rng, 1.0);
+            let dir = (target - orig).normalize();
+            let t_max = if rng.next_f32() < 0.5 { f32::MAX } else { 30.0 };
+            let expected = (0..triangles.len())
+                .filter_map(|i| hit(orig, dir, i, t_max))
+                .min_by(f32::total_cmp);
+            let actual = bvh.intersect(orig, dir, t_max, |i, t_max| hit(orig, dir, i, t_max));
+            assert_eq!(actual.map(|(_, t)| t), expected);
+            if let Some((i, t)) = actual {
+                assert_eq!(hit(orig, dir, i, f32::MAX), Some(t));
+            }
+            assert_eq!(
+                bvh.occluded(orig, dir, t_max, |i, t_max| hit(orig, dir, i, t_max)),
+                expected.is_some()
+            );
+        }
+    }
+
+    #[test]
+    fn random_soup() {
+        let mut rng = Rng::new(1, 0);
+        let triangles = soup(500, &mut rng, |_, rng| random_point(rng, 20.0));
+        assert_matches_brute_force(&triangles, &mut rng);
+    }
+
+    #[test]
+    fn empty() {
+        let bvh = Bvh::new(&[]);
+        assert_eq!(bvh.bounds(), Aabb::empty());
+        let dir = Vec3f::new(0.0, 0.0, -1.0);
+        assert_eq!(
+            bvh.intersect(Vec3f::default(), dir, f32::MAX, |_, _| Some(1.0)),
+            None
+        );
+        assert!(!bvh.occluded(Vec3f::default(), dir, f32::MAX, |_, _| Some(1.0)));
+    }
+
+    #[test]
+    fn single_primitive() {
+        let mut rng = Rng::new(2, 0);
+        let triangles = soup(1, &mut rng, |_, _| Vec3f::default());
+        assert_matches_brute_force(&triangles, &mut rng);
+    }
+
+    #[test]
+    fn coincident_centroids() {
+        // No split separates these, so the builder must stop instead of recursing forever.
+        let mut rng = Rng::new(3, 0);
+        let triangles = (0..100)
+            .map(|_| {
+                let [a, b] = [0, 1].map(|_| random_point(&mut rng, 4.0));
+                [a, b, -(a + b)]
+            })
+            .collect::<Vec<_>>();
+        assert_matches_brute_force(&triangles, &mut rng);
+    }
+}
@@ -0,0 +1,96 @@
+use crate::geometry::Vec3f;
+use std::f32::consts::FRAC_PI_2;
+
+/// Field of view of a camera, in radians.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fov {
+    /// Angle between the top and bottom edges of the image.
+    Vertical(f32),
+    /// Angle between the left and right edges of the image.
+    Horizontal(f32),
+}
+
+/// A pinhole camera at `position` looking at `target`.
+#[derive(Debug, Clone, Copy)]
+pub struct Camera {
+    pub width: usize,
+    pub height: usize,
+    pub position: Vec3f,
+    pub target: Vec3f,
+    /// Direction that appears upwards in the image. It need not be perpendicular to the view
+    /// direction, but must not be parallel to it.
+    pub up: Vec3f,
+    pub fov: Fov,
+    /// Width-to-height ratio of the image plane, `None` for square pixels.
+    pub aspect: Option<f32>,
+}
+
+impl Camera {
+    /// A camera at the origin looking down -Z with a vertical field of view of 90°.
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            position: Vec3f::new(0.0, 0.0, 0.0),
+            target: Vec3f::new(0.0, 0.0, -1.0),
+            up: Vec3f::new(0.0, 1.0, 0.0),
+            fov: Fov::Vertical(FRAC_PI_2),
+            aspect: None,
+        }
+    }
+
+    pub fn look_at(mut self, position: Vec3f, target: Vec3f, up: Vec3f) -> Self {
+        self.position = position;
+        self.target = target;
+        self.up = up;
+        self
+    }
+
+    /// Returns the camera moved around `target` by `angle` radians counter-clockwise about the
+    /// `up` axis, as for a turntable.
+    ///
+    /// ```
+    /// use std::f32::consts::PI;
+    /// use tinyraytracer::{Camera, Vec3f};
+    ///
+    /// let camera = Camera::new(4, 3).look_at(
+    ///     Vec3f::new(0.0, 0.0, 0.0),
+    ///     Vec3f::new(0.0, 0.0, -10.0),
+    ///     Vec3f::new(0.0, 1.0, 0.0),
+    /// );
+    /// let behind = camera.orbit(PI).position;
+    /// assert!((behind - Vec3f::new(0.0, 0.0, -20.0)).norm() < 1e-5);
+    /// ```
+    pub fn orbit(mut self, angle: f32) -> Self {
+        let axis = self.up.normalize();
+        let v = self.position - self.target;
+        let (sin, cos) = angle.sin_cos();
+        // Rodrigues' rotation formula.
+        let rotated = v * cos + axis.cross(v) * sin + axis * ((axis * v) * (1f32 - cos));
+        self.position = self.target + rotated;
+        self
+    }
+
+    /// Returns the origin and normalized direction of the ray through the point (`x`, `y`) of
+    /// the image, measured in pixels from the top left corner.
+    pub fn ray(&self, x: f32, y: f32) -> (Vec3f, Vec3f) {
+        let w = self.width as f32;
+        let h = self.height as f32;
+        let forward = (self.target - self.position).normalize();
+        let right = forward.cross(self.up).normalize();
+        let up = right.cross(forward);
+
+        // Work in pixel units, stretching x if the image plane is not shaped like the image.
+        let stretch = self.aspect.map_or(1f32, |aspect| aspect * h / w);
+        let dir_x = (x - w / 2f32) * stretch;
+        let dir_y = -y + h / 2f32;
+        let dir_z = match self.fov {
+            Fov::Vertical(fov) => h / (2f32 * (fov / 2f32).tan()),
+            Fov::Horizontal(fov) => w * stretch / (2f32 * (fov / 2f32).tan()),
+        };
+        (
+            self.position,
+            (right * dir_x + up * dir_y + forward * dir_z).normalize(),
+        )
+    }
+}
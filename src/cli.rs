@@ -0,0 +1,393 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tinyraytracer::sampling::{
+    BlueNoise, BoxFilter, Filter, GaussianFilter, Grid, Halton, MitchellFilter, Sampler, Sobol,
+    Stratified, TentFilter,
+};
+use tinyraytracer::tonemap::{Encoding, Operator};
+use tinyraytracer::{Integrator, Vec3f};
+
+pub const USAGE: &str = "\
+Usage: tinyraytracer [OPTIONS] [SCENE]
+
+Renders SCENE (a JSON scene file) to an image. Without SCENE the built-in
+default scene is rendered. Options override the values from the scene file.
+
+Options:
+  -s, --scene <PATH>        Scene file to render
+  -o, --output <PATH>       Output image path, `-` for PNG on stdout; may be
+                            repeated, the format is chosen by the extension
+                            (.png, .ppm, or .pfm, .hdr, .exr for linear
+                            high dynamic range values) [default: image.png]
+      --width <PIXELS>      Image width
+      --height <PIXELS>     Image height
+      --fov <DEGREES>       Vertical field of view, between 0 and 180
+      --hfov <DEGREES>      Horizontal field of view, between 0 and 180
+      --eye <X,Y,Z>         Camera position
+      --target <X,Y,Z>      Point the camera looks at
+      --up <X,Y,Z>          Upwards direction of the camera
+      --turntable <FRAMES>  Render FRAMES views orbiting the target; output
+                            files are numbered, e.g. image_000.png
+  -d, --max-depth <N>       Maximum reflection/refraction depth [default: 4]
+      --background <R,G,B>  Color of rays that miss the scene, e.g. 0.2,0.7,0.8,
+                            replacing an environment of the scene file
+      --environment <PATH>  Equirectangular .hdr or .pfm image surrounding and
+                            lighting the scene, instead of a background color
+      --integrator <NAME>   Light transport: whitted or path [default: whitted]
+      --samples <N>         Camera rays per pixel [default: 1]
+      --sampler <NAME>      Placement of the samples within a pixel: grid,
+                            stratified, halton, sobol or blue-noise
+                            [default: grid]
+      --filter <NAME>       Reconstruction filter: box, tent, gaussian or
+                            mitchell [default: box]
+      --shadow-samples <N>  Shadow rays per area light and towards the
+                            environment, overriding the scene
+      --tonemap <NAME>      Tone mapping for 8-bit images: normalize, clamp,
+                            reinhard, extended-reinhard or aces
+                            [default: normalize]
+      --white <LUMINANCE>   Luminance mapped to white by extended-reinhard
+                            [default: 4]
+      --exposure <EV>       Exposure adjustment in stops before tone mapping
+      --encoding <NAME>     Transfer encoding for 8-bit images: linear or srgb
+                            [default: linear]
+      --dither              Dither 8-bit images to hide banding
+  -j, --threads <N>         Number of render threads [default: all cores]
+  -h, --help                Print this help
+";
+
+#[derive(Debug)]
+pub enum Error {
+    Help,
+    Usage(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Help => write!(f, "{}", USAGE),
+            Error::Usage(message) => write!(f, "{}\n\nRun with --help for usage.", message),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Options {
+    pub scene: Option<PathBuf>,
+    pub outputs: Vec<PathBuf>,
+    pub width: Option<usize>,
+    pub height: Option<usize>,
+    pub fov: Option<f32>,
+    pub hfov: Option<f32>,
+    pub eye: Option<Vec3f>,
+    pub target: Option<Vec3f>,
+    pub up: Option<Vec3f>,
+    pub turntable: Option<usize>,
+    pub max_depth: Option<usize>,
+    pub background: Option<Vec3f>,
+    pub environment: Option<PathBuf>,
+    pub threads: Option<usize>,
+    pub integrator: Option<Integrator>,
+    pub samples: Option<usize>,
+    pub sampler: Option<Arc<dyn Sampler>>,
+    pub filter: Option<Arc<dyn Filter>>,
+    pub shadow_samples: Option<usize>,
+    pub tonemap: Option<Operator>,
+    pub white: Option<f32>,
+    pub exposure: Option<f32>,
+    pub encoding: Option<Encoding>,
+    pub dither: bool,
+}
+
+/// Luminance that extended Reinhard tone mapping maps to white unless `--white` is given.
+const DEFAULT_WHITE: f32 = 4f32;
+
+fn parse_positive(name: &str, value: &str) -> Result<usize, Error> {
+    match value.parse::<usize>() {
+        Ok(v) if v > 0 => Ok(v),
+        _ => Err(Error::Usage(format!(
+            "invalid value `{}` for {}: expected a positive integer",
+            value, name
+        ))),
+    }
+}
+
+fn parse_fov(name: &str, value: &str) -> Result<f32, Error> {
+    match value.parse::<f32>() {
+        Ok(v) if v > 0f32 && v < 180f32 => Ok(v),
+        _ => Err(Error::Usage(format!(
+            "invalid value `{}` for {}: expected degrees between 0 and 180",
+            value, name
+        ))),
+    }
+}
+
+fn parse_vec3(name: &str, value: &str) -> Result<Vec3f, Error> {
+    let channels = value
+        .split(',')
+        .map(|c| c.trim().parse::<f32>().ok().filter(|c| c.is_finite()))
+        .collect::<Option<Vec<_>>>();
+    match channels.as_deref() {
+        Some(&[r, g, b]) => Ok(Vec3f::new(r, g, b)),
+        _ => Err(Error::Usage(format!(
+            "invalid value `{}` for {}: expected three comma-separated numbers",
+            value, name
+        ))),
+    }
+}
+
+fn parse_integrator(name: &str, value: &str) -> Result<Integrator, Error> {
+    match value {
+        "whitted" => Ok(Integrator::Whitted),
+        "path" => Ok(Integrator::Path),
+        _ => Err(Error::Usage(format!(
+            "invalid value `{}` for {}: expected whitted or path",
+            value, name
+        ))),
+    }
+}
+
+fn parse_sampler(name: &str, value: &str) -> Result<Arc<dyn Sampler>, Error> {
+    Ok(match value {
+        "grid" => Arc::new(Grid),
+        "stratified" => Arc::new(Stratified),
+        "halton" => Arc::new(Halton),
+        "sobol" => Arc::new(Sobol),
+        "blue-noise" => Arc::new(BlueNoise::new()),
+        _ => {
+            return Err(Error::Usage(format!(
+                "invalid value `{}` for {}: expected grid, stratified, halton, sobol or blue-noise",
+                value, name
+            )))
+        }
+    })
+}
+
+fn parse_filter(name: &str, value: &str) -> Result<Arc<dyn Filter>, Error> {
+    Ok(match value {
+        "box" => Arc::new(BoxFilter::default()),
+        "tent" => Arc::new(TentFilter::default()),
+        "gaussian" => Arc::new(GaussianFilter::default()),
+        "mitchell" => Arc::new(MitchellFilter::default()),
+        _ => {
+            return Err(Error::Usage(format!(
+                "invalid value `{}` for {}: expected box, tent, gaussian or mitchell",
+                value, name
+            )))
+        }
+    })
+}
+
+fn parse_tonemap(name: &str, value: &str) -> Result<Operator, Error> {
+    Ok(match value {
+        "normalize" => Operator::Normalize,
+        "clamp" => Operator::Clamp,
+        "reinhard" => Operator::Reinhard,
+        "extended-reinhard" => Operator::ExtendedReinhard {
+            white: DEFAULT_WHITE,
+        },
+        "aces" => Operator::Aces,
+        _ => {
+            return Err(Error::Usage(format!(
+                "invalid value `{}` for {}: expected normalize, clamp, reinhard, \
+                 extended-reinhard or aces",
+                value, name
+            )))
+        }
+    })
+}
+
+fn parse_encoding(name: &str, value: &str) -> Result<Encoding, Error> {
+    match value {
+        "linear" => Ok(Encoding::Linear),
+        "srgb" => Ok(Encoding::Srgb),
+        _ => Err(Error::Usage(format!(
+            "invalid value `{}` for {}: expected linear or srgb",
+            value, name
+        ))),
+    }
+}
+
+fn parse_number(name: &str, value: &str, positive: bool) -> Result<f32, Error> {
+    match value.parse::<f32>() {
+        Ok(v) if v.is_finite() && (!positive || v > 0f32) => Ok(v),
+        _ => Err(Error::Usage(format!(
+            "invalid value `{}` for {}: expected a {}number",
+            value,
+            name,
+            if positive { "positive " } else { "" }
+        ))),
+    }
+}
+
+impl Options {
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self, Error> {
+        let mut options = Options::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if !arg.starts_with('-') {
+                if options.scene.is_some() {
+                    return Err(Error::Usage(format!("unexpected argument `{}`", arg)));
+                }
+                options.scene = Some(arg.into());
+                continue;
+            }
+            let (name, inline) = match arg.find('=') {
+                Some(i) if arg.starts_with("--") => (&arg[..i], Some(arg[i + 1..].to_string())),
+                _ => (arg.as_str(), None),
+            };
+            match name {
+                "-h" | "--help" => return Err(Error::Help),
+                "--dither" if inline.is_none() => {
+                    options.dither = true;
+                    continue;
+                }
+                "--dither" => return Err(Error::Usage(format!("{} does not take a value", name))),
+                "-s" | "--scene" | "-o" | "--output" | "--width" | "--height" | "--fov" | "-d"
+                | "--max-depth" | "--background" | "-j" | "--threads" | "--hfov" | "--eye"
+                | "--target" | "--up" | "--turntable" | "--integrator" | "--samples"
+                | "--sampler" | "--filter" | "--shadow-samples" | "--environment" | "--tonemap"
+                | "--white" | "--exposure" | "--encoding" => {}
+                _ => return Err(Error::Usage(format!("unknown option `{}`", name))),
+            }
+            let value = match inline.or_else(|| args.next()) {
+                Some(value) => value,
+                None => return Err(Error::Usage(format!("missing value for {}", name))),
+            };
+            match name {
+                "-s" | "--scene" if options.scene.is_some() => {
+                    return Err(Error::Usage(format!("{} given after a scene", name)))
+                }
+                "-s" | "--scene" => options.scene = Some(value.into()),
+                "-o" | "--output" => options.outputs.push(value.into()),
+                "--width" => options.width = Some(parse_positive(name, &value)?),
+                "--height" => options.height = Some(parse_positive(name, &value)?),
+                "--fov" => options.fov = Some(parse_fov(name, &value)?),
+                "-d" | "--max-depth" => {
+                    options.max_depth = Some(value.parse().map_err(|_| {
+                        Error::Usage(format!(
+                            "invalid value `{}` for {}: expected a non-negative integer",
+                            value, name
+                        ))
+                    })?)
+                }
+                "--background" => options.background = Some(parse_vec3(name, &value)?),
+                "--environment" => options.environment = Some(value.into()),
+                "--hfov" => options.hfov = Some(parse_fov(name, &value)?),
+                "--eye" => options.eye = Some(parse_vec3(name, &value)?),
+                "--target" => options.target = Some(parse_vec3(name, &value)?),
+                "--up" => options.up = Some(parse_vec3(name, &value)?),
+                "--turntable" => options.turntable = Some(parse_positive(name, &value)?),
+                "-j" | "--threads" => options.threads = Some(parse_positive(name, &value)?),
+                "--integrator" => options.integrator = Some(parse_integrator(name, &value)?),
+                "--samples" => options.samples = Some(parse_positive(name, &value)?),
+                "--sampler" => options.sampler = Some(parse_sampler(name, &value)?),
+                "--filter" => options.filter = Some(parse_filter(name, &value)?),
+                "--shadow-samples" => options.shadow_samples = Some(parse_positive(name, &value)?),
+                "--tonemap" => options.tonemap = Some(parse_tonemap(name, &value)?),
+                "--white" => options.white = Some(parse_number(name, &value, true)?),
+                "--exposure" => options.exposure = Some(parse_number(name, &value, false)?),
+                "--encoding" => options.encoding = Some(parse_encoding(name, &value)?),
+                _ => unreachable!(),
+            }
+        }
+        if options.fov.is_some() && options.hfov.is_some() {
+            return Err(Error::Usage("--fov and --hfov cannot be combined".into()));
+        }
+        if options.background.is_some() && options.environment.is_some() {
+            return Err(Error::Usage(
+                "--background and --environment cannot be combined".into(),
+            ));
+        }
+        if options.white.is_some()
+            && !matches!(options.tonemap, Some(Operator::ExtendedReinhard { .. }))
+        {
+            return Err(Error::Usage(
+                "--white requires --tonemap extended-reinhard".into(),
+            ));
+        }
+        Ok(options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, Error> {
+        Options::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    /// Asserts that parsing `args` fails with a usage message starting with `message`.
+    fn assert_usage(args: &[&str], message: &str) {
+        match parse(args) {
+            Err(Error::Usage(text)) => assert!(text.starts_with(message), "{}", text),
+            Err(Error::Help) => panic!("{:?} asked for help", args),
+            Ok(_) => panic!("parsed {:?}", args),
+        }
+    }
+
+    #[test]
+    fn takes_values_inline_or_separately() {
+        let options = parse(&[
+            "--width=320",
+            "--height",
+            "240",
+            "--fov=1.5",
+            "-s",
+            "a.json",
+        ])
+        .unwrap();
+        assert_eq!(options.width, Some(320));
+        assert_eq!(options.height, Some(240));
+        assert_eq!(options.fov, Some(1.5));
+        assert_eq!(options.scene, Some(PathBuf::from("a.json")));
+        let options = parse(&["--scene=a.json", "--background=0,0.5,1"]).unwrap();
+        assert_eq!(options.scene, Some(PathBuf::from("a.json")));
+        assert_eq!(options.background, Some(Vec3f::new(0.0, 0.5, 1.0)));
+        assert!(parse(&["--dither"]).unwrap().dither);
+        assert_usage(&["--dither=yes"], "--dither does not take a value");
+        assert_usage(&["--width"], "missing value for --width");
+        assert_usage(&["--width=0"], "invalid value `0` for --width");
+        assert_usage(&["--depth=3"], "unknown option `--depth`");
+    }
+
+    #[test]
+    fn rejects_a_second_scene() {
+        assert_usage(&["a.json", "b.json"], "unexpected argument `b.json`");
+        assert_usage(
+            &["--scene", "a.json", "b.json"],
+            "unexpected argument `b.json`",
+        );
+        assert_usage(
+            &["a.json", "--scene", "b.json"],
+            "--scene given after a scene",
+        );
+        assert_usage(
+            &["-s", "a.json", "--scene=b.json"],
+            "--scene given after a scene",
+        );
+    }
+
+    #[test]
+    fn rejects_conflicting_options() {
+        assert_usage(
+            &["--fov=1", "--hfov=1"],
+            "--fov and --hfov cannot be combined",
+        );
+        assert_usage(
+            &["--environment=sky.hdr", "--background=0,0,0"],
+            "--background and --environment cannot be combined",
+        );
+        assert_usage(
+            &["--white=2"],
+            "--white requires --tonemap extended-reinhard",
+        );
+        assert_usage(
+            &["--tonemap=aces", "--white=2"],
+            "--white requires --tonemap extended-reinhard",
+        );
+        let options = parse(&["--white=2", "--tonemap=extended-reinhard"]).unwrap();
+        assert_eq!(options.white, Some(2.0));
+    }
+}
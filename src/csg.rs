@@ -0,0 +1,287 @@
+//! Constructive solid geometry: solids combined by union, intersection and difference.
+//!
+//! Operands are solids, shapes whose [`Shape::spans`] list where a line is inside them: spheres,
+//! the closed [`primitive`](crate::primitive)s, instances and groups of solids, and other
+//! combinations. Combining works on these spans, so a
+//! ray starting inside a combination, as refracted rays do, finds where it leaves.
+
+use crate::geometry::{Aabb, Vec3f};
+use crate::shape::{self, Hit, Shape, Span};
+use std::sync::Arc;
+
+/// How a [`Csg`] combines its operands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    /// Inside either operand.
+    Union,
+    /// Inside both operands.
+    Intersection,
+    /// Inside the left operand but not the right one, which carves it.
+    Difference,
+}
+
+impl Operation {
+    fn inside(self, left: bool, right: bool) -> bool {
+        match self {
+            Operation::Union => left || right,
+            Operation::Intersection => left && right,
+            Operation::Difference => left && !right,
+        }
+    }
+}
+
+/// Merges two lists of spans along the same line by `op`.
+///
+/// Each boundary keeps the hit, and so the material, of the operand it belongs to. Surfaces of the
+/// right operand bounding a difference face into that operand, so their normals are flipped.
+pub(crate) fn combine<'a>(
+    op: Operation,
+    left: Vec<Span<'a>>,
+    right: Vec<Span<'a>>,
+) -> Vec<Span<'a>> {
+    // Each boundary, with whether it is on the right operand and whether the line enters there.
+    let mut events = Vec::with_capacity(2 * (left.len() + right.len()));
+    for (spans, is_right) in [(left, false), (right, true)] {
+        for span in spans {
+            events.push((span.enter, is_right, true));
+            events.push((span.exit, is_right, false));
+        }
+    }
+    events.sort_by(|a, b| a.0.t.total_cmp(&b.0.t));
+
+    let mut spans = Vec::new();
+    let (mut in_left, mut in_right) = (false, false);
+    let mut enter: Option<Hit> = None;
+    for (mut hit, is_right, entering) in events {
+        let was_inside = op.inside(in_left, in_right);
+        if is_right {
+            in_right = entering;
+        } else {
+            in_left = entering;
+        }
+        let inside = op.inside(in_left, in_right);
+        if inside == was_inside {
+            continue;
+        }
+        if is_right && op == Operation::Difference {
+            hit.normal = -hit.normal;
+            hit.geometric_normal = -hit.geometric_normal;
+        }
+        if inside {
+            enter = Some(hit);
+        } else if let Some(enter) = enter.take() {
+            spans.push(Span { enter, exit: hit });
+        }
+    }
+    spans
+}
+
+/// Two solids combined by an [`Operation`].
+///
+/// ```
+/// use std::sync::Arc;
+/// use tinyraytracer::csg::{Csg, Operation};
+/// use tinyraytracer::{Material, Shape, Sphere, Vec3f};
+///
+/// let ball = Arc::new(Sphere::new(Vec3f::new(0.0, 0.0, -10.0), 2.0, Material::default()));
+/// let bite = Arc::new(Sphere::new(Vec3f::new(0.0, 0.0, -8.0), 1.0, Material::default()));
+/// let bitten = Csg::new(Operation::Difference, ball, bite);
+///
+/// // The ray passes through the bite and hits the carved surface, which faces the ray.
+/// let hit = bitten
+///     .intersect(Vec3f::default(), Vec3f::new(0.0, 0.0, -1.0), 100.0)
+///     .unwrap();
+/// assert!((hit.t - 9.0).abs() < 1e-5);
+/// assert!((hit.normal - Vec3f::new(0.0, 0.0, 1.0)).norm() < 1e-5);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Csg {
+    pub operation: Operation,
+    left: Arc<dyn Shape>,
+    right: Arc<dyn Shape>,
+}
+
+impl Csg {
+    /// Combines two solids. Operands that are not solids are treated as empty.
+    pub fn new(operation: Operation, left: Arc<dyn Shape>, right: Arc<dyn Shape>) -> Self {
+        Self {
+            operation,
+            left,
+            right,
+        }
+    }
+
+    pub fn left(&self) -> &Arc<dyn Shape> {
+        &self.left
+    }
+
+    pub fn right(&self) -> &Arc<dyn Shape> {
+        &self.right
+    }
+}
+
+impl Shape for Csg {
+    fn bounds(&self) -> Option<Aabb> {
+        let (left, right) = (self.left.bounds(), self.right.bounds());
+        match self.operation {
+            Operation::Union => Some(left?.union(right?)),
+            Operation::Intersection => match (left, right) {
+                (Some(left), Some(right)) => Some(left.intersection(right)),
+                (left, right) => left.or(right),
+            },
+            Operation::Difference => left,
+        }
+    }
+
+    fn intersect(&self, orig: Vec3f, dir: Vec3f, t_max: f32) -> Option<Hit<'_>> {
+        shape::nearest_boundary(self.spans(orig, dir)?, t_max)
+    }
+
+    fn spans(&self, orig: Vec3f, dir: Vec3f) -> Option<Vec<Span<'_>>> {
+        let left = self.left.spans(orig, dir).unwrap_or_default();
+        if left.is_empty() && self.operation != Operation::Union {
+            return Some(left);
+        }
+        let right = self.right.spans(orig, dir).unwrap_or_default();
+        Some(combine(self.operation, left, right))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::Material;
+    use crate::shape::Sphere;
+
+    /// A unit ball centered on the x axis.
+    fn ball(x: f32) -> Arc<dyn Shape> {
+        Arc::new(Sphere::new(
+            Vec3f::new(x, 0.0, 0.0),
+            1.0,
+            Material::default(),
+        ))
+    }
+
+    /// The entry and exit distances of `shape` along the x axis, from x = `start`.
+    fn spans(shape: &dyn Shape, start: f32) -> Vec<(f32, f32)> {
+        shape
+            .spans(Vec3f::new(start, 0.0, 0.0), Vec3f::new(1.0, 0.0, 0.0))
+            .unwrap()
+            .iter()
+            .map(|span| (span.enter.t, span.exit.t))
+            .collect()
+    }
+
+    fn assert_spans(actual: Vec<(f32, f32)>, expected: &[(f32, f32)]) {
+        assert_eq!(
+            actual.len(),
+            expected.len(),
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+        for (a, e) in actual.iter().zip(expected) {
+            assert!(
+                (a.0 - e.0).abs() < 1e-4 && (a.1 - e.1).abs() < 1e-4,
+                "{:?} != {:?}",
+                actual,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn overlapping() {
+        let (left, right) = (ball(0.0), ball(1.5));
+        let union = Csg::new(Operation::Union, left.clone(), right.clone());
+        assert_spans(spans(&union, -5.0), &[(4.0, 7.5)]);
+        let intersection = Csg::new(Operation::Intersection, left.clone(), right.clone());
+        assert_spans(spans(&intersection, -5.0), &[(5.5, 6.0)]);
+        let difference = Csg::new(Operation::Difference, left, right);
+        assert_spans(spans(&difference, -5.0), &[(4.0, 5.5)]);
+    }
+
+    #[test]
+    fn touching() {
+        let (left, right) = (ball(0.0), ball(2.0));
+        // Whether the union is split where the balls touch, it covers both of them.
+        let union = spans(
+            &Csg::new(Operation::Union, left.clone(), right.clone()),
+            -5.0,
+        );
+        assert!((union[0].0 - 4.0).abs() < 1e-4);
+        assert!((union[union.len() - 1].1 - 8.0).abs() < 1e-4);
+        let length = union.iter().map(|(enter, exit)| exit - enter).sum::<f32>();
+        assert!((length - 4.0).abs() < 1e-4);
+        let intersection = Csg::new(Operation::Intersection, left.clone(), right.clone());
+        assert!(spans(&intersection, -5.0)
+            .iter()
+            .all(|(enter, exit)| exit - enter < 1e-4));
+        let difference = Csg::new(Operation::Difference, left, right);
+        assert_spans(spans(&difference, -5.0), &[(4.0, 6.0)]);
+    }
+
+    #[test]
+    fn disjoint() {
+        let (left, right) = (ball(0.0), ball(3.0));
+        let union = Csg::new(Operation::Union, left.clone(), right.clone());
+        assert_spans(spans(&union, -5.0), &[(4.0, 6.0), (7.0, 9.0)]);
+        let intersection = Csg::new(Operation::Intersection, left.clone(), right.clone());
+        assert_spans(spans(&intersection, -5.0), &[]);
+        assert!(intersection
+            .intersect(
+                Vec3f::new(-5.0, 0.0, 0.0),
+                Vec3f::new(1.0, 0.0, 0.0),
+                f32::MAX
+            )
+            .is_none());
+        let difference = Csg::new(Operation::Difference, left, right);
+        assert_spans(spans(&difference, -5.0), &[(4.0, 6.0)]);
+    }
+
+    #[test]
+    fn carved_surface_faces_out_of_the_difference() {
+        let difference = Csg::new(Operation::Difference, ball(0.0), ball(1.5));
+        let spans = difference
+            .spans(Vec3f::new(-5.0, 0.0, 0.0), Vec3f::new(1.0, 0.0, 0.0))
+            .unwrap();
+        let exit = spans[0].exit;
+        assert!((exit.normal - Vec3f::new(1.0, 0.0, 0.0)).norm() < 1e-5);
+        assert!((exit.geometric_normal - Vec3f::new(1.0, 0.0, 0.0)).norm() < 1e-5);
+    }
+
+    #[test]
+    fn origin_inside() {
+        let (left, right) = (ball(0.0), ball(1.5));
+        let dir = Vec3f::new(1.0, 0.0, 0.0);
+        // From inside the left ball only, the difference is left where the right ball starts.
+        let difference = Csg::new(Operation::Difference, left.clone(), right.clone());
+        let hit = difference
+            .intersect(Vec3f::default(), dir, f32::MAX)
+            .unwrap();
+        assert!((hit.t - 0.5).abs() < 1e-4);
+        // From inside both, the union is left where the right ball ends.
+        let union = Csg::new(Operation::Union, left.clone(), right.clone());
+        let hit = union
+            .intersect(Vec3f::new(0.75, 0.0, 0.0), dir, f32::MAX)
+            .unwrap();
+        assert!((hit.t - 1.75).abs() < 1e-4);
+        // And the intersection where the left ball ends.
+        let intersection = Csg::new(Operation::Intersection, left, right);
+        let hit = intersection
+            .intersect(Vec3f::new(0.75, 0.0, 0.0), dir, f32::MAX)
+            .unwrap();
+        assert!((hit.t - 0.25).abs() < 1e-4);
+    }
+
+    #[test]
+    fn nested() {
+        // Three balls in a row, the middle one carved out of the union of the outer two, which is
+        // then cut down to where it overlaps a fourth ball.
+        let outer = Arc::new(Csg::new(Operation::Union, ball(-1.5), ball(1.5)));
+        let carved = Arc::new(Csg::new(Operation::Difference, outer, ball(0.0)));
+        assert_spans(spans(&*carved, -5.0), &[(2.5, 4.0), (6.0, 7.5)]);
+        let twice = Csg::new(Operation::Intersection, carved, ball(1.0));
+        assert_spans(spans(&twice, -5.0), &[(6.0, 7.0)]);
+    }
+}
@@ -0,0 +1,181 @@
+//! Image-based lighting from an equirectangular environment map.
+
+use crate::framebuffer::Framebuffer;
+use crate::geometry::Vec3f;
+use std::f32::consts::PI;
+
+/// Light arriving from infinitely far away in every direction, looked up in a latitude-longitude
+/// image.
+///
+/// The top row of the image is straight up (+y) and its center looks down -z, the default view
+/// direction of the camera. Directions are importance sampled in proportion to the brightness of
+/// the image, so that small bright regions such as the sun are found by few shadow rays.
+///
+/// ```
+/// use tinyraytracer::environment::Environment;
+/// use tinyraytracer::{Framebuffer, Vec3f};
+///
+/// // A dark sky with one bright pixel in the middle.
+/// let mut image = Framebuffer::new(8, 4);
+/// for row in 0..4 {
+///     image[row].fill(Vec3f::new(0.1, 0.1, 0.1));
+/// }
+/// image[1][4] = Vec3f::new(100.0, 100.0, 100.0);
+/// let sky = Environment::new(image);
+///
+/// assert_eq!(sky.radiance(Vec3f::new(0.0, -1.0, 0.0)).0, [0.1; 3]);
+/// let (direction, radiance, pdf) = sky.sample([0.3, 0.4]).unwrap();
+/// assert_eq!(radiance.0, [100.0; 3]);
+/// assert!((sky.pdf(direction) - pdf).abs() < 1e-3 * pdf);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Environment {
+    image: Framebuffer,
+    /// Multiplies the radiance of the image.
+    pub intensity: f32,
+    /// Rotation of the image about the vertical axis, in radians.
+    pub rotation: f32,
+    /// Number of shadow rays traced towards the environment from each shaded point.
+    pub samples: usize,
+    /// Sampling weight of each pixel, its luminance times the solid angle it covers.
+    weights: Vec<f32>,
+    /// Cumulative distribution over the rows, with `height + 1` entries.
+    rows: Vec<f32>,
+    /// Cumulative distribution over the pixels of each row, with `width + 1` entries per row.
+    columns: Vec<f32>,
+    /// Sum of `weights`.
+    total: f32,
+}
+
+impl Environment {
+    /// Shadow rays traced towards the environment unless configured otherwise.
+    pub const DEFAULT_SAMPLES: usize = 16;
+
+    pub fn new(image: Framebuffer) -> Self {
+        let (width, height) = (image.width(), image.height());
+        let mut weights = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let sin = (PI * (y as f32 + 0.5) / height as f32).sin();
+            weights.extend(image[y].iter().map(|p| p.luminance().max(0f32) * sin));
+        }
+        if !weights.iter().any(|&w| w > 0f32) {
+            // A black image is never sampled for its light, any distribution will do.
+            weights.fill(1f32);
+        }
+        let mut columns = Vec::with_capacity((width + 1) * height);
+        let mut row_weights = Vec::with_capacity(height);
+        for row in weights.chunks(width) {
+            row_weights.push(row.iter().sum());
+            columns.extend(cumulative(row));
+        }
+        let total = row_weights.iter().sum();
+        Self {
+            image,
+            intensity: 1f32,
+            rotation: 0f32,
+            samples: Self::DEFAULT_SAMPLES,
+            weights,
+            rows: cumulative(&row_weights),
+            columns,
+            total,
+        }
+    }
+
+    /// The pixel seen in `direction`, which need not be normalized.
+    fn pixel(&self, direction: Vec3f) -> (usize, usize) {
+        let d = rotate_y(direction.normalize(), -self.rotation);
+        let (width, height) = (self.image.width(), self.image.height());
+        let u = 0.5 + d.0[0].atan2(-d.0[2]) / (2f32 * PI);
+        let v = d.0[1].clamp(-1f32, 1f32).acos() / PI;
+        let x = ((u * width as f32) as usize).min(width - 1);
+        let y = ((v * height as f32) as usize).min(height - 1);
+        (x, y)
+    }
+
+    /// The light arriving from `direction`.
+    pub fn radiance(&self, direction: Vec3f) -> Vec3f {
+        let (x, y) = self.pixel(direction);
+        self.image[y][x] * self.intensity
+    }
+
+    /// Picks a direction with a density proportional to the brightness of the image, using the
+    /// uniform random numbers `u`. Returns the direction with the light arriving from it and the
+    /// density per unit solid angle.
+    pub fn sample(&self, u: [f32; 2]) -> Option<(Vec3f, Vec3f, f32)> {
+        let (width, height) = (self.image.width(), self.image.height());
+        let (y, fy) = search(&self.rows, u[1]);
+        let (x, fx) = search(&self.columns[y * (width + 1)..(y + 1) * (width + 1)], u[0]);
+        let theta = PI * (y as f32 + fy) / height as f32;
+        let phi = 2f32 * PI * ((x as f32 + fx) / width as f32 - 0.5);
+        let sin = theta.sin();
+        if sin <= 0f32 {
+            return None;
+        }
+        let pdf = self.density(x, y, sin);
+        if pdf <= 0f32 {
+            return None;
+        }
+        let d = Vec3f::new(sin * phi.sin(), theta.cos(), -sin * phi.cos());
+        Some((
+            rotate_y(d, self.rotation),
+            self.image[y][x] * self.intensity,
+            pdf,
+        ))
+    }
+
+    /// The density with which [`sample`](Self::sample) picks `direction`.
+    pub fn pdf(&self, direction: Vec3f) -> f32 {
+        let (x, y) = self.pixel(direction);
+        let d = direction.normalize();
+        let sin = (1f32 - d.0[1] * d.0[1]).max(0f32).sqrt();
+        if sin <= 0f32 {
+            return 0f32;
+        }
+        self.density(x, y, sin)
+    }
+
+    /// The density per solid angle of pixel (`x`, `y`) at polar angle sine `sin`.
+    fn density(&self, x: usize, y: usize, sin: f32) -> f32 {
+        let pixels = self.weights.len() as f32;
+        let area = self.weights[y * self.image.width() + x] * pixels / self.total;
+        area / (2f32 * PI * PI * sin)
+    }
+}
+
+/// The normalized cumulative sums of `weights`, starting at 0.
+fn cumulative(weights: &[f32]) -> Vec<f32> {
+    let total: f32 = weights.iter().sum();
+    let mut cdf = Vec::with_capacity(weights.len() + 1);
+    let mut sum = 0f32;
+    cdf.push(0f32);
+    for (i, w) in weights.iter().enumerate() {
+        sum += w;
+        cdf.push(if total > 0f32 {
+            sum / total
+        } else {
+            (i + 1) as f32 / weights.len() as f32
+        });
+    }
+    cdf
+}
+
+/// Finds the interval of `cdf` containing `u`, returning its index and the position of `u` within
+/// it.
+fn search(cdf: &[f32], u: f32) -> (usize, f32) {
+    let last = cdf.len() - 2;
+    let i = cdf.partition_point(|&c| c <= u).clamp(1, last + 1) - 1;
+    let width = cdf[i + 1] - cdf[i];
+    let offset = if width > 0f32 {
+        ((u - cdf[i]) / width).clamp(0f32, 1f32)
+    } else {
+        0.5
+    };
+    (i, offset)
+}
+
+/// Rotates `v` by `angle` radians about the y axis.
+fn rotate_y(v: Vec3f, angle: f32) -> Vec3f {
+    let (sin, cos) = angle.sin_cos();
+    let [x, y, z] = v.0;
+    Vec3f::new(cos * x + sin * z, y, -sin * x + cos * z)
+}
@@ -0,0 +1,50 @@
+use crate::geometry::Vec3f;
+use std::ops::{Index, IndexMut};
+
+/// A row-major grid of linear RGB radiance values.
+///
+/// Values are not clamped; use the [`output`](crate::output) module to encode them into an image.
+#[derive(Debug, Clone)]
+pub struct Framebuffer {
+    pixels: Vec<Vec3f>,
+    width: usize,
+}
+
+impl Index<usize> for Framebuffer {
+    type Output = [Vec3f];
+
+    #[inline]
+    fn index(&self, index: usize) -> &Self::Output {
+        let i = index * self.width;
+        &self.pixels[i..i + self.width]
+    }
+}
+
+impl IndexMut<usize> for Framebuffer {
+    #[inline]
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        let i = index * self.width;
+        &mut self.pixels[i..i + self.width]
+    }
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            pixels: vec![Vec3f::default(); width * height],
+            width,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.pixels.len() / self.width
+    }
+
+    pub fn pixels(&self) -> &[Vec3f] {
+        &self.pixels
+    }
+}
@@ -1,6 +1,7 @@
 use std::ops::{Add, Mul, Neg, Sub};
 
-#[derive(Debug, Default, Clone, Copy)]
+/// A three-component vector, used for points, directions and RGB colors alike.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
 pub struct Vec3f(pub [f32; 3]);
 
 impl Add for Vec3f {
@@ -57,9 +58,7 @@ impl Vec3f {
     }
 
     pub fn as_bytes(&self) -> impl Iterator<Item = u8> + '_ {
-        self.0
-            .iter()
-            .map(|b| (b.min(1f32).max(0f32) * 255f32) as u8)
+        self.0.iter().map(|b| (b.clamp(0f32, 1f32) * 255f32) as u8)
     }
 
     pub fn norm(self) -> f32 {
@@ -70,12 +69,64 @@ impl Vec3f {
         self * (1f32 / self.norm())
     }
 
+    pub fn cross(self, other: Self) -> Self {
+        Self([
+            self.0[1] * other.0[2] - self.0[2] * other.0[1],
+            self.0[2] * other.0[0] - self.0[0] * other.0[2],
+            self.0[0] * other.0[1] - self.0[1] * other.0[0],
+        ])
+    }
+
+    /// Multiplies the components pairwise, e.g. to filter a color by another.
+    pub fn component_mul(self, other: Self) -> Self {
+        Self([
+            self.0[0] * other.0[0],
+            self.0[1] * other.0[1],
+            self.0[2] * other.0[2],
+        ])
+    }
+
+    /// The larger of each component of `self` and `other`.
+    pub fn max(self, other: Self) -> Self {
+        Self::new(
+            self.0[0].max(other.0[0]),
+            self.0[1].max(other.0[1]),
+            self.0[2].max(other.0[2]),
+        )
+    }
+
+    pub fn max_component(self) -> f32 {
+        self.0[0].max(self.0[1]).max(self.0[2])
+    }
+
+    /// Relative luminance of a linear RGB color.
+    pub fn luminance(self) -> f32 {
+        0.2126 * self.0[0] + 0.7152 * self.0[1] + 0.0722 * self.0[2]
+    }
+
+    /// Returns two unit vectors that together with this unit vector form an orthonormal basis.
+    pub fn basis(self) -> (Self, Self) {
+        // Duff et al., "Building an Orthonormal Basis, Revisited".
+        let [x, y, z] = self.0;
+        let sign = 1f32.copysign(z);
+        let a = -1f32 / (sign + z);
+        let b = x * y * a;
+        (
+            Vec3f::new(1f32 + sign * x * x * a, sign * b, -sign * x),
+            Vec3f::new(b, sign + y * y * a, -y),
+        )
+    }
+
     pub fn reflect(self, p: Self) -> Self {
         self - p * 2f32 * (self * p)
     }
 
-    pub fn refract(self, p: Self, mut etat: f32) -> Self {
-        let mut cosi = -(self * p).min(1f32).max(-1f32);
+    /// Refracts the unit vector through a surface with normal `p` into a medium with refractive
+    /// index `etat`, or out of it into vacuum if the vector leaves through the back of the surface.
+    ///
+    /// Returns `None` on total internal reflection.
+    pub fn refract(self, p: Self, mut etat: f32) -> Option<Self> {
+        let mut cosi = -(self * p).clamp(-1f32, 1f32);
         let mut etai = 1f32;
         let n = if cosi < 0f32 {
             cosi = -cosi;
@@ -87,9 +138,255 @@ impl Vec3f {
         let eta = etai / etat;
         let k = 1f32 - eta * eta * (1f32 - cosi * cosi);
         if k < 0f32 {
-            Vec3f::new(0.0, 0.0, 0.0)
+            None
+        } else {
+            Some(self * eta + n * (eta * cosi - k.sqrt()))
+        }
+    }
+}
+
+/// An axis-aligned bounding box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3f,
+    pub max: Vec3f,
+}
+
+impl Default for Aabb {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl Aabb {
+    pub fn new(min: Vec3f, max: Vec3f) -> Self {
+        Self { min, max }
+    }
+
+    /// A box containing nothing, the identity for [`Aabb::union`].
+    pub fn empty() -> Self {
+        Self {
+            min: Vec3f::new(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+            max: Vec3f::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+        }
+    }
+
+    pub fn union(self, other: Self) -> Self {
+        Self {
+            min: Vec3f::new(
+                self.min.0[0].min(other.min.0[0]),
+                self.min.0[1].min(other.min.0[1]),
+                self.min.0[2].min(other.min.0[2]),
+            ),
+            max: Vec3f::new(
+                self.max.0[0].max(other.max.0[0]),
+                self.max.0[1].max(other.max.0[1]),
+                self.max.0[2].max(other.max.0[2]),
+            ),
+        }
+    }
+
+    /// The box common to both, which is inverted (and hit by no ray) if they do not overlap.
+    pub fn intersection(self, other: Self) -> Self {
+        Self {
+            min: Vec3f::new(
+                self.min.0[0].max(other.min.0[0]),
+                self.min.0[1].max(other.min.0[1]),
+                self.min.0[2].max(other.min.0[2]),
+            ),
+            max: Vec3f::new(
+                self.max.0[0].min(other.max.0[0]),
+                self.max.0[1].min(other.max.0[1]),
+                self.max.0[2].min(other.max.0[2]),
+            ),
+        }
+    }
+
+    pub fn grow(self, p: Vec3f) -> Self {
+        self.union(Self { min: p, max: p })
+    }
+
+    pub fn centroid(&self) -> Vec3f {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        let d = self.max - self.min;
+        if d.0.iter().any(|&e| e < 0f32) {
+            return 0f32;
+        }
+        2f32 * (d.0[0] * d.0[1] + d.0[1] * d.0[2] + d.0[2] * d.0[0])
+    }
+
+    /// Returns the distance at which the ray enters the box, if it does so before `t_max`.
+    ///
+    /// `inv_dir` holds the reciprocals of the ray direction components.
+    pub fn ray_intersect(&self, orig: Vec3f, inv_dir: Vec3f, t_max: f32) -> Option<f32> {
+        let mut t0 = 0f32;
+        let mut t1 = t_max;
+        for axis in 0..3 {
+            let near = (self.min.0[axis] - orig.0[axis]) * inv_dir.0[axis];
+            let far = (self.max.0[axis] - orig.0[axis]) * inv_dir.0[axis];
+            let (near, far) = if near > far { (far, near) } else { (near, far) };
+            // Widen the far distance a little so rounding never misses a grazing hit.
+            let far = far * 1.000_000_4;
+            // Written so that NaNs (0 * inf) leave the interval unchanged.
+            t0 = if near > t0 { near } else { t0 };
+            t1 = if far < t1 { far } else { t1 };
+            if t0 > t1 {
+                return None;
+            }
+        }
+        Some(t0)
+    }
+}
+
+/// A 4x4 matrix in row-major order, used for affine transforms of points and vectors.
+///
+/// Matrices compose by multiplication, with the right-hand transform applied first:
+///
+/// ```
+/// use std::f32::consts::FRAC_PI_2;
+/// use tinyraytracer::geometry::Mat4;
+/// use tinyraytracer::Vec3f;
+///
+/// let m = Mat4::translation(Vec3f::new(0.0, 0.0, -5.0))
+///     * Mat4::rotation(Vec3f::new(0.0, 1.0, 0.0), FRAC_PI_2)
+///     * Mat4::scaling(Vec3f::new(2.0, 1.0, 1.0));
+/// let p = m.transform_point(Vec3f::new(1.0, 0.0, 0.0));
+/// assert!((p - Vec3f::new(0.0, 0.0, -7.0)).norm() < 1e-6);
+///
+/// let inverse = m.inverse().unwrap();
+/// assert!((inverse.transform_point(p) - Vec3f::new(1.0, 0.0, 0.0)).norm() < 1e-6);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mat4(pub [[f32; 4]; 4]);
+
+impl Default for Mat4 {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        Self(std::array::from_fn(|i| {
+            std::array::from_fn(|j| (0..4).map(|k| self.0[i][k] * other.0[k][j]).sum())
+        }))
+    }
+}
+
+impl Mat4 {
+    pub const IDENTITY: Self = Self([
+        [1f32, 0f32, 0f32, 0f32],
+        [0f32, 1f32, 0f32, 0f32],
+        [0f32, 0f32, 1f32, 0f32],
+        [0f32, 0f32, 0f32, 1f32],
+    ]);
+
+    pub fn translation(offset: Vec3f) -> Self {
+        let mut m = Self::IDENTITY;
+        for (row, &o) in m.0.iter_mut().zip(&offset.0) {
+            row[3] = o;
+        }
+        m
+    }
+
+    /// Scales each axis by the matching component of `factors`.
+    pub fn scaling(factors: Vec3f) -> Self {
+        let mut m = Self::IDENTITY;
+        for (i, &f) in factors.0.iter().enumerate() {
+            m.0[i][i] = f;
+        }
+        m
+    }
+
+    /// Rotates by `angle` radians about `axis`, counterclockwise when the axis points at the
+    /// viewer.
+    pub fn rotation(axis: Vec3f, angle: f32) -> Self {
+        let [x, y, z] = axis.normalize().0;
+        let (sin, cos) = angle.sin_cos();
+        let c = 1f32 - cos;
+        Self([
+            [
+                cos + x * x * c,
+                x * y * c - z * sin,
+                x * z * c + y * sin,
+                0f32,
+            ],
+            [
+                y * x * c + z * sin,
+                cos + y * y * c,
+                y * z * c - x * sin,
+                0f32,
+            ],
+            [
+                z * x * c - y * sin,
+                z * y * c + x * sin,
+                cos + z * z * c,
+                0f32,
+            ],
+            [0f32, 0f32, 0f32, 1f32],
+        ])
+    }
+
+    pub fn transpose(&self) -> Self {
+        Self(std::array::from_fn(|i| {
+            std::array::from_fn(|j| self.0[j][i])
+        }))
+    }
+
+    /// The inverse matrix, `None` if the matrix is singular.
+    pub fn inverse(&self) -> Option<Self> {
+        // Gauss-Jordan elimination with partial pivoting.
+        let mut m = self.0;
+        let mut inv = Self::IDENTITY.0;
+        for col in 0..4 {
+            let pivot = (col..4).max_by(|&a, &b| m[a][col].abs().total_cmp(&m[b][col].abs()))?;
+            if m[pivot][col].abs() < 1e-12 {
+                return None;
+            }
+            m.swap(col, pivot);
+            inv.swap(col, pivot);
+            let scale = 1f32 / m[col][col];
+            for j in 0..4 {
+                m[col][j] *= scale;
+                inv[col][j] *= scale;
+            }
+            for row in 0..4 {
+                let factor = m[row][col];
+                if row == col || factor == 0f32 {
+                    continue;
+                }
+                for j in 0..4 {
+                    m[row][j] -= factor * m[col][j];
+                    inv[row][j] -= factor * inv[col][j];
+                }
+            }
+        }
+        Some(Self(inv))
+    }
+
+    /// Transforms a point, including the translation.
+    pub fn transform_point(&self, p: Vec3f) -> Vec3f {
+        let m = &self.0;
+        let row = |i: usize| m[i][0] * p.0[0] + m[i][1] * p.0[1] + m[i][2] * p.0[2] + m[i][3];
+        let w = row(3);
+        let p = Vec3f::new(row(0), row(1), row(2));
+        if w == 1f32 {
+            p
         } else {
-            self * eta + n * (eta * cosi - k.sqrt())
+            p * (1f32 / w)
         }
     }
+
+    /// Transforms a direction or offset, ignoring the translation.
+    pub fn transform_vector(&self, v: Vec3f) -> Vec3f {
+        let m = &self.0;
+        Vec3f(std::array::from_fn(|i| {
+            m[i][0] * v.0[0] + m[i][1] * v.0[1] + m[i][2] * v.0[2]
+        }))
+    }
 }
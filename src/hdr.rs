@@ -0,0 +1,248 @@
+//! Decoding of high dynamic range images: Radiance RGBE (`.hdr`) and Portable Float Map (`.pfm`).
+
+use crate::framebuffer::Framebuffer;
+use crate::geometry::Vec3f;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+
+/// An error encountered while decoding an image.
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Invalid(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        if e.kind() == io::ErrorKind::UnexpectedEof {
+            Error::Invalid("unexpected end of file".into())
+        } else {
+            Error::Io(e)
+        }
+    }
+}
+
+fn invalid<T>(message: impl Into<String>) -> Result<T, Error> {
+    Err(Error::Invalid(message.into()))
+}
+
+/// Reads the image at `path`, choosing the decoder by its extension.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Framebuffer, Error> {
+    let path = path.as_ref();
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+    let decode = match extension.as_deref() {
+        Some("hdr") | Some("pic") => read_hdr,
+        Some("pfm") => read_pfm,
+        _ => return invalid("unknown image format, expected .hdr or .pfm"),
+    };
+    decode(BufReader::new(File::open(path)?))
+}
+
+/// Reads a header line, without the line break.
+fn read_line<R: BufRead>(r: &mut R) -> Result<String, Error> {
+    let mut line = Vec::new();
+    if r.read_until(b'\n', &mut line)? == 0 {
+        return invalid("unexpected end of file");
+    }
+    if line.last() == Some(&b'\n') {
+        line.pop();
+    }
+    String::from_utf8(line).or_else(|_| invalid("invalid header"))
+}
+
+/// Decodes a Radiance RGBE image, flat or run-length encoded.
+///
+/// Only the standard `-Y height +X width` orientation is supported.
+///
+/// ```
+/// use tinyraytracer::hdr;
+///
+/// let mut file = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 1 +X 2\n".to_vec();
+/// file.extend_from_slice(&[128, 64, 0, 129, 0, 0, 0, 0]);
+/// let image = hdr::read_hdr(&file[..]).unwrap();
+/// assert_eq!((image.width(), image.height()), (2, 1));
+/// assert_eq!(image[0][0].0, [1.00390625, 0.50390625, 0.00390625]);
+/// assert_eq!(image[0][1].0, [0.0; 3]);
+/// ```
+pub fn read_hdr<R: BufRead>(mut r: R) -> Result<Framebuffer, Error> {
+    let magic = read_line(&mut r)?;
+    if magic != "#?RADIANCE" && magic != "#?RGBE" {
+        return invalid("not a Radiance HDR file");
+    }
+    loop {
+        let line = read_line(&mut r)?;
+        if line.is_empty() {
+            break;
+        }
+        if let Some(format) = line.strip_prefix("FORMAT=") {
+            if format != "32-bit_rle_rgbe" {
+                return invalid(format!("unsupported format `{}`", format));
+            }
+        }
+    }
+    let resolution = read_line(&mut r)?;
+    let (width, height) = match resolution.split_whitespace().collect::<Vec<_>>()[..] {
+        ["-Y", height, "+X", width] => match (width.parse::<usize>(), height.parse::<usize>()) {
+            (Ok(width), Ok(height)) if width > 0 && height > 0 => (width, height),
+            _ => return invalid(format!("invalid resolution `{}`", resolution)),
+        },
+        _ => return invalid(format!("unsupported orientation `{}`", resolution)),
+    };
+
+    let mut image = Framebuffer::new(width, height);
+    let mut scanline = vec![[0u8; 4]; width];
+    for y in 0..height {
+        read_scanline(&mut r, &mut scanline)?;
+        for (pixel, rgbe) in image[y].iter_mut().zip(&scanline) {
+            *pixel = rgbe_to_rgb(*rgbe);
+        }
+    }
+    Ok(image)
+}
+
+fn read_scanline<R: Read>(r: &mut R, scanline: &mut [[u8; 4]]) -> Result<(), Error> {
+    let width = scanline.len();
+    let mut first = [0u8; 4];
+    r.read_exact(&mut first)?;
+    let run_length = first[0] == 2 && first[1] == 2 && first[2] & 0x80 == 0;
+    if !run_length || !(8..0x8000).contains(&width) {
+        scanline[0] = first;
+        for pixel in &mut scanline[1..] {
+            r.read_exact(pixel)?;
+        }
+        return Ok(());
+    }
+    if (first[2] as usize) << 8 | first[3] as usize != width {
+        return invalid("scanline length does not match the image width");
+    }
+    // Each channel is stored separately, as runs of one repeated byte or literal bytes.
+    for channel in 0..4 {
+        let mut x = 0;
+        while x < width {
+            let mut count = [0u8; 1];
+            r.read_exact(&mut count)?;
+            let (count, repeat) = match count[0] {
+                c if c > 128 => (c as usize - 128, true),
+                c => (c as usize, false),
+            };
+            if count == 0 || x + count > width {
+                return invalid("corrupt run-length encoded scanline");
+            }
+            if repeat {
+                let mut value = [0u8; 1];
+                r.read_exact(&mut value)?;
+                for pixel in &mut scanline[x..x + count] {
+                    pixel[channel] = value[0];
+                }
+            } else {
+                let mut values = [0u8; 128];
+                r.read_exact(&mut values[..count])?;
+                for (pixel, value) in scanline[x..x + count].iter_mut().zip(&values) {
+                    pixel[channel] = *value;
+                }
+            }
+            x += count;
+        }
+    }
+    Ok(())
+}
+
+/// Converts a pixel with a shared exponent to linear RGB.
+fn rgbe_to_rgb([r, g, b, e]: [u8; 4]) -> Vec3f {
+    if e == 0 {
+        return Vec3f::default();
+    }
+    let scale = 2f32.powi(e as i32 - 136);
+    Vec3f::new(
+        (r as f32 + 0.5) * scale,
+        (g as f32 + 0.5) * scale,
+        (b as f32 + 0.5) * scale,
+    )
+}
+
+/// Decodes a Portable Float Map, in color (`PF`) or grayscale (`Pf`).
+///
+/// ```
+/// use tinyraytracer::hdr;
+///
+/// let mut file = b"Pf\n1 2\n-1.0\n".to_vec();
+/// file.extend_from_slice(&0.25f32.to_le_bytes());
+/// file.extend_from_slice(&4.0f32.to_le_bytes());
+/// let image = hdr::read_pfm(&file[..]).unwrap();
+/// // Rows are stored from the bottom up.
+/// assert_eq!(image[0][0].0, [4.0; 3]);
+/// assert_eq!(image[1][0].0, [0.25; 3]);
+/// ```
+pub fn read_pfm<R: BufRead>(mut r: R) -> Result<Framebuffer, Error> {
+    let channels = match read_token(&mut r)?.as_str() {
+        "PF" => 3,
+        "Pf" => 1,
+        _ => return invalid("not a PFM file"),
+    };
+    let mut dimension = || match read_token(&mut r)?.parse::<usize>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => invalid("invalid image size"),
+    };
+    let (width, height) = (dimension()?, dimension()?);
+    let little_endian = match read_token(&mut r)?.parse::<f32>() {
+        Ok(scale) if scale < 0f32 => true,
+        Ok(scale) if scale > 0f32 => false,
+        _ => return invalid("invalid scale"),
+    };
+
+    let mut image = Framebuffer::new(width, height);
+    let mut row = vec![0u8; width * channels * 4];
+    for y in (0..height).rev() {
+        r.read_exact(&mut row)?;
+        for (pixel, bytes) in image[y].iter_mut().zip(row.chunks_exact(channels * 4)) {
+            let mut values = bytes.chunks_exact(4).map(|b| {
+                let b = [b[0], b[1], b[2], b[3]];
+                if little_endian {
+                    f32::from_le_bytes(b)
+                } else {
+                    f32::from_be_bytes(b)
+                }
+            });
+            *pixel = if channels == 3 {
+                Vec3f(std::array::from_fn(|_| values.next().unwrap_or_default()))
+            } else {
+                let v = values.next().unwrap_or_default();
+                Vec3f::new(v, v, v)
+            };
+        }
+    }
+    Ok(image)
+}
+
+/// Reads a whitespace-delimited header token, consuming the single whitespace character after it.
+fn read_token<R: BufRead>(r: &mut R) -> Result<String, Error> {
+    let mut token = Vec::new();
+    for byte in r.by_ref().bytes() {
+        let byte = byte?;
+        if !byte.is_ascii_whitespace() {
+            token.push(byte);
+        } else if !token.is_empty() {
+            break;
+        }
+        if token.len() > 32 {
+            return invalid("invalid header");
+        }
+    }
+    String::from_utf8(token).or_else(|_| invalid("invalid header"))
+}
@@ -0,0 +1,227 @@
+//! Transformed and grouped shapes, for placing shared geometry in a scene several times.
+
+use crate::bvh::Bvh;
+use crate::csg::{self, Operation};
+use crate::geometry::{Aabb, Mat4, Vec3f};
+use crate::scene::Material;
+use crate::shape::{Hit, Shape, Span};
+use std::sync::Arc;
+
+/// A shape placed by an affine transform, which may share its geometry with other instances.
+///
+/// Rays are transformed into the shape's own space and the hit back into the scene, so a unit
+/// sphere scaled unevenly becomes an ellipsoid with correct normals.
+///
+/// ```
+/// use std::sync::Arc;
+/// use tinyraytracer::instance::Instance;
+/// use tinyraytracer::{Mat4, Material, Shape, Sphere, Vec3f};
+///
+/// let sphere = Arc::new(Sphere::new(Vec3f::default(), 1.0, Material::default()));
+/// let ellipsoid = Mat4::translation(Vec3f::new(0.0, 0.0, -10.0))
+///     * Mat4::scaling(Vec3f::new(3.0, 1.0, 1.0));
+/// let instance = Instance::new(sphere, ellipsoid).unwrap();
+///
+/// let side = Vec3f::new(-1.0, 0.0, 0.0);
+/// let hit = instance.intersect(Vec3f::new(0.0, 0.0, -10.0), side, 100.0).unwrap();
+/// assert!((hit.t - 3.0).abs() < 1e-5);
+/// assert!((hit.normal - side).norm() < 1e-5);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Instance {
+    shape: Arc<dyn Shape>,
+    /// From the shape's space into the scene.
+    transform: Mat4,
+    inverse: Mat4,
+    /// Replaces the materials of the shape, if set.
+    pub material: Option<Material>,
+}
+
+impl Instance {
+    /// Places `shape` by `transform`, returning `None` if the transform is not invertible.
+    pub fn new(shape: Arc<dyn Shape>, transform: Mat4) -> Option<Self> {
+        Some(Self {
+            shape,
+            transform,
+            inverse: transform.inverse()?,
+            material: None,
+        })
+    }
+
+    pub fn with_material(mut self, material: Material) -> Self {
+        self.material = Some(material);
+        self
+    }
+
+    pub fn transform(&self) -> Mat4 {
+        self.transform
+    }
+
+    /// The ray in the shape's space, with a unit direction, and the factor converting distances
+    /// along the ray into that space.
+    fn object_ray(&self, orig: Vec3f, dir: Vec3f) -> Option<(Vec3f, Vec3f, f32)> {
+        let d = self.inverse.transform_vector(dir);
+        let scale = d.norm();
+        if scale > 0f32 && scale.is_finite() {
+            Some((
+                self.inverse.transform_point(orig),
+                d * (1f32 / scale),
+                scale,
+            ))
+        } else {
+            None
+        }
+    }
+
+    /// Moves a hit on the shape into the scene, given the ray and the factor from
+    /// [`object_ray`](Self::object_ray).
+    fn world_hit<'a>(&'a self, orig: Vec3f, dir: Vec3f, scale: f32, hit: Hit<'a>) -> Hit<'a> {
+        let t = hit.t / scale;
+        // Normals transform by the inverse transpose to stay perpendicular to the surface.
+        let normal_transform = self.inverse.transpose();
+        let normal = normal_transform.transform_vector(hit.normal);
+        let geometric_normal = normal_transform.transform_vector(hit.geometric_normal);
+        Hit {
+            t,
+            point: orig + dir * t,
+            normal: normal.normalize(),
+            geometric_normal: geometric_normal.normalize(),
+            uv: hit.uv,
+            dpdu: self.transform.transform_vector(hit.dpdu),
+            dpdv: self.transform.transform_vector(hit.dpdv),
+            material: self.material.as_ref().unwrap_or(hit.material),
+        }
+    }
+}
+
+impl Shape for Instance {
+    fn bounds(&self) -> Option<Aabb> {
+        let b = self.shape.bounds()?;
+        let corners = (0..8).map(|i| {
+            let pick = |axis: usize| {
+                if i & (1 << axis) == 0 {
+                    b.min.0[axis]
+                } else {
+                    b.max.0[axis]
+                }
+            };
+            Vec3f::new(pick(0), pick(1), pick(2))
+        });
+        Some(corners.fold(Aabb::empty(), |bounds, p| {
+            bounds.grow(self.transform.transform_point(p))
+        }))
+    }
+
+    fn intersect(&self, orig: Vec3f, dir: Vec3f, t_max: f32) -> Option<Hit<'_>> {
+        let (o, d, scale) = self.object_ray(orig, dir)?;
+        let hit = self.shape.intersect(o, d, t_max * scale)?;
+        Some(self.world_hit(orig, dir, scale, hit))
+    }
+
+    fn occluded(&self, orig: Vec3f, dir: Vec3f, t_max: f32) -> bool {
+        self.object_ray(orig, dir)
+            .is_some_and(|(o, d, scale)| self.shape.occluded(o, d, t_max * scale))
+    }
+
+    fn spans(&self, orig: Vec3f, dir: Vec3f) -> Option<Vec<Span<'_>>> {
+        let Some((o, d, scale)) = self.object_ray(orig, dir) else {
+            return Some(Vec::new());
+        };
+        let spans = self.shape.spans(o, d)?;
+        Some(
+            spans
+                .into_iter()
+                .map(|span| Span {
+                    enter: self.world_hit(orig, dir, scale, span.enter),
+                    exit: self.world_hit(orig, dir, scale, span.exit),
+                })
+                .collect(),
+        )
+    }
+}
+
+/// Several shapes acting as one, with a bounding volume hierarchy of their own. Groups let an
+/// [`Instance`] place a whole model, and hold the objects of a [`Scene`](crate::Scene).
+///
+/// A group of solids is the solid union of its shapes.
+#[derive(Debug, Clone)]
+pub struct Group {
+    shapes: Vec<Arc<dyn Shape>>,
+    bvh: Bvh,
+    /// Shapes in the hierarchy, indexed by primitive.
+    bounded: Vec<usize>,
+    /// Shapes without bounds, which are tested against every ray.
+    unbounded: Vec<usize>,
+}
+
+impl Group {
+    pub fn new(shapes: Vec<Arc<dyn Shape>>) -> Self {
+        let mut bounds = Vec::new();
+        let (mut bounded, mut unbounded) = (Vec::new(), Vec::new());
+        for (i, shape) in shapes.iter().enumerate() {
+            match shape.bounds() {
+                Some(b) => {
+                    bounds.push(b);
+                    bounded.push(i);
+                }
+                None => unbounded.push(i),
+            }
+        }
+        Self {
+            shapes,
+            bvh: Bvh::new(&bounds),
+            bounded,
+            unbounded,
+        }
+    }
+
+    pub fn shapes(&self) -> &[Arc<dyn Shape>] {
+        &self.shapes
+    }
+}
+
+impl Shape for Group {
+    fn bounds(&self) -> Option<Aabb> {
+        if self.unbounded.is_empty() {
+            Some(self.bvh.bounds())
+        } else {
+            None
+        }
+    }
+
+    fn intersect(&self, orig: Vec3f, dir: Vec3f, t_max: f32) -> Option<Hit<'_>> {
+        let mut nearest: Option<Hit> = None;
+        for &i in &self.unbounded {
+            let t_max = nearest.map_or(t_max, |hit| hit.t);
+            if let Some(hit) = self.shapes[i].intersect(orig, dir, t_max) {
+                nearest = Some(hit);
+            }
+        }
+        let t_max = nearest.map_or(t_max, |hit| hit.t);
+        self.bvh.intersect(orig, dir, t_max, |i, t_max| {
+            let hit = self.shapes[self.bounded[i]].intersect(orig, dir, t_max)?;
+            nearest = Some(hit);
+            Some(hit.t)
+        });
+        nearest
+    }
+
+    fn occluded(&self, orig: Vec3f, dir: Vec3f, t_max: f32) -> bool {
+        self.unbounded
+            .iter()
+            .any(|&i| self.shapes[i].occluded(orig, dir, t_max))
+            || self.bvh.occluded(orig, dir, t_max, |i, t_max| {
+                self.shapes[self.bounded[i]]
+                    .occluded(orig, dir, t_max)
+                    .then_some(0f32)
+            })
+    }
+
+    fn spans(&self, orig: Vec3f, dir: Vec3f) -> Option<Vec<Span<'_>>> {
+        let mut spans = Vec::new();
+        for shape in &self.shapes {
+            spans = csg::combine(Operation::Union, spans, shape.spans(orig, dir)?);
+        }
+        Some(spans)
+    }
+}
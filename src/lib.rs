@@ -0,0 +1,56 @@
+//! A simple ray tracer, with Whitted-style ray tracing and Monte Carlo path tracing integrators.
+//!
+//! Build a [`Scene`], describe the view with a [`Camera`] and trace it with a [`Renderer`]:
+//!
+//! ```
+//! use tinyraytracer::{Camera, Light, Material, Renderer, Scene, Sphere, Vec3f};
+//!
+//! let ivory = Material::new(1.0, [0.6, 0.3, 0.1, 0.0], Vec3f::new(0.4, 0.4, 0.3), 50.0);
+//!
+//! let mut scene = Scene::new();
+//! scene.add(Sphere::new(Vec3f::new(0.0, 0.0, -16.0), 2.0, ivory));
+//! scene.add_light(Light::new(Vec3f::new(-20.0, 20.0, 20.0), 1.5));
+//!
+//! let camera = Camera::new(64, 48);
+//! let framebuffer = Renderer::new().render(&scene, &camera);
+//!
+//! // The center of the image looks at the sphere, the corners at the background.
+//! assert_ne!(framebuffer[24][32], scene.background);
+//! assert_eq!(framebuffer[0][0], scene.background);
+//! ```
+//!
+//! Scenes can also be read from JSON files with the [`loader`] module, and rendered images are
+//! encoded with the [`output`] module.
+
+pub mod bsdf;
+pub mod bvh;
+pub mod camera;
+pub mod csg;
+pub mod environment;
+pub mod framebuffer;
+pub mod geometry;
+pub mod hdr;
+pub mod instance;
+pub mod loader;
+pub mod mesh;
+pub mod noise;
+pub mod obj;
+pub mod output;
+pub mod primitive;
+pub mod render;
+pub mod sampling;
+pub mod scene;
+pub mod shape;
+pub mod texture;
+pub mod tonemap;
+
+pub use crate::camera::{Camera, Fov};
+pub use crate::framebuffer::Framebuffer;
+pub use crate::geometry::{Mat4, Vec3f};
+pub use crate::mesh::TriangleMesh;
+pub use crate::primitive::{Cone, Cuboid, Cylinder, Disk, Torus};
+pub use crate::render::{Integrator, Renderer};
+pub use crate::scene::{
+    Bump, Light, LightSample, LightShape, Maps, Material, Model, PhongSurface, Scene, Spot,
+};
+pub use crate::shape::{Hit, Plane, Rectangle, Shape, Span, Sphere};
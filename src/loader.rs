@@ -0,0 +1,2016 @@
+//! Scene file loader.
+//!
+//! A scene is described by a JSON document:
+//!
+//! ```json
+//! {
+//!     "resolution": [1024, 768],
+//!     "camera": {
+//!         "position": [0.0, 0.0, 0.0],
+//!         "target": [0.0, 0.0, -1.0],
+//!         "up": [0.0, 1.0, 0.0],
+//!         "fov": 90.0
+//!     },
+//!     "background": [0.2, 0.7, 0.8],
+//!     "materials": {
+//!         "ivory": {
+//!             "refractive_index": 1.0,
+//!             "albedo": [0.6, 0.3, 0.1, 0.0],
+//!             "diffuse_color": [0.4, 0.4, 0.3],
+//!             "specular_exponent": 50.0
+//!         }
+//!     },
+//!     "spheres": [
+//!         { "center": [-3.0, 0.0, -16.0], "radius": 2.0, "material": "ivory" }
+//!     ],
+//!     "planes": [
+//!         { "point": [0.0, -4.0, 0.0], "normal": [0.0, 1.0, 0.0], "material": "ivory" }
+//!     ],
+//!     "rectangles": [
+//!         {
+//!             "origin": [-10.0, -4.0, -30.0],
+//!             "u": [0.0, 0.0, 20.0],
+//!             "v": [20.0, 0.0, 0.0],
+//!             "material": "ivory",
+//!             "checker": { "material": "red_rubber", "size": 2.0 }
+//!         }
+//!     ],
+//!     "meshes": [
+//!         {
+//!             "positions": [[-1.0, -1.0, -12.0], [1.0, -1.0, -12.0], [0.0, 1.0, -12.0]],
+//!             "triangles": [[0, 1, 2]],
+//!             "material": "ivory"
+//!         }
+//!     ],
+//!     "models": [
+//!         { "file": "teapot.obj", "material": "ivory" }
+//!     ],
+//!     "lights": [
+//!         { "position": [-20.0, 20.0, 20.0], "intensity": 1.5 },
+//!         {
+//!             "position": [30.0, 50.0, -25.0],
+//!             "intensity": 1.8,
+//!             "shape": { "type": "sphere", "radius": 2.0 },
+//!             "samples": 16
+//!         }
+//!     ]
+//! }
+//! ```
+//!
+//! Every top-level field but `resolution` is optional, and unknown fields are rejected
+//! everywhere. Vectors and RGB colors are arrays of three numbers.
+//!
+//! # Camera
+//!
+//! - `position`, `target` and `up` place the camera, looking from the origin down -z by default.
+//! - `fov` is the vertical field of view in degrees, 90 by default. `hfov` may be given instead
+//!   to fix the horizontal one.
+//! - `aspect` is the width-to-height ratio of the image plane, if the pixels are not square.
+//!
+//! # Background and environment
+//!
+//! - `background` is the color of rays that miss the scene, light blue by default.
+//! - `environment` may surround and light the scene instead: an equirectangular Radiance `.hdr`
+//!   or `.pfm` image `file`, scaled by `intensity`, turned by `rotation` degrees about the
+//!   vertical axis and sampled with `samples` shadow rays (16 by default).
+//!
+//! # Materials
+//!
+//! Every material field is optional and falls back to `Material::default()`. `model` selects one
+//! of the [`Model`](crate::scene::Model)s, and fields the model does not use are rejected:
+//!
+//! - `phong` (the default otherwise) uses `albedo`, `diffuse_color`, `specular_exponent` and
+//!   `refractive_index`.
+//! - `metallic_roughness` (the default if `metallic` or `roughness` is given) uses `metallic` and
+//!   `roughness`, between 0 and 1 and defaulting to 0 and 0.5, and `diffuse_color`.
+//! - `dielectric` uses `refractive_index` (1.5 by default) and an optional `absorption`
+//!   coefficient per color channel.
+//!
+//! Phong and metallic-roughness materials may multiply `diffuse_color` (white if omitted) by a
+//! `texture`. Phong materials may also tint the specular, reflected and refracted weights of
+//! their `albedo` by a `specular_map`, a `reflection_map` and a `refraction_map`, sRGB encoded by
+//! default like the `texture`. Scalar parameters are scaled by the luminance of a
+//! `specular_exponent_map`, a `metallic_map` or a `roughness_map`, linear by default, and
+//! default to 1 if only their map is given. Like `metallic` and `roughness`, the last two imply
+//! the `metallic_roughness` model.
+//!
+//! Any material may tilt its shading normals by a tangent-space `normal_map` or by a `bump_map`
+//! of heights up to `bump_scale` (0.05 by default) scene units. The values of both default to
+//! `linear` encoding.
+//!
+//! # Textures
+//!
+//! An image texture has a PNG, `.hdr` or `.pfm` `file`, resolved relative to the scene file, and:
+//!
+//! - `wrap`: `repeat` (the default) or `clamp`.
+//! - `filter`: `bilinear` (the default) or `nearest`.
+//! - `scale`: how many times it tiles per unit of texture coordinates, along each axis.
+//! - `encoding` of PNG values: `srgb` (the default) or `linear`.
+//!
+//! A procedural texture blends two `colors` (black and white by default), each an RGB color or a
+//! nested texture, by a `pattern` with features `size` units wide:
+//!
+//! - `checker`, `stripes` or `solid_checker`.
+//! - `noise`, `fbm` or `turbulence`, the last two summing `octaves` (6 by default).
+//! - `marble` or `wood`, bent by a `turbulence` strength.
+//! - `worley`.
+//!
+//! Spheres are mapped by longitude and latitude, planes by distance along the plane, rectangles
+//! from 0 to 1 along their edges, boxes across each face and cylinders and cones around their
+//! axis.
+//!
+//! # Shapes
+//!
+//! Each shape names its `material`.
+//!
+//! - `spheres`: a `center` and a `radius`.
+//! - `planes`: a `point` and a `normal`.
+//! - `rectangles`: spanned by the edges `u` and `v` from `origin`, facing along `u × v`.
+//! - `meshes`: `positions` and `triangles` indexing them, with optional `normals` and `uvs` with
+//!   one entry per position.
+//! - `models`: a Wavefront OBJ `file`, resolved relative to the scene file. `material` is
+//!   optional and used for faces without an MTL material.
+//! - `boxes`: the corners `min` and `max`, or a `center` and a `size`, optionally turned by a
+//!   `rotate`.
+//! - `cylinders`: from `base` to `top` with a `radius`, closed by flat caps.
+//! - `cones`: from a `base` of `radius` to an `apex`, closed by a flat base. A `top_radius`
+//!   narrower than `radius` cuts the cone off where it is that wide.
+//! - `disks`: a `center`, a `normal` and a `radius`. An `inner_radius` narrower than `radius`
+//!   cuts a hole out of the middle.
+//! - `tori`: a tube of `minor_radius` around a circle of `major_radius` about their `axis`,
+//!   centered on `center`.
+//! - `csg`: a `left` and a `right` solid combined by an `operation`: `union`, `intersection` or
+//!   `difference`. Operands have a `type`: `sphere`, `box`, `cylinder`, `cone`, `torus`,
+//!   `instance` of an object made of these only, or `csg` for another combination.
+//!
+//! Planes and rectangles may alternate their material with a second one in a `checker` pattern
+//! of squares `size` wide.
+//!
+//! # Objects and instances
+//!
+//! Named `objects` hold any shapes like the scene itself, but are only shown by `instances`. An
+//! instance places an `object` by a `transform` and may replace its `material`. A transform is
+//! either a row-major 4x4 `matrix` or applies, in this order:
+//!
+//! - `scale`: a number, or one per axis.
+//! - `rotate`: by an `angle` in degrees about an `axis`.
+//! - `translate`: an offset.
+//!
+//! # Lights
+//!
+//! - `intensity` is required, and `color` is white by default. Neither may be negative.
+//! - `position` places a point light, or the center of an area light with a `shape`: a `sphere`
+//!   with a `radius`, a `rectangle` spanned by the edges `u` and `v`, or a `disk` with a `normal`
+//!   and a `radius`. Area lights are sampled with `samples` shadow rays (16 by default).
+//! - `direction` instead of a `position` makes a directional light.
+//! - `spot` narrows the light to a cone with a `direction`, an `inner_angle` and an `outer_angle`
+//!   in degrees.
+//! - `inverse_square` opts into falloff with the squared distance.
+//!
+//! # Errors
+//!
+//! Errors name the offending field by its path in the document, such as `spheres[2].radius`.
+
+use crate::camera::{Camera, Fov};
+use crate::csg::{Csg, Operation};
+use crate::environment::Environment;
+use crate::geometry::{Mat4, Vec3f};
+use crate::hdr;
+use crate::instance::{Group, Instance};
+use crate::mesh::TriangleMesh;
+use crate::noise;
+use crate::obj;
+use crate::primitive::{Cone, Cuboid, Cylinder, Disk, Torus};
+use crate::scene::{Bump, Light, LightShape, Maps, Material, Scene};
+use crate::shape::{Plane, Rectangle, Shape, Sphere};
+use crate::texture::{ImageTexture, Interpolation, Pattern, Procedural, Texture, Wrap};
+use crate::tonemap::Encoding;
+use serde::de::{self, Deserializer};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// An error encountered while loading a scene file.
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    Parse {
+        field: String,
+        source: serde_json::Error,
+    },
+    Invalid {
+        field: String,
+        message: String,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Parse { field, source } if field.is_empty() => write!(f, "{}", source),
+            Error::Parse { field, source } => write!(f, "{}: {}", field, source),
+            Error::Invalid { field, message } => write!(f, "{}: {}", field, message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+fn invalid(field: String, message: impl Into<String>) -> Error {
+    Error::Invalid {
+        field,
+        message: message.into(),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneFile {
+    resolution: [usize; 2],
+    #[serde(default)]
+    camera: CameraDesc,
+    background: Option<[f32; 3]>,
+    environment: Option<EnvironmentDesc>,
+    #[serde(default)]
+    materials: BTreeMap<String, MaterialDesc>,
+    #[serde(default)]
+    spheres: Vec<SphereDesc>,
+    #[serde(default)]
+    planes: Vec<PlaneDesc>,
+    #[serde(default)]
+    rectangles: Vec<RectangleDesc>,
+    #[serde(default)]
+    meshes: Vec<MeshDesc>,
+    #[serde(default)]
+    models: Vec<ModelDesc>,
+    #[serde(default)]
+    boxes: Vec<BoxDesc>,
+    #[serde(default)]
+    cylinders: Vec<CylinderDesc>,
+    #[serde(default)]
+    cones: Vec<ConeDesc>,
+    #[serde(default)]
+    disks: Vec<DiskDesc>,
+    #[serde(default)]
+    tori: Vec<TorusDesc>,
+    #[serde(default)]
+    csg: Vec<CsgDesc>,
+    #[serde(default)]
+    objects: HashMap<String, ObjectDesc>,
+    #[serde(default)]
+    instances: Vec<InstanceDesc>,
+    #[serde(default)]
+    lights: Vec<LightDesc>,
+}
+
+/// Shapes that are placed together, directly in the scene or by instances of a named object.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ObjectDesc {
+    #[serde(default)]
+    spheres: Vec<SphereDesc>,
+    #[serde(default)]
+    planes: Vec<PlaneDesc>,
+    #[serde(default)]
+    rectangles: Vec<RectangleDesc>,
+    #[serde(default)]
+    meshes: Vec<MeshDesc>,
+    #[serde(default)]
+    models: Vec<ModelDesc>,
+    #[serde(default)]
+    boxes: Vec<BoxDesc>,
+    #[serde(default)]
+    cylinders: Vec<CylinderDesc>,
+    #[serde(default)]
+    cones: Vec<ConeDesc>,
+    #[serde(default)]
+    disks: Vec<DiskDesc>,
+    #[serde(default)]
+    tori: Vec<TorusDesc>,
+    #[serde(default)]
+    csg: Vec<CsgDesc>,
+}
+
+/// A named object once built, shared by its instances.
+struct Object {
+    shape: Arc<dyn Shape>,
+    /// Whether the object encloses a volume, so that CSG may combine it.
+    solid: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct InstanceDesc {
+    object: String,
+    #[serde(default)]
+    transform: TransformDesc,
+    material: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CsgDesc {
+    operation: OperationDesc,
+    left: SolidDesc,
+    right: SolidDesc,
+}
+
+/// An operand of a CSG combination, whose `type` names its kind.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum SolidDesc {
+    Sphere(SphereDesc),
+    Box(BoxDesc),
+    Cylinder(CylinderDesc),
+    Cone(ConeDesc),
+    Torus(TorusDesc),
+    Instance(InstanceDesc),
+    Csg(Box<CsgDesc>),
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum OperationDesc {
+    Union,
+    Intersection,
+    Difference,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TransformDesc {
+    matrix: Option<[[f32; 4]; 4]>,
+    scale: Option<ScaleDesc>,
+    rotate: Option<RotateDesc>,
+    translate: Option<[f32; 3]>,
+}
+
+#[derive(Debug)]
+enum ScaleDesc {
+    Uniform(f32),
+    Axes([f32; 3]),
+}
+
+impl<'de> Deserialize<'de> for ScaleDesc {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl<'de> de::Visitor<'de> for Visitor {
+            type Value = ScaleDesc;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a number or one number per axis")
+            }
+
+            fn visit_f64<E: de::Error>(self, v: f64) -> Result<Self::Value, E> {
+                Ok(ScaleDesc::Uniform(v as f32))
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+                Ok(ScaleDesc::Uniform(v as f32))
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+                Ok(ScaleDesc::Uniform(v as f32))
+            }
+
+            fn visit_seq<A: de::SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+                Deserialize::deserialize(de::value::SeqAccessDeserializer::new(seq))
+                    .map(ScaleDesc::Axes)
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RotateDesc {
+    axis: [f32; 3],
+    angle: f32,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CameraDesc {
+    position: Option<[f32; 3]>,
+    target: Option<[f32; 3]>,
+    up: Option<[f32; 3]>,
+    fov: Option<f32>,
+    hfov: Option<f32>,
+    aspect: Option<f32>,
+}
+
+impl CameraDesc {
+    fn build(&self, width: usize, height: usize) -> Result<Camera, Error> {
+        let mut camera = Camera::new(width, height);
+        let check_fov = |field: &str, fov: f32| {
+            if fov > 0f32 && fov < 180f32 {
+                Ok(fov.to_radians())
+            } else {
+                Err(invalid(
+                    format!("camera.{}", field),
+                    "must be between 0 and 180 degrees",
+                ))
+            }
+        };
+        camera.fov = match (self.fov, self.hfov) {
+            (Some(_), Some(_)) => {
+                return Err(invalid(
+                    "camera.hfov".into(),
+                    "cannot be combined with `fov`",
+                ))
+            }
+            (Some(fov), None) => Fov::Vertical(check_fov("fov", fov)?),
+            (None, Some(hfov)) => Fov::Horizontal(check_fov("hfov", hfov)?),
+            (None, None) => camera.fov,
+        };
+        if let Some(aspect) = self.aspect {
+            if !(aspect > 0f32 && aspect.is_finite()) {
+                return Err(invalid("camera.aspect".into(), "must be positive"));
+            }
+            camera.aspect = Some(aspect);
+        }
+        camera.position = self.position.map_or(camera.position, vec3);
+        camera.target = self.target.map_or(camera.target, vec3);
+        camera.up = self.up.map_or(camera.up, vec3);
+        let forward = camera.target - camera.position;
+        if forward.norm() == 0f32 {
+            return Err(invalid(
+                "camera.target".into(),
+                "must differ from the camera position",
+            ));
+        }
+        if forward.cross(camera.up).norm() == 0f32 {
+            return Err(invalid(
+                "camera.up".into(),
+                "must not be parallel to the view direction",
+            ));
+        }
+        Ok(camera)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MaterialDesc {
+    model: Option<MaterialModel>,
+    refractive_index: Option<f32>,
+    albedo: Option<[f32; 4]>,
+    diffuse_color: Option<[f32; 3]>,
+    specular_exponent: Option<f32>,
+    metallic: Option<f32>,
+    roughness: Option<f32>,
+    absorption: Option<[f32; 3]>,
+    texture: Option<TextureDesc>,
+    specular_map: Option<TextureDesc>,
+    reflection_map: Option<TextureDesc>,
+    refraction_map: Option<TextureDesc>,
+    specular_exponent_map: Option<TextureDesc>,
+    metallic_map: Option<TextureDesc>,
+    roughness_map: Option<TextureDesc>,
+    normal_map: Option<TextureDesc>,
+    bump_map: Option<TextureDesc>,
+    bump_scale: Option<f32>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TextureDesc {
+    file: Option<PathBuf>,
+    wrap: Option<WrapDesc>,
+    filter: Option<FilterDesc>,
+    scale: Option<[f32; 2]>,
+    encoding: Option<EncodingDesc>,
+    pattern: Option<PatternDesc>,
+    colors: Option<[TextureValue; 2]>,
+    size: Option<f32>,
+    octaves: Option<u32>,
+    turbulence: Option<f32>,
+}
+
+/// A constant color or a nested texture.
+#[derive(Debug)]
+enum TextureValue {
+    Color([f32; 3]),
+    Texture(Box<TextureDesc>),
+}
+
+// Dispatches on whether an array or an object is given, so that errors within either point at
+// the offending value instead of saying that neither matched.
+impl<'de> Deserialize<'de> for TextureValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl<'de> de::Visitor<'de> for Visitor {
+            type Value = TextureValue;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("an RGB color or a texture")
+            }
+
+            fn visit_seq<A: de::SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+                Deserialize::deserialize(de::value::SeqAccessDeserializer::new(seq))
+                    .map(TextureValue::Color)
+            }
+
+            fn visit_map<A: de::MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+                Deserialize::deserialize(de::value::MapAccessDeserializer::new(map))
+                    .map(TextureValue::Texture)
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum PatternDesc {
+    Checker,
+    SolidChecker,
+    Stripes,
+    Noise,
+    Fbm,
+    Turbulence,
+    Marble,
+    Wood,
+    Worley,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum WrapDesc {
+    Repeat,
+    Clamp,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum FilterDesc {
+    Nearest,
+    Bilinear,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum EncodingDesc {
+    Linear,
+    Srgb,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum MaterialModel {
+    Phong,
+    MetallicRoughness,
+    Dielectric,
+}
+
+impl MaterialModel {
+    fn name(self) -> &'static str {
+        match self {
+            MaterialModel::Phong => "phong",
+            MaterialModel::MetallicRoughness => "metallic_roughness",
+            MaterialModel::Dielectric => "dielectric",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SphereDesc {
+    center: [f32; 3],
+    radius: f32,
+    material: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CheckerDesc {
+    material: String,
+    size: f32,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PlaneDesc {
+    point: [f32; 3],
+    normal: [f32; 3],
+    material: String,
+    checker: Option<CheckerDesc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RectangleDesc {
+    origin: [f32; 3],
+    u: [f32; 3],
+    v: [f32; 3],
+    material: String,
+    checker: Option<CheckerDesc>,
+}
+
+/// A box given by its corners `min` and `max` or by its `center` and `size`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BoxDesc {
+    min: Option<[f32; 3]>,
+    max: Option<[f32; 3]>,
+    center: Option<[f32; 3]>,
+    size: Option<[f32; 3]>,
+    rotate: Option<RotateDesc>,
+    material: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CylinderDesc {
+    base: [f32; 3],
+    top: [f32; 3],
+    radius: f32,
+    material: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConeDesc {
+    base: [f32; 3],
+    apex: [f32; 3],
+    radius: f32,
+    top_radius: Option<f32>,
+    material: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DiskDesc {
+    center: [f32; 3],
+    normal: [f32; 3],
+    radius: f32,
+    inner_radius: Option<f32>,
+    material: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TorusDesc {
+    center: [f32; 3],
+    axis: [f32; 3],
+    major_radius: f32,
+    minor_radius: f32,
+    material: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MeshDesc {
+    positions: Vec<[f32; 3]>,
+    triangles: Vec<[u32; 3]>,
+    normals: Option<Vec<[f32; 3]>>,
+    uvs: Option<Vec<[f32; 2]>>,
+    material: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ModelDesc {
+    file: PathBuf,
+    material: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EnvironmentDesc {
+    file: PathBuf,
+    intensity: Option<f32>,
+    rotation: Option<f32>,
+    samples: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LightDesc {
+    position: Option<[f32; 3]>,
+    direction: Option<[f32; 3]>,
+    intensity: f32,
+    color: Option<[f32; 3]>,
+    shape: Option<LightShapeDesc>,
+    samples: Option<usize>,
+    spot: Option<SpotDesc>,
+    #[serde(default)]
+    inverse_square: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SpotDesc {
+    direction: [f32; 3],
+    inner_angle: f32,
+    outer_angle: f32,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum LightShapeDesc {
+    Sphere { radius: f32 },
+    Rectangle { u: [f32; 3], v: [f32; 3] },
+    Disk { normal: [f32; 3], radius: f32 },
+}
+
+/// Roughness of metallic-roughness materials that only specify `metallic`.
+const DEFAULT_ROUGHNESS: f32 = 0.5;
+
+/// Refractive index of dielectrics that do not specify one, that of common glass.
+const DEFAULT_DIELECTRIC_INDEX: f32 = 1.5;
+
+/// Strength of the turbulence bending the veins of marble textures that do not specify it.
+const DEFAULT_MARBLE_TURBULENCE: f32 = 2.5;
+
+/// Strength of the noise distorting the rings of wood textures that do not specify it.
+const DEFAULT_WOOD_TURBULENCE: f32 = 0.15;
+
+fn vec3(v: [f32; 3]) -> Vec3f {
+    Vec3f::new(v[0], v[1], v[2])
+}
+
+impl MaterialDesc {
+    fn build(&self, name: &str, dir: &Path) -> Result<Material, Error> {
+        let model = self.model.unwrap_or(
+            if self.metallic.is_some()
+                || self.roughness.is_some()
+                || self.metallic_map.is_some()
+                || self.roughness_map.is_some()
+            {
+                MaterialModel::MetallicRoughness
+            } else {
+                MaterialModel::Phong
+            },
+        );
+        let field = |field: &str| format!("materials.{}.{}", name, field);
+        let unused: &[(&str, bool)] = match model {
+            MaterialModel::Phong => &[
+                ("metallic", self.metallic.is_some()),
+                ("roughness", self.roughness.is_some()),
+                ("absorption", self.absorption.is_some()),
+                ("metallic_map", self.metallic_map.is_some()),
+                ("roughness_map", self.roughness_map.is_some()),
+            ],
+            MaterialModel::MetallicRoughness => &[
+                ("refractive_index", self.refractive_index.is_some()),
+                ("albedo", self.albedo.is_some()),
+                ("specular_exponent", self.specular_exponent.is_some()),
+                ("absorption", self.absorption.is_some()),
+                ("specular_map", self.specular_map.is_some()),
+                ("reflection_map", self.reflection_map.is_some()),
+                ("refraction_map", self.refraction_map.is_some()),
+                (
+                    "specular_exponent_map",
+                    self.specular_exponent_map.is_some(),
+                ),
+            ],
+            MaterialModel::Dielectric => &[
+                ("albedo", self.albedo.is_some()),
+                ("diffuse_color", self.diffuse_color.is_some()),
+                ("specular_exponent", self.specular_exponent.is_some()),
+                ("metallic", self.metallic.is_some()),
+                ("roughness", self.roughness.is_some()),
+                ("texture", self.texture.is_some()),
+                ("specular_map", self.specular_map.is_some()),
+                ("reflection_map", self.reflection_map.is_some()),
+                ("refraction_map", self.refraction_map.is_some()),
+                (
+                    "specular_exponent_map",
+                    self.specular_exponent_map.is_some(),
+                ),
+                ("metallic_map", self.metallic_map.is_some()),
+                ("roughness_map", self.roughness_map.is_some()),
+            ],
+        };
+        if let Some((unused, _)) = unused.iter().find(|(_, set)| *set) {
+            return Err(invalid(
+                field(unused),
+                format!("is not used by the `{}` model", model.name()),
+            ));
+        }
+        if self.refractive_index.is_some_and(|r| r <= 0f32) {
+            return Err(invalid(field("refractive_index"), "must be positive"));
+        }
+
+        let default = Material::default();
+        // A texture alone shows its own colors.
+        let diffuse_color = match (self.diffuse_color, &self.texture) {
+            (Some(color), _) => vec3(color),
+            (None, Some(_)) => Vec3f::new(1f32, 1f32, 1f32),
+            (None, None) => default.diffuse_color,
+        };
+        // So does a map of a scalar parameter without the parameter.
+        let scalar = |value: Option<f32>, map: &Option<TextureDesc>, default: f32| {
+            value.unwrap_or(if map.is_some() { 1f32 } else { default })
+        };
+        let unit = |name: &str, value: f32| {
+            if (0f32..=1f32).contains(&value) {
+                Ok(value)
+            } else {
+                Err(invalid(field(name), "must be between 0 and 1"))
+            }
+        };
+        let mut material = match model {
+            MaterialModel::Phong => Material::new(
+                self.refractive_index.unwrap_or(default.refractive_index),
+                self.albedo.unwrap_or(default.albedo),
+                diffuse_color,
+                scalar(
+                    self.specular_exponent,
+                    &self.specular_exponent_map,
+                    default.specular_exponent,
+                ),
+            ),
+            MaterialModel::MetallicRoughness => Material::metallic_roughness(
+                diffuse_color,
+                unit("metallic", scalar(self.metallic, &self.metallic_map, 0f32))?,
+                unit(
+                    "roughness",
+                    scalar(self.roughness, &self.roughness_map, DEFAULT_ROUGHNESS),
+                )?,
+            ),
+            MaterialModel::Dielectric => {
+                let absorption = self.absorption.unwrap_or([0f32; 3]);
+                if absorption.iter().any(|a| *a < 0f32) {
+                    return Err(invalid(field("absorption"), "must not be negative"));
+                }
+                Material::dielectric(
+                    self.refractive_index.unwrap_or(DEFAULT_DIELECTRIC_INDEX),
+                    vec3(absorption),
+                )
+            }
+        };
+        if let Some(texture) = &self.texture {
+            material.texture = Some(texture.build(&field("texture"), dir, Encoding::Srgb)?);
+        }
+        let map = |name: &str, desc: &Option<TextureDesc>, encoding: Encoding| {
+            desc.as_ref()
+                .map(|desc| desc.build(&field(name), dir, encoding))
+                .transpose()
+        };
+        material.maps = Maps {
+            specular: map("specular_map", &self.specular_map, Encoding::Srgb)?,
+            reflection: map("reflection_map", &self.reflection_map, Encoding::Srgb)?,
+            refraction: map("refraction_map", &self.refraction_map, Encoding::Srgb)?,
+            specular_exponent: map(
+                "specular_exponent_map",
+                &self.specular_exponent_map,
+                Encoding::Linear,
+            )?,
+            metallic: map("metallic_map", &self.metallic_map, Encoding::Linear)?,
+            roughness: map("roughness_map", &self.roughness_map, Encoding::Linear)?,
+        };
+        material.bump = match (&self.normal_map, &self.bump_map) {
+            (Some(_), Some(_)) => {
+                return Err(invalid(
+                    field("bump_map"),
+                    "cannot be combined with `normal_map`",
+                ))
+            }
+            (Some(normal_map), None) => Some(Bump::Normal(normal_map.build(
+                &field("normal_map"),
+                dir,
+                Encoding::Linear,
+            )?)),
+            (None, Some(bump_map)) => {
+                let scale = self.bump_scale.unwrap_or(Bump::DEFAULT_SCALE);
+                if !scale.is_finite() {
+                    return Err(invalid(field("bump_scale"), "must be finite"));
+                }
+                Some(Bump::Height {
+                    texture: bump_map.build(&field("bump_map"), dir, Encoding::Linear)?,
+                    scale,
+                })
+            }
+            (None, None) if self.bump_scale.is_some() => {
+                return Err(invalid(field("bump_scale"), "needs a `bump_map`"))
+            }
+            (None, None) => None,
+        };
+        Ok(material)
+    }
+}
+
+impl TextureDesc {
+    /// Builds the texture, decoding PNG images from `encoding` unless the description overrides
+    /// it.
+    fn build(
+        &self,
+        prefix: &str,
+        dir: &Path,
+        encoding: Encoding,
+    ) -> Result<Arc<dyn Texture>, Error> {
+        let field = |name: &str| format!("{}.{}", prefix, name);
+        let image_fields = [
+            ("wrap", self.wrap.is_some()),
+            ("filter", self.filter.is_some()),
+            ("scale", self.scale.is_some()),
+            ("encoding", self.encoding.is_some()),
+        ];
+        let pattern_fields = [
+            ("colors", self.colors.is_some()),
+            ("size", self.size.is_some()),
+            ("octaves", self.octaves.is_some()),
+            ("turbulence", self.turbulence.is_some()),
+        ];
+        match (&self.file, self.pattern) {
+            (Some(_), Some(_)) => Err(invalid(field("pattern"), "cannot be combined with `file`")),
+            (None, None) => Err(invalid(prefix.into(), "needs a `file` or a `pattern`")),
+            (Some(file), None) => {
+                reject_unused(prefix, &pattern_fields, "image textures")?;
+                Ok(Arc::new(self.build_image(file, prefix, dir, encoding)?))
+            }
+            (None, Some(pattern)) => {
+                reject_unused(prefix, &image_fields, "procedural textures")?;
+                Ok(Arc::new(
+                    self.build_procedural(pattern, prefix, dir, encoding)?,
+                ))
+            }
+        }
+    }
+
+    fn build_image(
+        &self,
+        file: &Path,
+        prefix: &str,
+        dir: &Path,
+        encoding: Encoding,
+    ) -> Result<ImageTexture, Error> {
+        let field = |name: &str| format!("{}.{}", prefix, name);
+        let encoding = match self.encoding {
+            Some(EncodingDesc::Linear) => Encoding::Linear,
+            Some(EncodingDesc::Srgb) => Encoding::Srgb,
+            None => encoding,
+        };
+        let path = dir.join(file);
+        let mut texture = ImageTexture::load(&path, encoding)
+            .map_err(|e| invalid(field("file"), format!("{}: {}", path.display(), e)))?;
+        texture.wrap = match self.wrap {
+            Some(WrapDesc::Clamp) => Wrap::Clamp,
+            Some(WrapDesc::Repeat) | None => Wrap::Repeat,
+        };
+        texture.interpolation = match self.filter {
+            Some(FilterDesc::Nearest) => Interpolation::Nearest,
+            Some(FilterDesc::Bilinear) | None => Interpolation::Bilinear,
+        };
+        if let Some(scale) = self.scale {
+            if !scale.iter().all(|s| s.is_finite() && *s != 0f32) {
+                return Err(invalid(field("scale"), "must be finite and non-zero"));
+            }
+            texture.scale = scale;
+        }
+        Ok(texture)
+    }
+
+    fn build_procedural(
+        &self,
+        pattern: PatternDesc,
+        prefix: &str,
+        dir: &Path,
+        encoding: Encoding,
+    ) -> Result<Procedural, Error> {
+        let field = |name: &str| format!("{}.{}", prefix, name);
+        let octaves = match self.octaves {
+            Some(0) => return Err(invalid(field("octaves"), "must be positive")),
+            Some(octaves) => octaves,
+            None => noise::DEFAULT_OCTAVES,
+        };
+        let turbulence = self.turbulence.unwrap_or(match pattern {
+            PatternDesc::Wood => DEFAULT_WOOD_TURBULENCE,
+            _ => DEFAULT_MARBLE_TURBULENCE,
+        });
+        let unused: &[(&str, bool)] = match pattern {
+            PatternDesc::Fbm | PatternDesc::Turbulence => {
+                &[("turbulence", self.turbulence.is_some())]
+            }
+            PatternDesc::Marble | PatternDesc::Wood => &[("octaves", self.octaves.is_some())],
+            _ => &[
+                ("octaves", self.octaves.is_some()),
+                ("turbulence", self.turbulence.is_some()),
+            ],
+        };
+        reject_unused(prefix, unused, "this pattern")?;
+        let pattern = match pattern {
+            PatternDesc::Checker => Pattern::Checker,
+            PatternDesc::SolidChecker => Pattern::SolidChecker,
+            PatternDesc::Stripes => Pattern::Stripes,
+            PatternDesc::Noise => Pattern::Noise,
+            PatternDesc::Fbm => Pattern::Fbm { octaves },
+            PatternDesc::Turbulence => Pattern::Turbulence { octaves },
+            PatternDesc::Marble => Pattern::Marble { turbulence },
+            PatternDesc::Wood => Pattern::Wood { turbulence },
+            PatternDesc::Worley => Pattern::Worley,
+        };
+
+        let value = |i: usize, default: Vec3f| -> Result<Arc<dyn Texture>, Error> {
+            match self.colors.as_ref().map(|colors| &colors[i]) {
+                Some(TextureValue::Color(color)) => Ok(Arc::new(vec3(*color))),
+                Some(TextureValue::Texture(texture)) => {
+                    texture.build(&field(&format!("colors[{}]", i)), dir, encoding)
+                }
+                None => Ok(Arc::new(default)),
+            }
+        };
+        let mut texture = Procedural {
+            pattern,
+            a: value(0, Vec3f::default())?,
+            b: value(1, Vec3f::new(1f32, 1f32, 1f32))?,
+            size: 1f32,
+        };
+        if let Some(size) = self.size {
+            if !(size > 0f32 && size.is_finite()) {
+                return Err(invalid(field("size"), "must be positive"));
+            }
+            texture.size = size;
+        }
+        Ok(texture)
+    }
+}
+
+/// Rejects the first of the `fields` that is set, as it is not used by `user`.
+fn reject_unused(prefix: &str, fields: &[(&str, bool)], user: &str) -> Result<(), Error> {
+    match fields.iter().find(|(_, set)| *set) {
+        Some((unused, _)) => Err(invalid(
+            format!("{}.{}", prefix, unused),
+            format!("is not used by {}", user),
+        )),
+        None => Ok(()),
+    }
+}
+
+impl LightDesc {
+    fn build(&self, prefix: &str) -> Result<Light, Error> {
+        let field = |name: &str| format!("{}.{}", prefix, name);
+        let positive = |name: &str, value: f32| {
+            if value > 0f32 {
+                Ok(value)
+            } else {
+                Err(invalid(field(name), "must be positive"))
+            }
+        };
+        let direction = |name: &str, v: [f32; 3]| {
+            if vec3(v).norm() > 0f32 {
+                Ok(vec3(v).normalize())
+            } else {
+                Err(invalid(field(name), "must not be zero"))
+            }
+        };
+        let radiant = |value: f32| value >= 0f32 && value.is_finite();
+        if !radiant(self.intensity) {
+            return Err(invalid(
+                field("intensity"),
+                "must be finite and not negative",
+            ));
+        }
+        if self
+            .color
+            .is_some_and(|color| !color.iter().all(|&c| radiant(c)))
+        {
+            return Err(invalid(field("color"), "must be finite and not negative"));
+        }
+        let mut light = match (self.position, self.direction) {
+            (Some(_), Some(_)) => {
+                return Err(invalid(
+                    field("direction"),
+                    "directional lights have no `position`",
+                ))
+            }
+            (None, None) => {
+                return Err(invalid(
+                    prefix.to_string(),
+                    "needs a `position`, or a `direction` for directional lights",
+                ))
+            }
+            (None, Some(d)) => {
+                for (name, set) in [
+                    ("shape", self.shape.is_some()),
+                    ("spot", self.spot.is_some()),
+                    ("inverse_square", self.inverse_square),
+                ] {
+                    if set {
+                        return Err(invalid(field(name), "is not used by directional lights"));
+                    }
+                }
+                Light::directional(direction("direction", d)?, self.intensity)
+            }
+            (Some(position), None) => {
+                let shape = match self.shape {
+                    None => LightShape::Point,
+                    Some(LightShapeDesc::Sphere { radius }) => LightShape::Sphere {
+                        radius: positive("shape.radius", radius)?,
+                    },
+                    Some(LightShapeDesc::Rectangle { u, v }) => {
+                        if vec3(u).cross(vec3(v)).norm() == 0f32 {
+                            return Err(invalid(
+                                field("shape"),
+                                "edges `u` and `v` must not be parallel",
+                            ));
+                        }
+                        LightShape::Rectangle {
+                            u: vec3(u),
+                            v: vec3(v),
+                        }
+                    }
+                    Some(LightShapeDesc::Disk { normal, radius }) => LightShape::Disk {
+                        normal: direction("shape.normal", normal)?,
+                        radius: positive("shape.radius", radius)?,
+                    },
+                };
+                Light::area(vec3(position), self.intensity, shape)
+            }
+        };
+        if let Some(color) = self.color {
+            light = light.with_color(vec3(color));
+        }
+        if let Some(spot) = &self.spot {
+            let angle = |name: &str, degrees: f32| {
+                if (0f32..=180f32).contains(&degrees) {
+                    Ok(degrees.to_radians())
+                } else {
+                    Err(invalid(field(name), "must be between 0 and 180 degrees"))
+                }
+            };
+            let inner = angle("spot.inner_angle", spot.inner_angle)?;
+            let outer = angle("spot.outer_angle", spot.outer_angle)?;
+            if inner > outer {
+                return Err(invalid(
+                    field("spot.inner_angle"),
+                    "must not exceed `outer_angle`",
+                ));
+            }
+            light = light.with_spot(direction("spot.direction", spot.direction)?, inner, outer);
+        }
+        match self.samples {
+            Some(0) => return Err(invalid(field("samples"), "must be positive")),
+            Some(samples) => light.samples = samples,
+            None => {}
+        }
+        light.inverse_square = self.inverse_square;
+        Ok(light)
+    }
+}
+
+impl EnvironmentDesc {
+    fn build(&self, dir: &Path) -> Result<Environment, Error> {
+        let path = dir.join(&self.file);
+        let image = hdr::load(&path).map_err(|e| {
+            invalid(
+                "environment.file".into(),
+                format!("{}: {}", path.display(), e),
+            )
+        })?;
+        let mut environment = Environment::new(image);
+        if let Some(intensity) = self.intensity {
+            if !(intensity >= 0f32 && intensity.is_finite()) {
+                return Err(invalid(
+                    "environment.intensity".into(),
+                    "must not be negative",
+                ));
+            }
+            environment.intensity = intensity;
+        }
+        if let Some(rotation) = self.rotation {
+            environment.rotation = rotation.to_radians();
+        }
+        match self.samples {
+            Some(0) => return Err(invalid("environment.samples".into(), "must be positive")),
+            Some(samples) => environment.samples = samples,
+            None => {}
+        }
+        Ok(environment)
+    }
+}
+
+fn lookup(
+    materials: &HashMap<&str, Material>,
+    name: &str,
+    field: String,
+) -> Result<Material, Error> {
+    materials
+        .get(name)
+        .cloned()
+        .ok_or_else(|| invalid(field, format!("unknown material `{}`", name)))
+}
+
+impl ObjectDesc {
+    /// Builds the shapes, naming fields in errors after `prefix`.
+    fn build(
+        self,
+        prefix: &str,
+        objects: &HashMap<String, Object>,
+        materials: &HashMap<&str, Material>,
+        dir: &Path,
+    ) -> Result<Vec<Arc<dyn Shape>>, Error> {
+        let mut shapes: Vec<Arc<dyn Shape>> = Vec::new();
+        for (i, sphere) in self.spheres.iter().enumerate() {
+            let sphere = sphere.build(&format!("{}spheres[{}]", prefix, i), materials)?;
+            shapes.push(Arc::new(sphere));
+        }
+        for (i, plane) in self.planes.iter().enumerate() {
+            let normal = vec3(plane.normal);
+            if normal.norm() == 0f32 {
+                return Err(invalid(
+                    format!("{}planes[{}].normal", prefix, i),
+                    "must be non-zero",
+                ));
+            }
+            let material = lookup(
+                materials,
+                &plane.material,
+                format!("{}planes[{}].material", prefix, i),
+            )?;
+            let mut shape = Plane::new(vec3(plane.point), normal, material);
+            if let Some(checker) = &plane.checker {
+                let (material, size) =
+                    checker.build(materials, format!("{}planes[{}].checker", prefix, i))?;
+                shape = shape.with_checker(material, size);
+            }
+            shapes.push(Arc::new(shape));
+        }
+        for (i, rectangle) in self.rectangles.iter().enumerate() {
+            let (u, v) = (vec3(rectangle.u), vec3(rectangle.v));
+            if u.cross(v).norm() == 0f32 {
+                return Err(invalid(
+                    format!("{}rectangles[{}]", prefix, i),
+                    "`u` and `v` must span a parallelogram",
+                ));
+            }
+            let material = lookup(
+                materials,
+                &rectangle.material,
+                format!("{}rectangles[{}].material", prefix, i),
+            )?;
+            let mut shape = Rectangle::new(vec3(rectangle.origin), u, v, material);
+            if let Some(checker) = &rectangle.checker {
+                let (material, size) =
+                    checker.build(materials, format!("{}rectangles[{}].checker", prefix, i))?;
+                shape = shape.with_checker(material, size);
+            }
+            shapes.push(Arc::new(shape));
+        }
+        for (i, mesh) in self.meshes.into_iter().enumerate() {
+            let field = |name: &str| format!("{}meshes[{}].{}", prefix, i, name);
+            let count = mesh.positions.len();
+            if let Some(t) = mesh
+                .triangles
+                .iter()
+                .position(|t| t.iter().any(|&v| v as usize >= count))
+            {
+                return Err(invalid(
+                    format!("{}[{}]", field("triangles"), t),
+                    format!(
+                        "vertex index out of range, the mesh has {} positions",
+                        count
+                    ),
+                ));
+            }
+            if mesh.normals.as_ref().is_some_and(|n| n.len() != count) {
+                return Err(invalid(
+                    field("normals"),
+                    "expected one normal per position",
+                ));
+            }
+            if let Some(n) = mesh
+                .normals
+                .iter()
+                .flatten()
+                .position(|&n| vec3(n).norm() == 0f32)
+            {
+                return Err(invalid(
+                    format!("{}[{}]", field("normals"), n),
+                    "must not be zero",
+                ));
+            }
+            if mesh.uvs.as_ref().is_some_and(|uv| uv.len() != count) {
+                return Err(invalid(
+                    field("uvs"),
+                    "expected one coordinate pair per position",
+                ));
+            }
+            let material = lookup(materials, &mesh.material, field("material"))?;
+            let positions = mesh.positions.into_iter().map(vec3).collect();
+            let mut triangle_mesh = TriangleMesh::new(positions, mesh.triangles, material);
+            if let Some(normals) = mesh.normals {
+                triangle_mesh = triangle_mesh.with_normals(normals.into_iter().map(vec3).collect());
+            }
+            if let Some(uvs) = mesh.uvs {
+                triangle_mesh = triangle_mesh.with_uvs(uvs);
+            }
+            shapes.push(Arc::new(triangle_mesh));
+        }
+        for (i, model) in self.models.iter().enumerate() {
+            let material = match &model.material {
+                Some(name) => lookup(materials, name, format!("{}models[{}].material", prefix, i))?,
+                None => Material::default(),
+            };
+            let meshes = obj::load(dir.join(&model.file), material)
+                .map_err(|e| invalid(format!("{}models[{}].file", prefix, i), e.to_string()))?;
+            for mesh in meshes {
+                shapes.push(Arc::new(mesh));
+            }
+        }
+        for (i, cuboid) in self.boxes.iter().enumerate() {
+            let cuboid = cuboid.build(&format!("{}boxes[{}]", prefix, i), materials)?;
+            shapes.push(Arc::new(cuboid));
+        }
+        for (i, cylinder) in self.cylinders.iter().enumerate() {
+            let cylinder = cylinder.build(&format!("{}cylinders[{}]", prefix, i), materials)?;
+            shapes.push(Arc::new(cylinder));
+        }
+        for (i, cone) in self.cones.iter().enumerate() {
+            let cone = cone.build(&format!("{}cones[{}]", prefix, i), materials)?;
+            shapes.push(Arc::new(cone));
+        }
+        for (i, disk) in self.disks.iter().enumerate() {
+            let disk = disk.build(&format!("{}disks[{}]", prefix, i), materials)?;
+            shapes.push(Arc::new(disk));
+        }
+        for (i, torus) in self.tori.iter().enumerate() {
+            let torus = torus.build(&format!("{}tori[{}]", prefix, i), materials)?;
+            shapes.push(Arc::new(torus));
+        }
+        for (i, csg) in self.csg.iter().enumerate() {
+            let csg = csg.build(&format!("{}csg[{}]", prefix, i), objects, materials)?;
+            shapes.push(Arc::new(csg));
+        }
+        Ok(shapes)
+    }
+
+    /// Whether all the shapes enclose a volume.
+    fn is_solid(&self) -> bool {
+        self.planes.is_empty()
+            && self.rectangles.is_empty()
+            && self.meshes.is_empty()
+            && self.models.is_empty()
+            && self.disks.is_empty()
+    }
+
+    /// The objects that CSG operands instance, which must be built first.
+    fn references(&self) -> Vec<&str> {
+        let mut names = Vec::new();
+        for csg in &self.csg {
+            csg.references(&mut names);
+        }
+        names
+    }
+}
+
+impl SphereDesc {
+    fn build(&self, prefix: &str, materials: &HashMap<&str, Material>) -> Result<Sphere, Error> {
+        if self.radius <= 0f32 {
+            return Err(invalid(format!("{}.radius", prefix), "must be positive"));
+        }
+        let material = lookup(materials, &self.material, format!("{}.material", prefix))?;
+        Ok(Sphere::new(vec3(self.center), self.radius, material))
+    }
+}
+
+impl BoxDesc {
+    fn build(&self, prefix: &str, materials: &HashMap<&str, Material>) -> Result<Cuboid, Error> {
+        let field = |name: &str| format!("{}.{}", prefix, name);
+        let (center, size) = match (self.min, self.max, self.center, self.size) {
+            (Some(min), Some(max), None, None) => {
+                let (min, max) = (vec3(min), vec3(max));
+                ((min + max) * 0.5, max - min)
+            }
+            (None, None, Some(center), Some(size)) => (vec3(center), vec3(size)),
+            _ => {
+                return Err(invalid(
+                    prefix.into(),
+                    "needs either `min` and `max` or `center` and `size`",
+                ))
+            }
+        };
+        if size.0.iter().any(|&s| !(s > 0f32 && s.is_finite())) {
+            return Err(if self.size.is_some() {
+                invalid(field("size"), "must be positive")
+            } else {
+                invalid(field("max"), "must be greater than `min` along every axis")
+            });
+        }
+        let material = lookup(materials, &self.material, field("material"))?;
+        let (mut x, mut y) = (Vec3f::new(1f32, 0f32, 0f32), Vec3f::new(0f32, 1f32, 0f32));
+        if let Some(rotate) = &self.rotate {
+            let rotation = rotate.build(&field("rotate"))?;
+            x = rotation.transform_vector(x);
+            y = rotation.transform_vector(y);
+        }
+        Ok(Cuboid::oriented(center, size, x, y, material))
+    }
+}
+
+impl CylinderDesc {
+    fn build(&self, prefix: &str, materials: &HashMap<&str, Material>) -> Result<Cylinder, Error> {
+        let field = |name: &str| format!("{}.{}", prefix, name);
+        if self.radius <= 0f32 {
+            return Err(invalid(field("radius"), "must be positive"));
+        }
+        if self.top == self.base {
+            return Err(invalid(field("top"), "must differ from `base`"));
+        }
+        let material = lookup(materials, &self.material, field("material"))?;
+        Ok(Cylinder::new(
+            vec3(self.base),
+            vec3(self.top),
+            self.radius,
+            material,
+        ))
+    }
+}
+
+impl ConeDesc {
+    fn build(&self, prefix: &str, materials: &HashMap<&str, Material>) -> Result<Cone, Error> {
+        let field = |name: &str| format!("{}.{}", prefix, name);
+        if self.radius <= 0f32 {
+            return Err(invalid(field("radius"), "must be positive"));
+        }
+        if self.apex == self.base {
+            return Err(invalid(field("apex"), "must differ from `base`"));
+        }
+        let material = lookup(materials, &self.material, field("material"))?;
+        let mut cone = Cone::new(vec3(self.base), vec3(self.apex), self.radius, material);
+        if let Some(radius) = self.top_radius {
+            if radius < 0f32 {
+                return Err(invalid(field("top_radius"), "must not be negative"));
+            }
+            if radius >= self.radius {
+                return Err(invalid(field("top_radius"), "must be less than `radius`"));
+            }
+            cone = cone.with_top_radius(radius);
+        }
+        Ok(cone)
+    }
+}
+
+impl DiskDesc {
+    fn build(&self, prefix: &str, materials: &HashMap<&str, Material>) -> Result<Disk, Error> {
+        let field = |name: &str| format!("{}.{}", prefix, name);
+        if self.radius <= 0f32 {
+            return Err(invalid(field("radius"), "must be positive"));
+        }
+        let normal = vec3(self.normal);
+        if normal.norm() == 0f32 {
+            return Err(invalid(field("normal"), "must be non-zero"));
+        }
+        let material = lookup(materials, &self.material, field("material"))?;
+        let mut disk = Disk::new(vec3(self.center), normal, self.radius, material);
+        if let Some(radius) = self.inner_radius {
+            if radius < 0f32 {
+                return Err(invalid(field("inner_radius"), "must not be negative"));
+            }
+            if radius >= self.radius {
+                return Err(invalid(field("inner_radius"), "must be less than `radius`"));
+            }
+            disk = disk.with_inner_radius(radius);
+        }
+        Ok(disk)
+    }
+}
+
+impl TorusDesc {
+    fn build(&self, prefix: &str, materials: &HashMap<&str, Material>) -> Result<Torus, Error> {
+        let field = |name: &str| format!("{}.{}", prefix, name);
+        let axis = vec3(self.axis);
+        if axis.norm() == 0f32 {
+            return Err(invalid(field("axis"), "must be non-zero"));
+        }
+        if !(self.minor_radius > 0f32 && self.minor_radius < self.major_radius) {
+            return Err(invalid(
+                field("minor_radius"),
+                "must be positive and smaller than `major_radius`",
+            ));
+        }
+        let material = lookup(materials, &self.material, field("material"))?;
+        Ok(Torus::new(
+            vec3(self.center),
+            axis,
+            self.major_radius,
+            self.minor_radius,
+            material,
+        ))
+    }
+}
+
+impl InstanceDesc {
+    fn build(
+        &self,
+        prefix: &str,
+        objects: &HashMap<String, Object>,
+        materials: &HashMap<&str, Material>,
+    ) -> Result<Instance, Error> {
+        let field = |name: &str| format!("{}.{}", prefix, name);
+        let object = objects
+            .get(&self.object)
+            .ok_or_else(|| invalid(field("object"), format!("unknown object `{}`", self.object)))?;
+        let transform = self.transform.build(&field("transform"))?;
+        let mut instance = Instance::new(object.shape.clone(), transform)
+            .ok_or_else(|| invalid(field("transform"), "must be invertible"))?;
+        if let Some(name) = &self.material {
+            instance = instance.with_material(lookup(materials, name, field("material"))?);
+        }
+        Ok(instance)
+    }
+}
+
+impl CsgDesc {
+    fn build(
+        &self,
+        prefix: &str,
+        objects: &HashMap<String, Object>,
+        materials: &HashMap<&str, Material>,
+    ) -> Result<Csg, Error> {
+        let operation = match self.operation {
+            OperationDesc::Union => Operation::Union,
+            OperationDesc::Intersection => Operation::Intersection,
+            OperationDesc::Difference => Operation::Difference,
+        };
+        let left = self
+            .left
+            .build(&format!("{}.left", prefix), objects, materials)?;
+        let right = self
+            .right
+            .build(&format!("{}.right", prefix), objects, materials)?;
+        Ok(Csg::new(operation, left, right))
+    }
+
+    fn references<'a>(&'a self, names: &mut Vec<&'a str>) {
+        for operand in [&self.left, &self.right] {
+            match operand {
+                SolidDesc::Sphere(_)
+                | SolidDesc::Box(_)
+                | SolidDesc::Cylinder(_)
+                | SolidDesc::Cone(_)
+                | SolidDesc::Torus(_) => {}
+                SolidDesc::Instance(instance) => names.push(&instance.object),
+                SolidDesc::Csg(csg) => csg.references(names),
+            }
+        }
+    }
+}
+
+impl SolidDesc {
+    fn build(
+        &self,
+        prefix: &str,
+        objects: &HashMap<String, Object>,
+        materials: &HashMap<&str, Material>,
+    ) -> Result<Arc<dyn Shape>, Error> {
+        Ok(match self {
+            SolidDesc::Sphere(sphere) => Arc::new(sphere.build(prefix, materials)?),
+            SolidDesc::Box(cuboid) => Arc::new(cuboid.build(prefix, materials)?),
+            SolidDesc::Cylinder(cylinder) => Arc::new(cylinder.build(prefix, materials)?),
+            SolidDesc::Cone(cone) => Arc::new(cone.build(prefix, materials)?),
+            SolidDesc::Torus(torus) => Arc::new(torus.build(prefix, materials)?),
+            SolidDesc::Instance(instance) => {
+                if objects.get(&instance.object).is_some_and(|o| !o.solid) {
+                    return Err(invalid(
+                        format!("{}.object", prefix),
+                        format!(
+                            "object `{}` does not enclose a volume, only objects without planes, \
+                             rectangles, disks, meshes and models can be combined",
+                            instance.object
+                        ),
+                    ));
+                }
+                Arc::new(instance.build(prefix, objects, materials)?)
+            }
+            SolidDesc::Csg(csg) => Arc::new(csg.build(prefix, objects, materials)?),
+        })
+    }
+}
+
+impl TransformDesc {
+    /// The transform scaling, then rotating, then translating, or the given matrix.
+    fn build(&self, prefix: &str) -> Result<Mat4, Error> {
+        let field = |name: &str| format!("{}.{}", prefix, name);
+        if let Some(matrix) = self.matrix {
+            reject_unused(
+                prefix,
+                &[
+                    ("scale", self.scale.is_some()),
+                    ("rotate", self.rotate.is_some()),
+                    ("translate", self.translate.is_some()),
+                ],
+                "transforms with a `matrix`",
+            )?;
+            return Ok(Mat4(matrix));
+        }
+        let mut transform = Mat4::IDENTITY;
+        if let Some(scale) = &self.scale {
+            let factors = match *scale {
+                ScaleDesc::Uniform(s) => Vec3f::new(s, s, s),
+                ScaleDesc::Axes(s) => vec3(s),
+            };
+            if factors.0.contains(&0f32) {
+                return Err(invalid(field("scale"), "must be non-zero"));
+            }
+            transform = Mat4::scaling(factors);
+        }
+        if let Some(rotate) = &self.rotate {
+            transform = rotate.build(&field("rotate"))? * transform;
+        }
+        if let Some(offset) = self.translate {
+            transform = Mat4::translation(vec3(offset)) * transform;
+        }
+        Ok(transform)
+    }
+}
+
+impl RotateDesc {
+    fn build(&self, prefix: &str) -> Result<Mat4, Error> {
+        let axis = vec3(self.axis);
+        if axis.norm() == 0f32 {
+            return Err(invalid(format!("{}.axis", prefix), "must be non-zero"));
+        }
+        Ok(Mat4::rotation(axis, self.angle.to_radians()))
+    }
+}
+
+impl CheckerDesc {
+    fn build(
+        &self,
+        materials: &HashMap<&str, Material>,
+        field: String,
+    ) -> Result<(Material, f32), Error> {
+        if self.size <= 0f32 {
+            return Err(invalid(format!("{}.size", field), "must be positive"));
+        }
+        let material = lookup(materials, &self.material, format!("{}.material", field))?;
+        Ok((material, self.size))
+    }
+}
+
+impl SceneFile {
+    fn build(self, dir: &Path) -> Result<(Scene, Camera), Error> {
+        let [width, height] = self.resolution;
+        if width == 0 || height == 0 {
+            return Err(invalid("resolution".into(), "must be non-zero"));
+        }
+        let camera = self.camera.build(width, height)?;
+
+        let mut materials = HashMap::new();
+        for (name, desc) in &self.materials {
+            materials.insert(name.as_str(), desc.build(name, dir)?);
+        }
+
+        let mut scene = Scene::new();
+        if let Some(background) = self.background {
+            scene.background = vec3(background);
+        }
+        if let Some(environment) = &self.environment {
+            if self.background.is_some() {
+                return Err(invalid(
+                    "environment".into(),
+                    "cannot be combined with `background`",
+                ));
+            }
+            scene.environment = Some(Arc::new(environment.build(dir)?));
+        }
+        // Objects may combine instances of other objects, which are built first.
+        let mut objects = HashMap::new();
+        let mut pending = self.objects.into_iter().collect::<Vec<_>>();
+        pending.sort_by(|a, b| a.0.cmp(&b.0));
+        while !pending.is_empty() {
+            let ready = pending.iter().position(|(_, object)| {
+                object
+                    .references()
+                    .iter()
+                    .all(|&name| pending.iter().all(|(other, _)| other != name))
+            });
+            let Some(i) = ready else {
+                return Err(invalid(
+                    format!("objects.{}.csg", pending[0].0),
+                    "instances objects that depend on each other",
+                ));
+            };
+            let (name, object) = pending.remove(i);
+            let prefix = format!("objects.{}.", name);
+            let solid = object.is_solid();
+            let mut shapes = object.build(&prefix, &objects, &materials, dir)?;
+            let shape = if shapes.len() == 1 {
+                shapes.remove(0)
+            } else {
+                Arc::new(Group::new(shapes)) as Arc<dyn Shape>
+            };
+            objects.insert(name, Object { shape, solid });
+        }
+        let shapes = ObjectDesc {
+            spheres: self.spheres,
+            planes: self.planes,
+            rectangles: self.rectangles,
+            meshes: self.meshes,
+            models: self.models,
+            boxes: self.boxes,
+            cylinders: self.cylinders,
+            cones: self.cones,
+            disks: self.disks,
+            tori: self.tori,
+            csg: self.csg,
+        };
+        for shape in shapes.build("", &objects, &materials, dir)? {
+            scene.add_shared(shape);
+        }
+        for (i, instance) in self.instances.iter().enumerate() {
+            let prefix = format!("instances[{}]", i);
+            scene.add(instance.build(&prefix, &objects, &materials)?);
+        }
+        for (i, light) in self.lights.iter().enumerate() {
+            scene.add_light(light.build(&format!("lights[{}]", i))?);
+        }
+        Ok((scene, camera))
+    }
+}
+
+/// Parses a scene description, returning the scene and the camera it is viewed from.
+///
+/// Relative paths in the scene are resolved against the current directory.
+pub fn from_str(s: &str) -> Result<(Scene, Camera), Error> {
+    parse(s, Path::new(""))
+}
+
+fn parse(s: &str, dir: &Path) -> Result<(Scene, Camera), Error> {
+    let mut de = serde_json::Deserializer::from_str(s);
+    let scene: SceneFile = serde_path_to_error::deserialize(&mut de).map_err(|e| {
+        let field = e.path().to_string();
+        Error::Parse {
+            field: if field == "." { String::new() } else { field },
+            source: e.into_inner(),
+        }
+    })?;
+    de.end().map_err(|source| Error::Parse {
+        field: String::new(),
+        source,
+    })?;
+    scene.build(dir)
+}
+
+/// Reads and parses the scene file at `path`.
+///
+/// Relative paths in the scene are resolved against the directory of the scene file.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<(Scene, Camera), Error> {
+    let path = path.as_ref();
+    parse(
+        &fs::read_to_string(path)?,
+        path.parent().unwrap_or_else(|| Path::new("")),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::Model;
+
+    /// Loads a scene with the given fields besides its resolution, and an `ivory` material
+    /// unless the fields define the materials.
+    fn load_with(fields: &str) -> Result<(Scene, Camera), Error> {
+        let materials = if fields.contains(r#""materials""#) {
+            ""
+        } else {
+            r#""materials": { "ivory": {} },"#
+        };
+        from_str(&format!(
+            r#"{{ "resolution": [4, 3], {} {} }}"#,
+            materials, fields
+        ))
+    }
+
+    /// Asserts that loading fails on `field` with a message starting with `message`.
+    fn assert_error(fields: &str, field: &str, message: &str) {
+        let error = match load_with(fields) {
+            Ok(_) => panic!("loaded `{}`", fields),
+            Err(error) => error,
+        };
+        let (actual, text) = match &error {
+            Error::Parse { field, source } => (field, source.to_string()),
+            Error::Invalid { field, message } => (field, message.clone()),
+            Error::Io(e) => panic!("{}", e),
+        };
+        assert_eq!(actual, field, "{}", error);
+        assert!(text.starts_with(message), "{}", error);
+        assert_eq!(error.to_string(), format!("{}: {}", field, text));
+    }
+
+    #[test]
+    fn loads_a_valid_scene() {
+        let (_, camera) = load_with(
+            r#""camera": { "fov": 60 },
+               "spheres": [{ "center": [0, 0, -5], "radius": 1, "material": "ivory" }]"#,
+        )
+        .unwrap();
+        assert_eq!((camera.width, camera.height), (4, 3));
+    }
+
+    #[test]
+    fn names_unknown_fields() {
+        assert_error(
+            r#""spheres": [{ "center": [0, 0, -5], "radius": 1, "material": "ivory", "colour": 1 }]"#,
+            "spheres[0].colour",
+            "unknown field `colour`",
+        );
+        assert_error(
+            r#""camera": { "position": [0, 0, 0], "zoom": 2 }"#,
+            "camera.zoom",
+            "unknown field `zoom`",
+        );
+    }
+
+    #[test]
+    fn names_fields_of_the_wrong_type() {
+        assert_error(
+            r#""lights": [{ "position": [0, 0, 0], "intensity": "bright" }]"#,
+            "lights[0].intensity",
+            "invalid type: string \"bright\", expected f32",
+        );
+        assert_error(
+            r#""spheres": [{ "center": [0, 0], "radius": 1, "material": "ivory" }]"#,
+            "spheres[0].center",
+            "invalid length 2",
+        );
+    }
+
+    #[test]
+    fn names_nested_fields() {
+        assert_error(
+            r#""csg": [{
+                "operation": "union",
+                "left": { "type": "sphere", "center": [0, 0, 0], "radius": 1, "material": "ivory" },
+                "right": { "type": "cone", "base": [0, 0, 0], "apex": [0, 1, 0], "radius": 1,
+                           "top_radius": 2, "material": "ivory" }
+            }]"#,
+            "csg[0].right.top_radius",
+            "must be less than `radius`",
+        );
+    }
+
+    #[test]
+    fn textures_any_phong_color_channel() {
+        let (scene, _) = load_with(
+            r#""materials": {
+                   "mirror": {
+                       "albedo": [0.0, 0.5, 1.0, 0.0],
+                       "specular_map": { "pattern": "noise" },
+                       "reflection_map": { "pattern": "checker", "colors": [[1, 0, 0], [0, 0, 1]] }
+                   }
+               },
+               "spheres": [{ "center": [0, 0, -5], "radius": 1, "material": "mirror" }]"#,
+        )
+        .unwrap();
+        let hit = scene
+            .intersect(Vec3f::default(), Vec3f::new(0.0, 0.0, -1.0))
+            .unwrap();
+        let maps = &hit.material.maps;
+        assert!(maps.specular.is_some() && maps.reflection.is_some() && maps.refraction.is_none());
+        let reflection = hit.material.phong_at(hit.uv, hit.point).albedo[2];
+        assert!(reflection.0 == [1.0, 0.0, 0.0] || reflection.0 == [0.0, 0.0, 1.0]);
+        assert_error(
+            r#""materials": { "gold": { "metallic": 1, "specular_map": { "pattern": "noise" } } }"#,
+            "materials.gold.specular_map",
+            "is not used by the `metallic_roughness` model",
+        );
+    }
+
+    #[test]
+    fn textures_scalar_channels() {
+        let (scene, _) = load_with(
+            r#""materials": {
+                   "smudged": {
+                       "metallic": 1,
+                       "roughness_map": { "pattern": "checker", "colors": [[0.2, 0.2, 0.2], [0.8, 0.8, 0.8]] }
+                   }
+               },
+               "spheres": [{ "center": [0, 0, -5], "radius": 1, "material": "smudged" }]"#,
+        )
+        .unwrap();
+        let hit = scene
+            .intersect(Vec3f::default(), Vec3f::new(0.0, 0.0, -1.0))
+            .unwrap();
+        // A roughness map alone implies the model and holds the roughness.
+        let material = hit.material;
+        assert!(material.maps.roughness.is_some());
+        assert_eq!(
+            material.model,
+            Model::MetallicRoughness {
+                metallic: 1.0,
+                roughness: 1.0
+            }
+        );
+        assert_error(
+            r#""materials": { "plastic": { "metallic_map": { "pattern": "noise" }, "albedo": [1, 0, 0, 0] } }"#,
+            "materials.plastic.albedo",
+            "is not used by the `metallic_roughness` model",
+        );
+        assert_error(
+            r#""materials": { "glass": { "model": "dielectric", "specular_exponent_map": { "pattern": "noise" } } }"#,
+            "materials.glass.specular_exponent_map",
+            "is not used by the `dielectric` model",
+        );
+    }
+
+    #[test]
+    fn names_invalid_values() {
+        assert_error(
+            r#""spheres": [{ "center": [0, 0, -5], "radius": -1, "material": "ivory" }]"#,
+            "spheres[0].radius",
+            "must be positive",
+        );
+        assert_error(
+            r#""camera": { "fov": 180 }"#,
+            "camera.fov",
+            "must be between 0 and 180 degrees",
+        );
+        assert_error(
+            r#""disks": [{ "center": [0, 0, -5], "normal": [0, 0, 1], "radius": 1,
+                          "inner_radius": 1, "material": "ivory" }]"#,
+            "disks[0].inner_radius",
+            "must be less than `radius`",
+        );
+    }
+
+    #[test]
+    fn rejects_zero_mesh_normals() {
+        assert_error(
+            r#""meshes": [{
+                "positions": [[0, 0, -5], [1, 0, -5], [0, 1, -5]],
+                "triangles": [[0, 1, 2]],
+                "normals": [[0, 0, 1], [0, 0, 0], [0, 0, 1]],
+                "material": "ivory"
+            }]"#,
+            "meshes[0].normals[1]",
+            "must not be zero",
+        );
+    }
+
+    #[test]
+    fn rejects_unphysical_lights() {
+        assert_error(
+            r#""lights": [{ "position": [0, 0, 0], "intensity": -1 }]"#,
+            "lights[0].intensity",
+            "must be finite and not negative",
+        );
+        // Too large for an `f32`.
+        assert_error(
+            r#""lights": [{ "direction": [0, -1, 0], "intensity": 1e39 }]"#,
+            "lights[0].intensity",
+            "must be finite and not negative",
+        );
+        assert_error(
+            r#""lights": [
+                { "position": [0, 0, 0], "intensity": 1 },
+                { "position": [0, 0, 0], "intensity": 1, "color": [1, -0.5, 1] }
+            ]"#,
+            "lights[1].color",
+            "must be finite and not negative",
+        );
+        assert!(load_with(
+            r#""lights": [{ "position": [0, 0, 0], "intensity": 0, "color": [0, 0.5, 1] }]"#
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn reports_invalid_materials_in_name_order() {
+        let invalid = |name: &str| {
+            format!(
+                r#""{}": {{ "metallic": 1, "specular_map": {{ "pattern": "noise" }} }}"#,
+                name
+            )
+        };
+        let names = ["zinc", "gold", "tin", "copper", "iron", "silver"];
+        let materials = names.iter().map(|&name| invalid(name)).collect::<Vec<_>>();
+        assert_error(
+            &format!(r#""materials": {{ {} }}"#, materials.join(", ")),
+            "materials.copper.specular_map",
+            "is not used by the `metallic_roughness` model",
+        );
+    }
+
+    #[test]
+    fn names_bad_references() {
+        assert_error(
+            r#""spheres": [{ "center": [0, 0, -5], "radius": 1, "material": "ebony" }]"#,
+            "spheres[0].material",
+            "unknown material `ebony`",
+        );
+        assert_error(
+            r#""instances": [{ "object": "teapot" }]"#,
+            "instances[0].object",
+            "unknown object `teapot`",
+        );
+    }
+}
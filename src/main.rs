@@ -1,307 +1,179 @@
-use crate::geometry::Vec3f;
-use png::HasParameters;
-use std::f32::consts::FRAC_PI_2;
-use std::fs::File;
-use std::io::BufWriter;
-use std::ops::{Index, IndexMut};
-
-mod geometry;
-
-type Result<T> = std::result::Result<T, Box<std::error::Error>>;
-
-#[derive(Debug, Clone, Copy)]
-struct Light {
-    position: Vec3f,
-    intensity: f32,
-}
-
-impl Light {
-    fn new(position: Vec3f, intensity: f32) -> Self {
-        Self {
-            position,
-            intensity,
-        }
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tinyraytracer::environment::Environment;
+use tinyraytracer::output::{self, Format};
+use tinyraytracer::tonemap::{Operator, ToneMapping};
+use tinyraytracer::{hdr, loader, Fov, Renderer, Scene};
+
+mod cli;
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+fn run(options: cli::Options) -> Result<()> {
+    let (mut scene, mut camera) = match &options.scene {
+        Some(path) => loader::load(path).map_err(|e| format!("{}: {}", path.display(), e))?,
+        None => loader::from_str(include_str!("../scenes/default.json"))
+            .map_err(|e| format!("default scene: {}", e))?,
+    };
+    override_background(&mut scene, &options)?;
+    let mut renderer = Renderer::new();
+    if let Some(width) = options.width {
+        camera.width = width;
     }
-}
-
-#[derive(Debug, Clone, Copy)]
-struct Material {
-    albedo: [f32; 4],
-    diffuse_color: Vec3f,
-    refractive_index: f32,
-    specular_exponent: f32,
-}
-
-impl Default for Material {
-    fn default() -> Self {
-        Self {
-            albedo: [1f32, 0f32, 0f32, 0f32],
-            diffuse_color: Vec3f::default(),
-            refractive_index: 1f32,
-            specular_exponent: 0f32,
-        }
+    if let Some(height) = options.height {
+        camera.height = height;
     }
-}
-
-impl Material {
-    fn new(r: f32, albedo: [f32; 4], color: Vec3f, spec: f32) -> Self {
-        Self {
-            albedo,
-            diffuse_color: color,
-            refractive_index: r,
-            specular_exponent: spec,
-        }
+    if let Some(fov) = options.fov {
+        camera.fov = Fov::Vertical(fov.to_radians());
     }
-}
-
-#[derive(Debug, Clone, Copy)]
-struct Sphere {
-    center: Vec3f,
-    radius: f32,
-    material: Material,
-}
-
-impl Sphere {
-    fn new(center: Vec3f, radius: f32, material: Material) -> Self {
-        Self {
-            center,
-            radius,
-            material,
-        }
+    if let Some(hfov) = options.hfov {
+        camera.fov = Fov::Horizontal(hfov.to_radians());
     }
-
-    fn ray_intersect(&self, p: Vec3f, direction: Vec3f, t0: &mut f32) -> bool {
-        let vcp = self.center - p;
-        let tca = vcp * direction;
-        let d2 = vcp * vcp - tca * tca;
-        if d2 > self.radius * self.radius {
-            return false;
-        }
-        let thc = (self.radius * self.radius - d2).sqrt();
-        *t0 = tca - thc;
-        let t1 = tca + thc;
-        if *t0 < 0f32 {
-            *t0 = t1;
-        }
-        if *t0 < 0f32 {
-            return false;
-        }
-        true
+    if let Some(eye) = options.eye {
+        camera.position = eye;
     }
-}
-
-#[derive(Debug)]
-struct Image {
-    framebuffer: Vec<Vec3f>,
-    width: usize,
-    fov: f32,
-    spheres: Vec<Sphere>,
-    lights: Vec<Light>,
-}
-
-impl Index<usize> for Image {
-    type Output = [Vec3f];
-
-    #[inline]
-    fn index(&self, index: usize) -> &Self::Output {
-        let i = index * self.width;
-        &self.framebuffer[i..i + self.width]
+    if let Some(target) = options.target {
+        camera.target = target;
     }
-}
-
-impl IndexMut<usize> for Image {
-    #[inline]
-    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
-        let i = index * self.width;
-        &mut self.framebuffer[i..i + self.width]
+    if let Some(up) = options.up {
+        camera.up = up;
     }
-}
-
-impl Image {
-    fn new(width: usize, height: usize) -> Self {
-        Self {
-            framebuffer: vec![Vec3f::default(); width * height],
-            width,
-            fov: FRAC_PI_2,
-            spheres: Vec::new(),
-            lights: Vec::new(),
-        }
+    let forward = camera.target - camera.position;
+    if forward.norm() == 0f32 || forward.cross(camera.up).norm() == 0f32 {
+        return Err("the camera must look at a point other than its position, \
+                    and its up direction must not be parallel to the view direction"
+            .into());
     }
-
-    fn width(&self) -> usize {
-        self.width
+    if let Some(max_depth) = options.max_depth {
+        renderer.max_depth = max_depth;
     }
-
-    fn height(&self) -> usize {
-        self.framebuffer.len() / self.width
+    if let Some(threads) = options.threads {
+        renderer.threads = threads;
+    }
+    if let Some(integrator) = options.integrator {
+        renderer.integrator = integrator;
+    }
+    if let Some(samples) = options.samples {
+        renderer.samples = samples;
+    }
+    if let Some(sampler) = options.sampler {
+        renderer.sampler = sampler;
+    }
+    if let Some(filter) = options.filter {
+        renderer.filter = filter;
+    }
+    if let Some(shadow_samples) = options.shadow_samples {
+        renderer.shadow_samples = shadow_samples;
     }
 
-    fn scene_intersect(&self, orig: Vec3f, direction: Vec3f) -> Option<(Vec3f, Vec3f, Material)> {
-        let mut spheres_dist = std::f32::MAX;
-        let mut hit = Vec3f::default();
-        let mut n = Vec3f::default();
-        let mut material = Material::default();
-        let mut dist_i = 0f32;
-        for sphere in &self.spheres {
-            if sphere.ray_intersect(orig, direction, &mut dist_i) && dist_i < spheres_dist {
-                spheres_dist = dist_i;
-                hit = orig + direction * dist_i;
-                n = (hit - sphere.center).normalize();
-                material = sphere.material;
-            }
-        }
+    let mut tone_mapping = ToneMapping::new();
+    if let Some(operator) = options.tonemap {
+        tone_mapping.operator = operator;
+    }
+    if let Some(white) = options.white {
+        tone_mapping.operator = Operator::ExtendedReinhard { white };
+    }
+    if let Some(exposure) = options.exposure {
+        tone_mapping.exposure = exposure;
+    }
+    if let Some(encoding) = options.encoding {
+        tone_mapping.encoding = encoding;
+    }
+    tone_mapping.dither = options.dither;
 
-        let mut checkerboard_dist = std::f32::MAX;
-        if direction.0[1].abs() > 1e-3 {
-            let d = -(orig.0[1] + 4f32) / direction.0[1]; // the checkerboard plane has equation y = -4
-            let pt = orig + direction * d;
-            if d > 0f32
-                && pt.0[0].abs() < 10f32
-                && pt.0[2] < -10f32
-                && pt.0[2] > -30f32
-                && d < spheres_dist
-            {
-                checkerboard_dist = d;
-                hit = pt;
-                n = Vec3f::new(0.0, 1.0, 0.0);
-                material.diffuse_color =
-                    if ((0.5 * hit.0[0] + 1000f32) as usize + (0.5 * hit.0[2]) as usize) & 1 != 0 {
-                        Vec3f::new(1.0, 1.0, 1.0)
-                    } else {
-                        Vec3f::new(1.0, 0.7, 0.3)
-                    };
-                material.diffuse_color = material.diffuse_color * 0.3;
+    let mut outputs = options.outputs;
+    if outputs.is_empty() {
+        outputs.push(PathBuf::from("image.png"));
+    }
+    for output in &outputs {
+        if output == Path::new("-") {
+            if options.turntable.is_some() {
+                return Err("a turntable cannot be written to stdout".into());
             }
-        }
-
-        if spheres_dist.min(checkerboard_dist) < 1000f32 {
-            Some((hit, n, material))
-        } else {
-            None
+        } else if Format::from_path(output).is_none() {
+            return Err(format!("{}: unknown image format", output.display()).into());
         }
     }
 
-    fn cast_ray(&self, orig: Vec3f, dir: Vec3f, depth: Option<usize>) -> Vec3f {
-        if let Some((point, n, material)) = depth.and_then(|_| self.scene_intersect(orig, dir)) {
-            let reflect_dir = dir.reflect(n).normalize();
-            let refract_dir = dir.refract(n, material.refractive_index);
-            let reflect_orig = if reflect_dir * n < 0f32 {
-                point - n * 1e-3
-            } else {
-                point + n * 1e-3
-            };
-            let refract_orig = if refract_dir * n < 0f32 {
-                point - n * 1e-3
+    let frames = options.turntable.unwrap_or(1);
+    for frame in 0..frames {
+        let angle = 2f32 * std::f32::consts::PI * frame as f32 / frames as f32;
+        let framebuffer = renderer.render(&scene, &camera.orbit(angle));
+        for output in &outputs {
+            let result = if output == Path::new("-") {
+                output::write_png(&framebuffer, &tone_mapping, io::stdout().lock())
+            } else if options.turntable.is_some() {
+                output::save(
+                    &framebuffer,
+                    frame_path(output, frame, frames),
+                    &tone_mapping,
+                )
             } else {
-                point + n * 1e-3
+                output::save(&framebuffer, output, &tone_mapping)
             };
-            let reflect_color = self.cast_ray(
-                reflect_orig,
-                reflect_dir,
-                depth.map(|d| d + 1).filter(|d| *d <= 4),
-            );
-            let refract_color = self.cast_ray(
-                refract_orig,
-                refract_dir,
-                depth.map(|d| d + 1).filter(|d| *d <= 4),
-            );
-            let mut diffuse_light_intensity = 0f32;
-            let mut specular_light_intensity = 0f32;
-            for light in &self.lights {
-                let light_dir = (light.position - point).normalize();
-                let light_distance = (light.position - point).norm();
-                let shadow_orig = if light_dir * n < 0f32 {
-                    point - n * 1e-3
-                } else {
-                    point + n * 1e-3
-                };
-                if let Some((shadow_pt, _, _)) = self.scene_intersect(shadow_orig, light_dir) {
-                    if (shadow_pt - shadow_orig).norm() < light_distance {
-                        continue;
-                    }
-                }
-                diffuse_light_intensity += light.intensity * 0f32.max(light_dir * n);
-                specular_light_intensity += 0f32
-                    .max(-(-light_dir).reflect(n) * dir)
-                    .powf(material.specular_exponent)
-                    * light.intensity;
-            }
-            material.diffuse_color * diffuse_light_intensity * material.albedo[0]
-                + Vec3f::new(1.0, 1.0, 1.0) * specular_light_intensity * material.albedo[1]
-                + reflect_color * material.albedo[2]
-                + refract_color * material.albedo[3]
-        } else {
-            Vec3f::new(0.2, 0.7, 0.8) // background color
+            result.map_err(|e| format!("{}: {}", output.display(), e))?;
         }
     }
+    Ok(())
+}
 
-    fn render(&mut self) -> Result<()> {
-        let w = self.width() as f32;
-        let h = self.height() as f32;
-
-        for i in 0..self.height() {
-            for j in 0..self.width() {
-                let dir_x = (j as f32 + 0.5) - w / 2f32;
-                let dir_y = -(i as f32 + 0.5) + h / 2f32;
-                let dir_z = -h / (2f32 * (self.fov / 2f32).tan());
-                self[i][j] = self.cast_ray(
-                    Vec3f::new(0f32, 0f32, 0f32),
-                    Vec3f::new(dir_x, dir_y, dir_z).normalize(),
-                    Some(0),
-                );
-            }
-        }
-
-        let w = BufWriter::new(File::create("image.png")?);
-        let mut encoder = png::Encoder::new(w, self.width() as u32, self.height() as u32);
-        encoder.set(png::ColorType::RGB).set(png::BitDepth::Eight);
-        let mut writer = encoder.write_header()?;
-        writer.write_image_data(
-            &self
-                .framebuffer
-                .iter_mut()
-                .flat_map(|p| {
-                    let max = p.0[0].max(p.0[1].max(p.0[2]));
-                    if max > 1f32 {
-                        *p = *p * (1f32 / max);
-                    }
-                    p.as_bytes()
-                })
-                .collect::<Vec<_>>(),
-        )?;
-        Ok(())
+/// Applies `--background` or `--environment` to what rays that miss the scene see. A background
+/// color also drops an environment given by the scene file, which would otherwise hide it.
+fn override_background(scene: &mut Scene, options: &cli::Options) -> Result<()> {
+    if let Some(background) = options.background {
+        scene.background = background;
+        scene.environment = None;
     }
-
-    fn add_sphere(&mut self, sphere: Sphere) {
-        self.spheres.push(sphere);
+    if let Some(path) = &options.environment {
+        let image = hdr::load(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        scene.environment = Some(Arc::new(Environment::new(image)));
     }
+    Ok(())
+}
 
-    fn add_light(&mut self, light: Light) {
-        self.lights.push(light);
+/// Inserts the zero-padded frame number before the extension of `path`.
+fn frame_path(path: &Path, frame: usize, frames: usize) -> PathBuf {
+    let digits = (frames - 1).to_string().len().max(3);
+    let mut name = path.file_stem().unwrap_or_default().to_os_string();
+    name.push(format!("_{:0width$}", frame, width = digits));
+    if let Some(extension) = path.extension() {
+        name.push(".");
+        name.push(extension);
     }
+    path.with_file_name(name)
 }
 
 fn main() {
-    let mut image = Image::new(1024, 768);
-
-    let ivory = Material::new(1.0, [0.6, 0.3, 0.1, 0.0], Vec3f::new(0.4, 0.4, 0.3), 50.0);
-    let glass = Material::new(1.5, [0.0, 0.5, 0.1, 0.8], Vec3f::new(0.6, 0.7, 0.8), 125.0);
-    let red_rubber = Material::new(1.0, [0.9, 0.1, 0.0, 0.0], Vec3f::new(0.3, 0.1, 0.1), 10.0);
-    let mirror = Material::new(
-        1.0,
-        [0.0, 10.0, 0.8, 0.0],
-        Vec3f::new(1.0, 1.0, 1.0),
-        1425.0,
-    );
-
-    image.add_sphere(Sphere::new(Vec3f::new(-3.0, 0.0, -16.0), 2.0, ivory));
-    image.add_sphere(Sphere::new(Vec3f::new(-1.0, -1.5, -12.0), 2.0, glass));
-    image.add_sphere(Sphere::new(Vec3f::new(1.5, -0.5, -18.0), 3.0, red_rubber));
-    image.add_sphere(Sphere::new(Vec3f::new(7.0, 5.0, -18.0), 4.0, mirror));
-
-    image.add_light(Light::new(Vec3f::new(-20.0, 20.0, 20.0), 1.5));
-    image.add_light(Light::new(Vec3f::new(30.0, 50.0, -25.0), 1.8));
-    image.add_light(Light::new(Vec3f::new(30.0, 20.0, 30.0), 1.7));
+    let options = match cli::Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(cli::Error::Help) => {
+            print!("{}", cli::USAGE);
+            return;
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(2);
+        }
+    };
+    if let Err(e) = run(options) {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
 
-    image.render().expect("render");
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tinyraytracer::Vec3f;
+
+    #[test]
+    fn background_replaces_the_scene_environment() {
+        let (mut scene, _) = loader::load("scenes/environment.json").unwrap();
+        assert!(scene.environment.is_some());
+        let options = cli::Options::parse(vec!["--background=0.2,0.7,0.8".to_string()]).unwrap();
+        override_background(&mut scene, &options).unwrap();
+        assert!(scene.environment.is_none());
+        assert_eq!(scene.background, Vec3f::new(0.2, 0.7, 0.8));
+    }
 }
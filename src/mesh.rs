@@ -0,0 +1,277 @@
+//! Triangle meshes.
+
+use crate::bvh::Bvh;
+use crate::geometry::{Aabb, Vec3f};
+use crate::scene::Material;
+use crate::shape::{Hit, Shape};
+
+/// Intersects a ray with the triangle `v`, returning the distance and the barycentric weights of
+/// the three vertices at the hit point.
+///
+/// This is the watertight algorithm of Woop, Benthin and Wald: rays through a shared edge or
+/// vertex hit at least one of the adjacent triangles. Both sides of the triangle are hit.
+pub fn ray_triangle_intersect(orig: Vec3f, dir: Vec3f, v: [Vec3f; 3]) -> Option<(f32, [f32; 3])> {
+    // Permute the axes so that the ray direction is largest along z.
+    let abs = [dir.0[0].abs(), dir.0[1].abs(), dir.0[2].abs()];
+    let kz = if abs[0] > abs[1] {
+        if abs[0] > abs[2] {
+            0
+        } else {
+            2
+        }
+    } else if abs[1] > abs[2] {
+        1
+    } else {
+        2
+    };
+    let (mut kx, mut ky) = ((kz + 1) % 3, (kz + 2) % 3);
+    if dir.0[kz] < 0f32 {
+        std::mem::swap(&mut kx, &mut ky);
+    }
+
+    // Shear the vertices into a space where the ray starts at the origin and points along +z.
+    let sx = dir.0[kx] / dir.0[kz];
+    let sy = dir.0[ky] / dir.0[kz];
+    let sz = 1f32 / dir.0[kz];
+    let a = v[0] - orig;
+    let b = v[1] - orig;
+    let c = v[2] - orig;
+    let (ax, ay) = (a.0[kx] - sx * a.0[kz], a.0[ky] - sy * a.0[kz]);
+    let (bx, by) = (b.0[kx] - sx * b.0[kz], b.0[ky] - sy * b.0[kz]);
+    let (cx, cy) = (c.0[kx] - sx * c.0[kz], c.0[ky] - sy * c.0[kz]);
+
+    let mut u = cx * by - cy * bx;
+    let mut v = ax * cy - ay * cx;
+    let mut w = bx * ay - by * ax;
+    // Fall back to double precision on edges to stay watertight.
+    if u == 0f32 || v == 0f32 || w == 0f32 {
+        u = (f64::from(cx) * f64::from(by) - f64::from(cy) * f64::from(bx)) as f32;
+        v = (f64::from(ax) * f64::from(cy) - f64::from(ay) * f64::from(cx)) as f32;
+        w = (f64::from(bx) * f64::from(ay) - f64::from(by) * f64::from(ax)) as f32;
+    }
+    if (u < 0f32 || v < 0f32 || w < 0f32) && (u > 0f32 || v > 0f32 || w > 0f32) {
+        return None;
+    }
+    let det = u + v + w;
+    if det == 0f32 {
+        return None;
+    }
+
+    let t = (u * a.0[kz] + v * b.0[kz] + w * c.0[kz]) * sz / det;
+    if t > 0f32 {
+        Some((t, [u / det, v / det, w / det]))
+    } else {
+        None
+    }
+}
+
+/// The derivatives of the position on the triangle `p` with respect to the texture coordinates,
+/// given the coordinates `uv` of its vertices. Both are zero if the coordinates are degenerate.
+fn position_derivatives(p: [Vec3f; 3], uv: [[f32; 2]; 3]) -> (Vec3f, Vec3f) {
+    let (dp02, dp12) = (p[0] - p[2], p[1] - p[2]);
+    let duv02 = [uv[0][0] - uv[2][0], uv[0][1] - uv[2][1]];
+    let duv12 = [uv[1][0] - uv[2][0], uv[1][1] - uv[2][1]];
+    let det = duv02[0] * duv12[1] - duv02[1] * duv12[0];
+    if det.abs() < 1e-12 {
+        return (Vec3f::default(), Vec3f::default());
+    }
+    let inv = 1f32 / det;
+    (
+        (dp02 * duv12[1] - dp12 * duv02[1]) * inv,
+        (dp12 * duv02[0] - dp02 * duv12[0]) * inv,
+    )
+}
+
+/// An indexed triangle mesh with optional per-vertex normals and texture coordinates.
+#[derive(Debug, Clone)]
+pub struct TriangleMesh {
+    positions: Vec<Vec3f>,
+    normals: Vec<Vec3f>,
+    uvs: Vec<[f32; 2]>,
+    triangles: Vec<[u32; 3]>,
+    pub material: Material,
+    bvh: Bvh,
+}
+
+impl TriangleMesh {
+    /// Creates a mesh from vertex positions and triangles given as triples of vertex indices.
+    ///
+    /// The front face of a triangle is the one from which its vertices appear counter-clockwise.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a triangle refers to a vertex that does not exist.
+    ///
+    /// ```
+    /// use tinyraytracer::{Material, Shape, TriangleMesh, Vec3f};
+    ///
+    /// let quad = TriangleMesh::new(
+    ///     vec![
+    ///         Vec3f::new(-1.0, -1.0, -5.0),
+    ///         Vec3f::new(1.0, -1.0, -5.0),
+    ///         Vec3f::new(1.0, 1.0, -5.0),
+    ///         Vec3f::new(-1.0, 1.0, -5.0),
+    ///     ],
+    ///     vec![[0, 1, 2], [0, 2, 3]],
+    ///     Material::default(),
+    /// );
+    /// let hit = quad
+    ///     .intersect(Vec3f::default(), Vec3f::new(0.0, 0.0, -1.0), f32::MAX)
+    ///     .unwrap();
+    /// assert!((hit.t - 5.0).abs() < 1e-5);
+    /// assert_eq!(hit.normal, Vec3f::new(0.0, 0.0, 1.0));
+    /// ```
+    pub fn new(positions: Vec<Vec3f>, triangles: Vec<[u32; 3]>, material: Material) -> Self {
+        assert!(
+            triangles
+                .iter()
+                .flatten()
+                .all(|&i| (i as usize) < positions.len()),
+            "triangle vertex index out of range"
+        );
+        let bounds = triangles
+            .iter()
+            .map(|t| {
+                t.iter()
+                    .fold(Aabb::empty(), |b, &i| b.grow(positions[i as usize]))
+            })
+            .collect::<Vec<_>>();
+        Self {
+            positions,
+            normals: Vec::new(),
+            uvs: Vec::new(),
+            triangles,
+            material,
+            bvh: Bvh::new(&bounds),
+        }
+    }
+
+    /// Sets per-vertex shading normals, which are interpolated across the faces.
+    ///
+    /// Hits keep the flat face normal as their geometric normal.
+    ///
+    /// # Panics
+    ///
+    /// Panics unless there is exactly one normal per vertex.
+    ///
+    /// ```
+    /// use tinyraytracer::{Material, Shape, TriangleMesh, Vec3f};
+    ///
+    /// let tilted = Vec3f::new(0.6, 0.0, 0.8);
+    /// let triangle = TriangleMesh::new(
+    ///     vec![
+    ///         Vec3f::new(-1.0, -1.0, -5.0),
+    ///         Vec3f::new(1.0, -1.0, -5.0),
+    ///         Vec3f::new(0.0, 1.0, -5.0),
+    ///     ],
+    ///     vec![[0, 1, 2]],
+    ///     Material::default(),
+    /// )
+    /// .with_normals(vec![tilted; 3]);
+    /// let hit = triangle
+    ///     .intersect(Vec3f::default(), Vec3f::new(0.0, 0.0, -1.0), f32::MAX)
+    ///     .unwrap();
+    /// assert!((hit.normal - tilted).norm() < 1e-5);
+    /// assert_eq!(hit.geometric_normal, Vec3f::new(0.0, 0.0, 1.0));
+    /// ```
+    pub fn with_normals(mut self, normals: Vec<Vec3f>) -> Self {
+        assert_eq!(normals.len(), self.positions.len(), "one normal per vertex");
+        self.normals = normals.into_iter().map(Vec3f::normalize).collect();
+        self
+    }
+
+    /// Sets per-vertex texture coordinates.
+    ///
+    /// # Panics
+    ///
+    /// Panics unless there is exactly one coordinate pair per vertex.
+    pub fn with_uvs(mut self, uvs: Vec<[f32; 2]>) -> Self {
+        assert_eq!(
+            uvs.len(),
+            self.positions.len(),
+            "one texture coordinate per vertex"
+        );
+        self.uvs = uvs;
+        self
+    }
+
+    pub fn positions(&self) -> &[Vec3f] {
+        &self.positions
+    }
+
+    pub fn triangles(&self) -> &[[u32; 3]] {
+        &self.triangles
+    }
+
+    fn vertices(&self, triangle: usize) -> [Vec3f; 3] {
+        let [a, b, c] = self.triangles[triangle];
+        [
+            self.positions[a as usize],
+            self.positions[b as usize],
+            self.positions[c as usize],
+        ]
+    }
+}
+
+impl Shape for TriangleMesh {
+    fn bounds(&self) -> Option<Aabb> {
+        Some(self.bvh.bounds())
+    }
+
+    fn intersect(&self, orig: Vec3f, dir: Vec3f, t_max: f32) -> Option<Hit<'_>> {
+        let mut barycentric = [0f32; 3];
+        let (triangle, t) = self.bvh.intersect(orig, dir, t_max, |i, t_max| {
+            let (t, b) = ray_triangle_intersect(orig, dir, self.vertices(i))?;
+            if t < t_max {
+                barycentric = b;
+                Some(t)
+            } else {
+                None
+            }
+        })?;
+
+        let [p0, p1, p2] = self.vertices(triangle);
+        let indices = self.triangles[triangle].map(|i| i as usize);
+        let interpolate =
+            |v: [Vec3f; 3]| v[0] * barycentric[0] + v[1] * barycentric[1] + v[2] * barycentric[2];
+        let face_normal = (p1 - p0).cross(p2 - p0).normalize();
+        let (normal, geometric_normal) = if self.normals.is_empty() {
+            (face_normal, face_normal)
+        } else {
+            // The face normal is turned to the side the vertex normals say is outside.
+            let normal = interpolate(indices.map(|i| self.normals[i])).normalize();
+            if face_normal * normal < 0f32 {
+                (normal, -face_normal)
+            } else {
+                (normal, face_normal)
+            }
+        };
+        // Without texture coordinates, the barycentric coordinates of the second and third
+        // vertices serve instead.
+        let uvs = if self.uvs.is_empty() {
+            [[0f32, 0f32], [1f32, 0f32], [0f32, 1f32]]
+        } else {
+            indices.map(|i| self.uvs[i])
+        };
+        let uv = interpolate(uvs.map(|[u, v]| Vec3f::new(u, v, 0f32)));
+        let (dpdu, dpdv) = position_derivatives([p0, p1, p2], uvs);
+        Some(Hit {
+            t,
+            point: orig + dir * t,
+            normal,
+            geometric_normal,
+            uv: [uv.0[0], uv.0[1]],
+            dpdu,
+            dpdv,
+            material: &self.material,
+        })
+    }
+
+    fn occluded(&self, orig: Vec3f, dir: Vec3f, t_max: f32) -> bool {
+        self.bvh.occluded(orig, dir, t_max, |i, t_max| {
+            ray_triangle_intersect(orig, dir, self.vertices(i))
+                .map(|(t, _)| t)
+                .filter(|&t| t < t_max)
+        })
+    }
+}
@@ -0,0 +1,138 @@
+//! Solid noise functions for procedural textures.
+//!
+//! The functions are deterministic: the pseudo-random gradients and feature points are hashed from
+//! the integer lattice, so no tables need to be seeded.
+
+use crate::geometry::Vec3f;
+
+/// Octaves summed by [`fbm`] and [`turbulence`] unless configured otherwise.
+pub const DEFAULT_OCTAVES: u32 = 6;
+
+/// Mixes the integer lattice point (`x`, `y`, `z`) into 32 pseudo-random bits.
+fn hash(x: i32, y: i32, z: i32) -> u32 {
+    let mut h = (x as u32).wrapping_mul(0x8da6_b343)
+        ^ (y as u32).wrapping_mul(0xd816_3841)
+        ^ (z as u32).wrapping_mul(0xcb1a_b31f);
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x7feb_352d);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x846c_a68b);
+    h ^ (h >> 16)
+}
+
+/// The dot product of `(x, y, z)` with one of the twelve gradients pointing at the edges of a cube.
+fn gradient(hash: u32, x: f32, y: f32, z: f32) -> f32 {
+    match hash % 12 {
+        0 => x + y,
+        1 => -x + y,
+        2 => x - y,
+        3 => -x - y,
+        4 => x + z,
+        5 => -x + z,
+        6 => x - z,
+        7 => -x - z,
+        8 => y + z,
+        9 => -y + z,
+        10 => y - z,
+        _ => -y - z,
+    }
+}
+
+/// Perlin's quintic interpolation weight, with zero first and second derivatives at 0 and 1.
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6f32 - 15f32) + 10f32)
+}
+
+fn lerp(t: f32, a: f32, b: f32) -> f32 {
+    a + t * (b - a)
+}
+
+/// Perlin's gradient noise, smoothly varying between about -1 and 1 with features one unit apart.
+/// It is zero at integer points.
+///
+/// ```
+/// use tinyraytracer::noise;
+/// use tinyraytracer::Vec3f;
+///
+/// assert_eq!(noise::perlin(Vec3f::new(3.0, -1.0, 2.0)), 0.0);
+/// let n = noise::perlin(Vec3f::new(0.3, 1.7, -2.2));
+/// assert!(n != 0.0 && n.abs() <= 1.0);
+/// ```
+pub fn perlin(p: Vec3f) -> f32 {
+    let [x, y, z] = p.0;
+    let (x0, y0, z0) = (x.floor(), y.floor(), z.floor());
+    let (fx, fy, fz) = (x - x0, y - y0, z - z0);
+    let (x0, y0, z0) = (x0 as i32, y0 as i32, z0 as i32);
+    let corner = |dx: i32, dy: i32, dz: i32| {
+        gradient(
+            hash(x0 + dx, y0 + dy, z0 + dz),
+            fx - dx as f32,
+            fy - dy as f32,
+            fz - dz as f32,
+        )
+    };
+    let (u, v, w) = (fade(fx), fade(fy), fade(fz));
+    let n = lerp(
+        w,
+        lerp(
+            v,
+            lerp(u, corner(0, 0, 0), corner(1, 0, 0)),
+            lerp(u, corner(0, 1, 0), corner(1, 1, 0)),
+        ),
+        lerp(
+            v,
+            lerp(u, corner(0, 0, 1), corner(1, 0, 1)),
+            lerp(u, corner(0, 1, 1), corner(1, 1, 1)),
+        ),
+    );
+    n.clamp(-1f32, 1f32)
+}
+
+/// Sums `octaves` layers of noise, each of twice the frequency and half the amplitude of the one
+/// before, normalized to stay between -1 and 1 ("fractional Brownian motion").
+pub fn fbm(p: Vec3f, octaves: u32) -> f32 {
+    octave_sum(p, octaves, perlin)
+}
+
+/// Like [`fbm`], but summing the absolute value of each layer, which gives creases where the noise
+/// crosses zero. It is between 0 and 1.
+pub fn turbulence(p: Vec3f, octaves: u32) -> f32 {
+    octave_sum(p, octaves, |p| perlin(p).abs())
+}
+
+fn octave_sum(p: Vec3f, octaves: u32, noise: impl Fn(Vec3f) -> f32) -> f32 {
+    let (mut sum, mut total) = (0f32, 0f32);
+    let (mut frequency, mut amplitude) = (1f32, 1f32);
+    for _ in 0..octaves.max(1) {
+        sum += amplitude * noise(p * frequency);
+        total += amplitude;
+        frequency *= 2f32;
+        amplitude *= 0.5;
+    }
+    sum / total
+}
+
+/// Worley's cellular noise: the distance from `p` to the nearest of a set of feature points
+/// scattered one per unit cell, clamped to 1.
+pub fn worley(p: Vec3f) -> f32 {
+    let cell = p.0.map(|c| c.floor() as i32);
+    let mut nearest = f32::INFINITY;
+    for dz in -1..=1 {
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                let (x, y, z) = (cell[0] + dx, cell[1] + dy, cell[2] + dz);
+                let h = hash(x, y, z);
+                // Three independent offsets within the cell from the bits of one hash.
+                let offset = |shift: u32| ((h >> shift) & 0x3ff) as f32 / 1024f32;
+                let feature = Vec3f::new(
+                    x as f32 + offset(0),
+                    y as f32 + offset(10),
+                    z as f32 + offset(20),
+                );
+                let d = feature - p;
+                nearest = nearest.min(d * d);
+            }
+        }
+    }
+    nearest.sqrt().min(1f32)
+}
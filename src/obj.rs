@@ -0,0 +1,613 @@
+//! Wavefront OBJ and MTL import.
+//!
+//! Supported OBJ directives are `v`, `vt`, `vn`, `f`, `g`, `o`, `s`, `usemtl` and `mtllib`.
+//! Polygons are triangulated as fans and negative (relative) indices are resolved. Faces are
+//! grouped into one [`TriangleMesh`] per group and material. Zero-length `vn` normals are
+//! reported as an error.
+//!
+//! MTL materials map onto [`Material`] as follows:
+//!
+//! | MTL | Material |
+//! |-----|----------|
+//! | `Kd` | `diffuse_color` |
+//! | `map_Kd` | `texture`, an sRGB image relative to the MTL file |
+//! | `bump`, `map_bump` | `bump`, a linear [height](Bump::Height) map with the default scale |
+//! | `norm` | `bump`, a linear tangent-space [normal](Bump::Normal) map |
+//! | `Ks` | `albedo[1]`, the mean of the three channels |
+//! | `map_Ks` | `maps.specular`, an sRGB image tinting the highlights |
+//! | `Ns` | `specular_exponent` |
+//! | `Ni` | `refractive_index` |
+//! | `d`, `Tr` | `albedo[3]` is the transparency, `albedo[0]` the opacity |
+//! | `Pm`, `Pr` | `metallic` and `roughness` of the [`MetallicRoughness`](Model::MetallicRoughness) model |
+//!
+//! `Ka`, `Ke`, `Tf`, `illum` and `sharpness` have no equivalent and are ignored. Any other
+//! directive, such as other texture maps, texture map options or free-form geometry, is reported
+//! as an error.
+
+use crate::geometry::Vec3f;
+use crate::mesh::TriangleMesh;
+use crate::scene::{Bump, Material, Model};
+use crate::texture::ImageTexture;
+use crate::tonemap::Encoding;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// An error encountered while importing an OBJ or MTL file.
+#[derive(Debug)]
+pub enum Error {
+    Io(PathBuf, io::Error),
+    Parse {
+        file: PathBuf,
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(file, e) => write!(f, "{}: {}", file.display(), e),
+            Error::Parse {
+                file,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", file.display(), line, message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Tracks the position in a file for error messages.
+struct Cursor<'a> {
+    file: &'a Path,
+    line: usize,
+}
+
+impl Cursor<'_> {
+    fn error(&self, message: impl Into<String>) -> Error {
+        Error::Parse {
+            file: self.file.to_path_buf(),
+            line: self.line,
+            message: message.into(),
+        }
+    }
+
+    fn floats<'b, I: Iterator<Item = &'b str>>(
+        &self,
+        directive: &str,
+        args: I,
+        min: usize,
+        max: usize,
+    ) -> Result<Vec<f32>, Error> {
+        let values = args
+            .map(|a| {
+                a.parse::<f32>()
+                    .map_err(|_| self.error(format!("`{}`: invalid number `{}`", directive, a)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if values.len() < min || values.len() > max {
+            return Err(self.error(if min == max {
+                format!("`{}` expects {} numbers", directive, min)
+            } else {
+                format!("`{}` expects {} to {} numbers", directive, min, max)
+            }));
+        }
+        Ok(values)
+    }
+
+    fn vec3<'b, I: Iterator<Item = &'b str>>(
+        &self,
+        directive: &str,
+        args: I,
+    ) -> Result<Vec3f, Error> {
+        let v = self.floats(directive, args, 3, 3)?;
+        Ok(Vec3f::new(v[0], v[1], v[2]))
+    }
+}
+
+fn read(path: &Path) -> Result<String, Error> {
+    fs::read_to_string(path).map_err(|e| Error::Io(path.to_path_buf(), e))
+}
+
+/// Parses the materials of an MTL file.
+pub fn parse_mtl(source: &str, file: &Path) -> Result<HashMap<String, Material>, Error> {
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, Material)> = None;
+    let mut cursor = Cursor { file, line: 0 };
+    for (n, line) in source.lines().enumerate() {
+        cursor.line = n + 1;
+        let line = line.split('#').next().unwrap_or("");
+        let mut args = line.split_whitespace();
+        let directive = match args.next() {
+            Some(d) => d,
+            None => continue,
+        };
+        if directive == "newmtl" {
+            let name = args.collect::<Vec<_>>().join(" ");
+            if name.is_empty() {
+                return Err(cursor.error("`newmtl` expects a name"));
+            }
+            if let Some((name, material)) = current.take() {
+                materials.insert(name, material);
+            }
+            current = Some((name, Material::default()));
+            continue;
+        }
+        let material = match current.as_mut() {
+            Some((_, material)) => material,
+            None => return Err(cursor.error(format!("`{}` before `newmtl`", directive))),
+        };
+        match directive {
+            "Kd" => material.diffuse_color = cursor.vec3(directive, args)?,
+            "Ks" => {
+                let ks = cursor.vec3(directive, args)?;
+                material.albedo[1] = (ks.0[0] + ks.0[1] + ks.0[2]) / 3f32;
+            }
+            "Ns" => material.specular_exponent = cursor.floats(directive, args, 1, 1)?[0],
+            "Ni" => material.refractive_index = cursor.floats(directive, args, 1, 1)?[0],
+            "d" | "Tr" => {
+                let value = cursor.floats(directive, args, 1, 1)?[0];
+                let opacity = if directive == "d" {
+                    value
+                } else {
+                    1f32 - value
+                };
+                material.albedo[0] = opacity;
+                material.albedo[3] = 1f32 - opacity;
+            }
+            "Pm" | "Pr" => {
+                let value = cursor.floats(directive, args, 1, 1)?[0];
+                let (mut metallic, mut roughness) = match material.model {
+                    Model::MetallicRoughness {
+                        metallic,
+                        roughness,
+                    } => (metallic, roughness),
+                    Model::Phong | Model::Dielectric { .. } => (0f32, 0.5),
+                };
+                if directive == "Pm" {
+                    metallic = value;
+                } else {
+                    roughness = value;
+                }
+                material.model = Model::MetallicRoughness {
+                    metallic,
+                    roughness,
+                };
+            }
+            "map_Kd" | "map_Ks" | "bump" | "map_bump" | "map_Bump" | "norm" => {
+                let args = args.collect::<Vec<_>>();
+                match args.first() {
+                    None => {
+                        return Err(cursor.error(format!("`{}` expects a file name", directive)))
+                    }
+                    Some(option) if option.starts_with('-') => {
+                        return Err(cursor
+                            .error(format!("unsupported `{}` option `{}`", directive, option)))
+                    }
+                    Some(_) => {}
+                }
+                let name = args.join(" ");
+                let path = file.parent().unwrap_or_else(|| Path::new("")).join(&name);
+                // Only color maps are sRGB encoded.
+                let encoding = if matches!(directive, "map_Kd" | "map_Ks") {
+                    Encoding::Srgb
+                } else {
+                    Encoding::Linear
+                };
+                let texture = Arc::new(ImageTexture::load(&path, encoding).map_err(|e| {
+                    cursor.error(format!("`{}` {}: {}", directive, path.display(), e))
+                })?);
+                match directive {
+                    "map_Kd" => material.texture = Some(texture),
+                    "map_Ks" => material.maps.specular = Some(texture),
+                    "norm" => material.bump = Some(Bump::Normal(texture)),
+                    _ => {
+                        material.bump = Some(Bump::Height {
+                            texture,
+                            scale: Bump::DEFAULT_SCALE,
+                        })
+                    }
+                }
+            }
+            "Ka" | "Ke" | "Tf" | "illum" | "sharpness" => {}
+            _ => return Err(cursor.error(format!("unsupported MTL directive `{}`", directive))),
+        }
+    }
+    if let Some((name, material)) = current {
+        materials.insert(name, material);
+    }
+    Ok(materials)
+}
+
+/// Triangles sharing a group and material, with vertices deduplicated by their attribute indices.
+#[derive(Default)]
+struct Part {
+    vertices: HashMap<[usize; 3], u32>,
+    positions: Vec<Vec3f>,
+    normals: Vec<Option<Vec3f>>,
+    uvs: Vec<Option<[f32; 2]>>,
+    triangles: Vec<[u32; 3]>,
+}
+
+impl Part {
+    /// Returns the index of the vertex combining the given attributes, adding it if needed.
+    fn vertex(
+        &mut self,
+        v: usize,
+        vt: Option<usize>,
+        vn: Option<usize>,
+        positions: &[Vec3f],
+        uvs: &[[f32; 2]],
+        normals: &[Vec3f],
+    ) -> u32 {
+        let key = [v, vt.unwrap_or(usize::MAX), vn.unwrap_or(usize::MAX)];
+        if let Some(&id) = self.vertices.get(&key) {
+            return id;
+        }
+        let id = self.positions.len() as u32;
+        self.positions.push(positions[v]);
+        self.uvs.push(vt.map(|i| uvs[i]));
+        self.normals.push(vn.map(|i| normals[i]));
+        self.vertices.insert(key, id);
+        id
+    }
+
+    fn into_mesh(self, material: Material) -> TriangleMesh {
+        let mut mesh = TriangleMesh::new(self.positions, self.triangles, material);
+        if let Some(normals) = self.normals.into_iter().collect::<Option<Vec<_>>>() {
+            mesh = mesh.with_normals(normals);
+        }
+        if let Some(uvs) = self.uvs.into_iter().collect::<Option<Vec<_>>>() {
+            mesh = mesh.with_uvs(uvs);
+        }
+        mesh
+    }
+}
+
+/// Resolves a 1-based or negative OBJ index against `count` elements.
+fn resolve(cursor: &Cursor, index: &str, count: usize, kind: &str) -> Result<usize, Error> {
+    let i = index
+        .parse::<isize>()
+        .map_err(|_| cursor.error(format!("invalid {} index `{}`", kind, index)))?;
+    let resolved = if i > 0 { i - 1 } else { count as isize + i };
+    if i == 0 || resolved < 0 || resolved >= count as isize {
+        return Err(cursor.error(format!(
+            "{} index {} out of range, {} defined so far",
+            kind, i, count
+        )));
+    }
+    Ok(resolved as usize)
+}
+
+/// Parses an OBJ file into meshes.
+///
+/// `mtl` is called with the file name of every `mtllib` and returns its contents and the path
+/// used in error messages. Faces without a material get `default_material`.
+///
+/// ```
+/// use std::path::Path;
+/// use tinyraytracer::{obj, Material};
+///
+/// let source = "
+/// v -1 -1 -5
+/// v 1 -1 -5
+/// v 1 1 -5
+/// v -1 1 -5
+/// f -4 -3 -2 -1
+/// ";
+/// let meshes = obj::parse_obj(source, Path::new("quad.obj"), Material::default(), |name| {
+///     panic!("no material library {} expected", name)
+/// })
+/// .unwrap();
+/// assert_eq!(meshes.len(), 1);
+/// assert_eq!(meshes[0].triangles(), &[[0, 1, 2], [0, 2, 3]]);
+///
+/// let error = obj::parse_obj("v 0 0 0\nl 1 1\n", Path::new("line.obj"), Material::default(), |_| {
+///     unreachable!()
+/// });
+/// assert_eq!(
+///     error.unwrap_err().to_string(),
+///     "line.obj:2: unsupported OBJ directive `l`"
+/// );
+/// ```
+pub fn parse_obj<F>(
+    source: &str,
+    file: &Path,
+    default_material: Material,
+    mut mtl: F,
+) -> Result<Vec<TriangleMesh>, Error>
+where
+    F: FnMut(&str) -> Result<(String, PathBuf), Error>,
+{
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut uvs = Vec::new();
+    let mut materials = HashMap::new();
+    let mut parts: Vec<((String, Option<String>), Part)> = Vec::new();
+    let mut part_index = HashMap::new();
+    let mut group = String::new();
+    let mut material: Option<String> = None;
+    let mut cursor = Cursor { file, line: 0 };
+
+    for (n, line) in source.lines().enumerate() {
+        cursor.line = n + 1;
+        let line = line.split('#').next().unwrap_or("");
+        let mut args = line.split_whitespace();
+        let directive = match args.next() {
+            Some(d) => d,
+            None => continue,
+        };
+        match directive {
+            "v" => {
+                // An optional fourth `w` component is ignored.
+                let v = cursor.floats(directive, args, 3, 4)?;
+                positions.push(Vec3f::new(v[0], v[1], v[2]));
+            }
+            "vn" => {
+                let normal = cursor.vec3(directive, args)?;
+                if normal.norm() == 0f32 {
+                    return Err(cursor.error("`vn` must not be zero"));
+                }
+                normals.push(normal);
+            }
+            "vt" => {
+                let v = cursor.floats(directive, args, 1, 3)?;
+                uvs.push([v[0], v.get(1).copied().unwrap_or(0f32)]);
+            }
+            "g" | "o" => group = args.collect::<Vec<_>>().join(" "),
+            "s" => {}
+            "usemtl" => {
+                let name = args.collect::<Vec<_>>().join(" ");
+                if !materials.contains_key(&name) {
+                    return Err(cursor.error(format!("unknown material `{}`", name)));
+                }
+                material = Some(name);
+            }
+            "mtllib" => {
+                for name in args {
+                    let (source, path) = mtl(name)?;
+                    materials.extend(parse_mtl(&source, &path)?);
+                }
+            }
+            "f" => {
+                let mut corners = Vec::new();
+                for corner in args {
+                    let mut fields = corner.split('/');
+                    let v = resolve(
+                        &cursor,
+                        fields.next().unwrap_or(""),
+                        positions.len(),
+                        "vertex",
+                    )?;
+                    let vt = match fields.next() {
+                        Some("") | None => None,
+                        Some(i) => Some(resolve(&cursor, i, uvs.len(), "texture coordinate")?),
+                    };
+                    let vn = match fields.next() {
+                        Some("") | None => None,
+                        Some(i) => Some(resolve(&cursor, i, normals.len(), "normal")?),
+                    };
+                    if fields.next().is_some() {
+                        return Err(cursor.error(format!("invalid face vertex `{}`", corner)));
+                    }
+                    corners.push((v, vt, vn));
+                }
+                if corners.len() < 3 {
+                    return Err(cursor.error("a face needs at least three vertices"));
+                }
+
+                let key = (group.clone(), material.clone());
+                let index = *part_index.entry(key.clone()).or_insert_with(|| {
+                    parts.push((key, Part::default()));
+                    parts.len() - 1
+                });
+                let part = &mut parts[index].1;
+                let ids = corners
+                    .iter()
+                    .map(|&(v, vt, vn)| part.vertex(v, vt, vn, &positions, &uvs, &normals))
+                    .collect::<Vec<_>>();
+                for k in 1..ids.len() - 1 {
+                    part.triangles.push([ids[0], ids[k], ids[k + 1]]);
+                }
+            }
+            _ => return Err(cursor.error(format!("unsupported OBJ directive `{}`", directive))),
+        }
+    }
+
+    Ok(parts
+        .into_iter()
+        .map(|((_, material), part)| {
+            let material =
+                material.map_or_else(|| default_material.clone(), |name| materials[&name].clone());
+            part.into_mesh(material)
+        })
+        .collect())
+}
+
+/// Loads the OBJ file at `path`, reading material libraries relative to its directory.
+pub fn load<P: AsRef<Path>>(
+    path: P,
+    default_material: Material,
+) -> Result<Vec<TriangleMesh>, Error> {
+    let path = path.as_ref();
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    parse_obj(&read(path)?, path, default_material, |name| {
+        let mtl = dir.join(name);
+        Ok((read(&mtl)?, mtl))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shape::Shape;
+
+    fn parse(source: &str) -> Result<Vec<TriangleMesh>, Error> {
+        parse_obj(source, Path::new("test.obj"), Material::default(), |name| {
+            Ok((
+                "newmtl red\nKd 1 0 0\nnewmtl blue\nKd 0 0 1\n".to_string(),
+                PathBuf::from(name),
+            ))
+        })
+    }
+
+    #[test]
+    fn resolves_negative_indices() {
+        let meshes = parse(
+            "
+v 0 0 0
+v 1 0 0
+v 0 1 0
+f 1 2 3
+v 0 0 1
+f -4 -3 -1
+",
+        )
+        .unwrap();
+        assert_eq!(meshes.len(), 1);
+        assert_eq!(meshes[0].triangles(), &[[0, 1, 2], [0, 1, 3]]);
+        assert_eq!(meshes[0].positions()[3], Vec3f::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn triangulates_polygons_as_fans() {
+        let meshes = parse(
+            "
+v 0 0 0
+v 1 0 0
+v 2 1 0
+v 1 2 0
+v 0 1 0
+f 1 2 3 4 5
+",
+        )
+        .unwrap();
+        assert_eq!(meshes[0].triangles(), &[[0, 1, 2], [0, 2, 3], [0, 3, 4]]);
+    }
+
+    #[test]
+    fn reads_face_vertex_attributes() {
+        let source = "
+v -1 -1 -5
+v 1 -1 -5
+v 0 1 -5
+vt 0 0
+vt 1 0
+vt 0 1
+vn 0 0 2
+vn 0 0 1
+vn 0 0 1
+f 1/1/1 2/2/2 3/3/3
+f 1//1 2//2 3//3
+f 1/1 2/2 3/3
+";
+        let meshes = parse(source).unwrap();
+        // The same positions with other attributes are separate vertices.
+        assert_eq!(meshes[0].positions().len(), 9);
+        assert_eq!(meshes[0].triangles().len(), 3);
+
+        // Each variant alone, so that the nearest hit is the face under test.
+        let hit_first_face = |line: &str| {
+            let source = source.lines().filter(|l| !l.starts_with('f'));
+            let source = source.chain([line]).collect::<Vec<_>>().join("\n");
+            let meshes = parse(&source).unwrap();
+            let hit = meshes[0]
+                .intersect(
+                    Vec3f::new(0.0, -0.5, 0.0),
+                    Vec3f::new(0.0, 0.0, -1.0),
+                    f32::MAX,
+                )
+                .unwrap();
+            (hit.normal, hit.uv)
+        };
+        let (normal, uv) = hit_first_face("f 1/1/1 2/2/2 3/3/3");
+        assert!((normal - Vec3f::new(0.0, 0.0, 1.0)).norm() < 1e-5);
+        assert!((uv[0] - 0.375).abs() < 1e-5 && (uv[1] - 0.25).abs() < 1e-5);
+        let (normal, _) = hit_first_face("f 1//1 2//2 3//3");
+        assert!((normal - Vec3f::new(0.0, 0.0, 1.0)).norm() < 1e-5);
+        let (_, uv) = hit_first_face("f 1/3 2/1 3/2");
+        assert!((uv[0] - 0.25).abs() < 1e-5 && (uv[1] - 0.375).abs() < 1e-5);
+    }
+
+    #[test]
+    fn groups_faces_by_material() {
+        let meshes = parse(
+            "
+mtllib colors.mtl
+v 0 0 0
+v 1 0 0
+v 0 1 0
+f 1 2 3
+usemtl red
+f 1 2 3
+usemtl blue
+f 1 2 3
+",
+        )
+        .unwrap();
+        let colors = meshes
+            .iter()
+            .map(|mesh| mesh.material.diffuse_color)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            colors,
+            [
+                Material::default().diffuse_color,
+                Vec3f::new(1.0, 0.0, 0.0),
+                Vec3f::new(0.0, 0.0, 1.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn maps_mtl_fields() {
+        let materials = parse_mtl(
+            "
+newmtl glass # a comment
+Kd 0.1 0.2 0.3
+Ks 0.3 0.6 0.9
+Ns 120
+Ni 1.5
+d 0.25
+",
+            Path::new("test.mtl"),
+        )
+        .unwrap();
+        let glass = &materials["glass"];
+        assert_eq!(glass.diffuse_color, Vec3f::new(0.1, 0.2, 0.3));
+        assert!((glass.albedo[1] - 0.6).abs() < 1e-6);
+        assert_eq!(glass.specular_exponent, 120.0);
+        assert_eq!(glass.refractive_index, 1.5);
+        assert_eq!((glass.albedo[0], glass.albedo[3]), (0.25, 0.75));
+    }
+
+    #[test]
+    fn reports_out_of_range_indices() {
+        let error = parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 4\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "test.obj:4: vertex index 4 out of range, 3 defined so far"
+        );
+        let error = parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 -4\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "test.obj:4: vertex index -4 out of range, 3 defined so far"
+        );
+        let error = parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1//1 2 3\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "test.obj:4: normal index 1 out of range, 0 defined so far"
+        );
+    }
+
+    #[test]
+    fn rejects_zero_normals() {
+        let error = parse("vn 0 0 0\n").unwrap_err();
+        assert_eq!(error.to_string(), "test.obj:1: `vn` must not be zero");
+    }
+}
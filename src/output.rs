@@ -0,0 +1,449 @@
+//! Encoding of rendered framebuffers into image files.
+//!
+//! The 8-bit formats are tone mapped as configured by a [`ToneMapping`], while the high dynamic
+//! range formats (PFM, Radiance HDR and OpenEXR) store the linear radiance values as they are.
+
+use crate::framebuffer::Framebuffer;
+use crate::geometry::Vec3f;
+use crate::tonemap::ToneMapping;
+use png::HasParameters;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// An output image format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// 8-bit RGB PNG.
+    Png,
+    /// 8-bit binary PPM (`P6`).
+    Ppm,
+    /// 32-bit float RGB Portable Float Map.
+    Pfm,
+    /// Radiance RGBE, with 8-bit mantissas sharing an exponent.
+    Hdr,
+    /// OpenEXR with uncompressed 32-bit float channels.
+    Exr,
+}
+
+impl Format {
+    /// Guesses the format from the extension of `path`.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "png" => Some(Format::Png),
+            "ppm" => Some(Format::Ppm),
+            "pfm" => Some(Format::Pfm),
+            "hdr" => Some(Format::Hdr),
+            "exr" => Some(Format::Exr),
+            _ => None,
+        }
+    }
+}
+
+/// Encodes the framebuffer as a PNG into `w`.
+///
+/// ```
+/// use tinyraytracer::tonemap::ToneMapping;
+/// use tinyraytracer::{output, Camera, Renderer, Scene};
+///
+/// let framebuffer = Renderer::new().render(&Scene::new(), &Camera::new(4, 3));
+/// let mut png = Vec::new();
+/// output::write_png(&framebuffer, &ToneMapping::default(), &mut png).unwrap();
+/// assert_eq!(&png[1..4], b"PNG");
+/// ```
+pub fn write_png<W: Write>(
+    framebuffer: &Framebuffer,
+    tone_mapping: &ToneMapping,
+    w: W,
+) -> io::Result<()> {
+    let mut encoder = png::Encoder::new(w, framebuffer.width() as u32, framebuffer.height() as u32);
+    encoder.set(png::ColorType::RGB).set(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&tone_mapping.to_rgb8(framebuffer))?;
+    Ok(())
+}
+
+/// Encodes the framebuffer as a binary PPM into `w`.
+pub fn write_ppm<W: Write>(
+    framebuffer: &Framebuffer,
+    tone_mapping: &ToneMapping,
+    mut w: W,
+) -> io::Result<()> {
+    write!(
+        w,
+        "P6\n{} {}\n255\n",
+        framebuffer.width(),
+        framebuffer.height()
+    )?;
+    w.write_all(&tone_mapping.to_rgb8(framebuffer))?;
+    w.flush()
+}
+
+/// Encodes the framebuffer as a little-endian color PFM into `w`.
+///
+/// ```
+/// use tinyraytracer::{hdr, output, Framebuffer, Vec3f};
+///
+/// let mut framebuffer = Framebuffer::new(2, 2);
+/// framebuffer[0][1] = Vec3f::new(12.5, 0.0, -1.0);
+/// let mut pfm = Vec::new();
+/// output::write_pfm(&framebuffer, &mut pfm).unwrap();
+/// assert_eq!(hdr::read_pfm(&pfm[..]).unwrap().pixels(), framebuffer.pixels());
+/// ```
+pub fn write_pfm<W: Write>(framebuffer: &Framebuffer, mut w: W) -> io::Result<()> {
+    write!(
+        w,
+        "PF\n{} {}\n-1.0\n",
+        framebuffer.width(),
+        framebuffer.height()
+    )?;
+    for y in (0..framebuffer.height()).rev() {
+        for pixel in &framebuffer[y] {
+            for channel in pixel.0 {
+                w.write_all(&channel.to_le_bytes())?;
+            }
+        }
+    }
+    w.flush()
+}
+
+/// Encodes the framebuffer as a run-length encoded Radiance RGBE image into `w`. Negative values
+/// are stored as zero.
+///
+/// ```
+/// use tinyraytracer::{hdr, output, Framebuffer, Vec3f};
+///
+/// let mut framebuffer = Framebuffer::new(16, 2);
+/// framebuffer[1][3] = Vec3f::new(1000.0, 1.0, 0.001);
+/// let mut rgbe = Vec::new();
+/// output::write_hdr(&framebuffer, &mut rgbe).unwrap();
+///
+/// let decoded = hdr::read_hdr(&rgbe[..]).unwrap();
+/// assert_eq!(decoded[0][3].0, [0.0; 3]);
+/// // The channels share the exponent of the brightest one.
+/// assert!((decoded[1][3].0[0] - 1000.0).abs() < 4.0);
+/// assert!((decoded[1][3].0[1] - 1.0).abs() < 4.0);
+/// ```
+pub fn write_hdr<W: Write>(framebuffer: &Framebuffer, mut w: W) -> io::Result<()> {
+    let (width, height) = (framebuffer.width(), framebuffer.height());
+    write!(
+        w,
+        "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+        height, width
+    )?;
+    let mut channel = Vec::with_capacity(width);
+    for y in 0..height {
+        let scanline = framebuffer[y]
+            .iter()
+            .map(|&p| to_rgbe(p))
+            .collect::<Vec<_>>();
+        // Run-length encoding is only defined for these widths.
+        if !(8..0x8000).contains(&width) {
+            for rgbe in &scanline {
+                w.write_all(rgbe)?;
+            }
+            continue;
+        }
+        w.write_all(&[2, 2, (width >> 8) as u8, width as u8])?;
+        for c in 0..4 {
+            channel.clear();
+            channel.extend(scanline.iter().map(|rgbe| rgbe[c]));
+            write_runs(&mut w, &channel)?;
+        }
+    }
+    w.flush()
+}
+
+/// Converts linear RGB to 8-bit mantissas sharing the exponent of the largest channel.
+fn to_rgbe(p: Vec3f) -> [u8; 4] {
+    let p = Vec3f(p.0.map(|c| c.max(0f32)));
+    let max = p.max_component();
+    if max.is_nan() || max < 1e-32 {
+        return [0; 4];
+    }
+    // Infinities saturate like any other value too bright for the largest exponent.
+    let max = max.min(f32::MAX);
+    let mut exponent = max.log2().floor() as i32 + 1;
+    if max >= 2f32.powi(exponent) {
+        exponent += 1;
+    }
+    let exponent = exponent.clamp(-128, 127);
+    let scale = 256f32 / 2f32.powi(exponent);
+    let [r, g, b] = p.0.map(|c| (c * scale).min(255f32) as u8);
+    [r, g, b, (exponent + 128) as u8]
+}
+
+/// Writes one channel of a scanline as runs of a repeated byte and stretches of literal bytes.
+fn write_runs<W: Write>(w: &mut W, bytes: &[u8]) -> io::Result<()> {
+    const MIN_RUN: usize = 4;
+    let run_at = |i: usize| {
+        let max = (bytes.len() - i).min(127);
+        (1..max).take_while(|&k| bytes[i + k] == bytes[i]).count() + 1
+    };
+    let mut i = 0;
+    while i < bytes.len() {
+        let run = run_at(i);
+        if run >= MIN_RUN {
+            w.write_all(&[128 + run as u8, bytes[i]])?;
+            i += run;
+            continue;
+        }
+        let start = i;
+        while i < bytes.len() && i - start < 128 && run_at(i) < MIN_RUN {
+            i += 1;
+        }
+        w.write_all(&[(i - start) as u8])?;
+        w.write_all(&bytes[start..i])?;
+    }
+    Ok(())
+}
+
+/// Encodes the framebuffer as a single-part scanline OpenEXR image with uncompressed 32-bit float
+/// `R`, `G` and `B` channels into `w`.
+///
+/// ```
+/// use tinyraytracer::{output, Framebuffer};
+///
+/// let mut exr = Vec::new();
+/// output::write_exr(&Framebuffer::new(3, 2), &mut exr).unwrap();
+/// assert_eq!(&exr[..4], &[0x76, 0x2f, 0x31, 0x01]);
+/// ```
+pub fn write_exr<W: Write>(framebuffer: &Framebuffer, mut w: W) -> io::Result<()> {
+    const FLOAT: i32 = 2;
+    let (width, height) = (framebuffer.width(), framebuffer.height());
+    let mut header = Vec::new();
+    header.extend_from_slice(&[0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0]);
+    let mut attribute = |name: &str, kind: &str, value: &[u8]| {
+        for s in [name, kind] {
+            header.extend_from_slice(s.as_bytes());
+            header.push(0);
+        }
+        header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+        header.extend_from_slice(value);
+    };
+    // Channels are stored in alphabetical order.
+    let mut channels = Vec::new();
+    for name in ["B", "G", "R"] {
+        channels.extend_from_slice(name.as_bytes());
+        channels.push(0);
+        channels.extend_from_slice(&FLOAT.to_le_bytes());
+        channels.extend_from_slice(&[0; 4]);
+        channels.extend_from_slice(&1i32.to_le_bytes());
+        channels.extend_from_slice(&1i32.to_le_bytes());
+    }
+    channels.push(0);
+    attribute("channels", "chlist", &channels);
+    attribute("compression", "compression", &[0]);
+    let window = [0, 0, width as i32 - 1, height as i32 - 1]
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect::<Vec<_>>();
+    attribute("dataWindow", "box2i", &window);
+    attribute("displayWindow", "box2i", &window);
+    attribute("lineOrder", "lineOrder", &[0]);
+    attribute("pixelAspectRatio", "float", &1f32.to_le_bytes());
+    attribute("screenWindowCenter", "v2f", &[0; 8]);
+    attribute("screenWindowWidth", "float", &1f32.to_le_bytes());
+    header.push(0);
+    w.write_all(&header)?;
+
+    // Each scanline is a chunk of its y coordinate, its size and the channels one after another,
+    // located through a table of offsets from the start of the file.
+    let chunk_size = 8 + width * 3 * 4;
+    let first = header.len() + height * 8;
+    for y in 0..height {
+        w.write_all(&((first + y * chunk_size) as u64).to_le_bytes())?;
+    }
+    for y in 0..height {
+        w.write_all(&(y as i32).to_le_bytes())?;
+        w.write_all(&((width * 3 * 4) as i32).to_le_bytes())?;
+        for c in [2, 1, 0] {
+            for pixel in &framebuffer[y] {
+                w.write_all(&pixel.0[c].to_le_bytes())?;
+            }
+        }
+    }
+    w.flush()
+}
+
+/// Encodes the framebuffer in the given format into `w`, tone mapping it if the format is 8-bit.
+pub fn write<W: Write>(
+    framebuffer: &Framebuffer,
+    format: Format,
+    tone_mapping: &ToneMapping,
+    w: W,
+) -> io::Result<()> {
+    match format {
+        Format::Png => write_png(framebuffer, tone_mapping, w),
+        Format::Ppm => write_ppm(framebuffer, tone_mapping, w),
+        Format::Pfm => write_pfm(framebuffer, w),
+        Format::Hdr => write_hdr(framebuffer, w),
+        Format::Exr => write_exr(framebuffer, w),
+    }
+}
+
+/// Writes the framebuffer to `path`, choosing the format from the file extension.
+pub fn save<P: AsRef<Path>>(
+    framebuffer: &Framebuffer,
+    path: P,
+    tone_mapping: &ToneMapping,
+) -> io::Result<()> {
+    let path = path.as_ref();
+    let format = Format::from_path(path).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "unknown image format, expected a .png, .ppm, .pfm, .hdr or .exr extension",
+        )
+    })?;
+    write(
+        framebuffer,
+        format,
+        tone_mapping,
+        BufWriter::new(File::create(path)?),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hdr;
+
+    fn runs(bytes: &[u8]) -> Vec<u8> {
+        let mut encoded = Vec::new();
+        write_runs(&mut encoded, bytes).unwrap();
+        encoded
+    }
+
+    /// The pixel data of a Radiance HDR file, after the resolution line.
+    fn hdr_body(framebuffer: &Framebuffer) -> Vec<u8> {
+        let mut file = Vec::new();
+        write_hdr(framebuffer, &mut file).unwrap();
+        let header = format!(
+            "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+            framebuffer.height(),
+            framebuffer.width()
+        );
+        assert!(file.starts_with(header.as_bytes()));
+        file.split_off(header.len())
+    }
+
+    fn decode(rgbe: [u8; 4]) -> [f32; 3] {
+        let mut file = b"#?RADIANCE\n\n-Y 1 +X 1\n".to_vec();
+        file.extend_from_slice(&rgbe);
+        hdr::read_hdr(&file[..]).unwrap()[0][0].0
+    }
+
+    #[test]
+    fn rgbe_of_zero_and_invalid_values() {
+        assert_eq!(to_rgbe(Vec3f::default()), [0; 4]);
+        assert_eq!(to_rgbe(Vec3f::new(-1.0, -2.0, -3.0)), [0; 4]);
+        assert_eq!(to_rgbe(Vec3f::new(f32::NAN, 0.0, 0.0)), [0; 4]);
+    }
+
+    #[test]
+    fn rgbe_of_tiny_values() {
+        // Below the smallest exponent worth storing, values are black.
+        assert_eq!(to_rgbe(Vec3f::new(1e-33, 0.0, 0.0)), [0; 4]);
+        let [r, g, b] = decode(to_rgbe(Vec3f::new(1e-30, 0.5e-30, 0.0)));
+        assert!((r / 1e-30 - 1.0).abs() < 0.01);
+        assert!((g / 0.5e-30 - 1.0).abs() < 0.01);
+        assert!(b < 1e-32);
+    }
+
+    #[test]
+    fn rgbe_of_exact_powers_of_two() {
+        assert_eq!(to_rgbe(Vec3f::new(1.0, 0.5, 0.25)), [128, 64, 32, 129]);
+        assert_eq!(to_rgbe(Vec3f::new(0.0, 0.0, 1024.0)), [0, 0, 128, 139]);
+    }
+
+    #[test]
+    fn rgbe_of_huge_values() {
+        // The exponent saturates, as do the mantissas of values beyond it.
+        assert_eq!(to_rgbe(Vec3f::new(f32::MAX, 0.0, 0.0)), [255, 0, 0, 255]);
+        assert_eq!(
+            to_rgbe(Vec3f::new(f32::INFINITY, 1.0, 0.0)),
+            [255, 0, 0, 255]
+        );
+        let [r, _, _] = decode(to_rgbe(Vec3f::new(1e30, 0.0, 0.0)));
+        assert!((r / 1e30 - 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn runs_split_at_127_bytes() {
+        assert_eq!(runs(&[7; 127]), [128 + 127, 7]);
+        assert_eq!(runs(&[7; 128]), [128 + 127, 7, 1, 7]);
+        assert_eq!(runs(&[7; 131]), [128 + 127, 7, 128 + 4, 7]);
+        assert_eq!(runs(&[7; 254]), [128 + 127, 7, 128 + 127, 7]);
+    }
+
+    #[test]
+    fn literals_split_at_128_bytes() {
+        let bytes = (0..=255).collect::<Vec<u8>>();
+        let encoded = runs(&bytes[..128]);
+        assert_eq!(encoded[0], 128);
+        assert_eq!(&encoded[1..], &bytes[..128]);
+        let encoded = runs(&bytes[..129]);
+        assert_eq!(encoded[0], 128);
+        assert_eq!(&encoded[1..129], &bytes[..128]);
+        assert_eq!(&encoded[129..], &[1, 128]);
+    }
+
+    #[test]
+    fn short_repeats_stay_literal() {
+        assert_eq!(runs(&[1, 2, 2, 2, 3]), [5, 1, 2, 2, 2, 3]);
+        assert_eq!(runs(&[1, 2, 2, 2, 2]), [1, 1, 128 + 4, 2]);
+    }
+
+    #[test]
+    fn hdr_round_trip() {
+        let (width, height) = (300, 3);
+        let mut framebuffer = Framebuffer::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                // Long runs of equal pixels, then varying ones.
+                let v = if x < 140 {
+                    0.5
+                } else {
+                    (x * (y + 1)) as f32 * 0.37
+                };
+                framebuffer[y][x] = Vec3f::new(v, v * 0.25, 1.0 / (v + 1.0));
+            }
+        }
+        let mut file = Vec::new();
+        write_hdr(&framebuffer, &mut file).unwrap();
+        let decoded = hdr::read_hdr(&file[..]).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (width, height));
+        for (a, b) in framebuffer.pixels().iter().zip(decoded.pixels()) {
+            // Each channel is accurate to one step of the brightest channel's mantissa.
+            let step = a.max_component() / 128.0;
+            for c in 0..3 {
+                assert!((a.0[c] - b.0[c]).abs() <= step, "{:?} != {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn narrow_and_wide_scanlines_are_flat() {
+        for width in [1, 7, 0x8000, 0x8001] {
+            let mut framebuffer = Framebuffer::new(width, 2);
+            framebuffer[0][0] = Vec3f::new(1.0, 0.5, 0.25);
+            let body = hdr_body(&framebuffer);
+            assert_eq!(body.len(), 4 * width * 2);
+            assert_eq!(&body[..4], &[128, 64, 32, 129]);
+            let mut file = Vec::new();
+            write_hdr(&framebuffer, &mut file).unwrap();
+            let decoded = hdr::read_hdr(&file[..]).unwrap();
+            assert_eq!(decoded[0][0].0, decode([128, 64, 32, 129]));
+        }
+    }
+
+    #[test]
+    fn scanlines_from_8_to_0x7fff_are_run_length_encoded() {
+        for width in [8, 0x7fff] {
+            let body = hdr_body(&Framebuffer::new(width, 1));
+            assert_eq!(&body[..4], &[2, 2, (width >> 8) as u8, width as u8]);
+            assert!(body.len() < 4 * width);
+        }
+    }
+}
@@ -0,0 +1,1080 @@
+//! Analytic primitives beyond spheres and planes: boxes, cylinders, cones, disks and tori.
+//!
+//! Each is intersected in a frame of its own, with the primitive's axis along local z. All but
+//! the disk enclose a volume and can be combined by [`csg`](crate::csg).
+
+use crate::geometry::{Aabb, Vec3f};
+use crate::scene::Material;
+use crate::shape::{self, Hit, Shape, Span};
+use std::f32::consts::PI;
+
+/// An orthonormal frame placing a primitive in the scene.
+#[derive(Debug, Clone, Copy)]
+struct Frame {
+    origin: Vec3f,
+    axes: [Vec3f; 3],
+}
+
+impl Frame {
+    /// A frame at `origin` whose z axis points along `axis`.
+    fn around(origin: Vec3f, axis: Vec3f) -> Self {
+        let w = axis.normalize();
+        let (u, v) = w.basis();
+        Self {
+            origin,
+            axes: [u, v, w],
+        }
+    }
+
+    fn local_point(&self, p: Vec3f) -> Vec3f {
+        self.local_vector(p - self.origin)
+    }
+
+    fn local_vector(&self, v: Vec3f) -> Vec3f {
+        Vec3f::new(v * self.axes[0], v * self.axes[1], v * self.axes[2])
+    }
+
+    fn world_vector(&self, v: Vec3f) -> Vec3f {
+        self.axes[0] * v.0[0] + self.axes[1] * v.0[1] + self.axes[2] * v.0[2]
+    }
+
+    /// Bounds in the scene of the local box from `min` to `max`.
+    fn bounds(&self, min: Vec3f, max: Vec3f) -> Aabb {
+        (0..8).fold(Aabb::empty(), |bounds, i| {
+            let pick = |axis: usize| {
+                if i & (1 << axis) == 0 {
+                    min.0[axis]
+                } else {
+                    max.0[axis]
+                }
+            };
+            let corner = Vec3f::new(pick(0), pick(1), pick(2));
+            bounds.grow(self.origin + self.world_vector(corner))
+        })
+    }
+
+    /// A hit given in local coordinates, moved into the scene.
+    fn hit<'a>(&self, orig: Vec3f, dir: Vec3f, local: LocalHit, material: &'a Material) -> Hit<'a> {
+        let normal = self.world_vector(local.normal).normalize();
+        Hit {
+            t: local.t,
+            point: orig + dir * local.t,
+            normal,
+            geometric_normal: normal,
+            uv: local.uv,
+            dpdu: self.world_vector(local.dpdu),
+            dpdv: self.world_vector(local.dpdv),
+            material,
+        }
+    }
+}
+
+/// The parts of a [`Hit`] that a primitive computes in its own frame.
+#[derive(Debug, Clone, Copy)]
+struct LocalHit {
+    t: f32,
+    normal: Vec3f,
+    uv: [f32; 2],
+    dpdu: Vec3f,
+    dpdv: Vec3f,
+}
+
+/// The fraction of a turn by which `(x, y)` is rotated from the negative x axis, from 0 to 1.
+fn turn(x: f32, y: f32) -> f32 {
+    0.5 + y.atan2(x) / (2f32 * PI)
+}
+
+/// A box, which may be rotated.
+///
+/// Each face is textured from 0 to 1 along its edges, seen from outside.
+///
+/// ```
+/// use tinyraytracer::{Cuboid, Material, Shape, Vec3f};
+///
+/// let cuboid = Cuboid::new(
+///     Vec3f::new(-1.0, -1.0, -6.0),
+///     Vec3f::new(1.0, 1.0, -4.0),
+///     Material::default(),
+/// );
+/// let hit = cuboid
+///     .intersect(Vec3f::default(), Vec3f::new(0.0, 0.0, -1.0), f32::MAX)
+///     .unwrap();
+/// assert_eq!(hit.t, 4.0);
+/// assert_eq!(hit.normal, Vec3f::new(0.0, 0.0, 1.0));
+/// assert_eq!(hit.uv, [0.5, 0.5]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Cuboid {
+    frame: Frame,
+    half_size: Vec3f,
+    pub material: Material,
+}
+
+impl Cuboid {
+    /// An axis-aligned box between the corners `min` and `max`.
+    pub fn new(min: Vec3f, max: Vec3f, material: Material) -> Self {
+        Self {
+            frame: Frame {
+                origin: (min + max) * 0.5,
+                axes: [
+                    Vec3f::new(1f32, 0f32, 0f32),
+                    Vec3f::new(0f32, 1f32, 0f32),
+                    Vec3f::new(0f32, 0f32, 1f32),
+                ],
+            },
+            half_size: (max - min) * 0.5,
+            material,
+        }
+    }
+
+    /// A box centered on `center` with edges of length `size` along `x`, `y` and `x × y`. `y` is
+    /// made perpendicular to `x` if it is not.
+    pub fn oriented(center: Vec3f, size: Vec3f, x: Vec3f, y: Vec3f, material: Material) -> Self {
+        let x = x.normalize();
+        let y = (y - x * (x * y)).normalize();
+        Self {
+            frame: Frame {
+                origin: center,
+                axes: [x, y, x.cross(y)],
+            },
+            half_size: size * 0.5,
+            material,
+        }
+    }
+
+    /// The hit on the face perpendicular to local `axis` at distance `t`.
+    fn face(&self, o: Vec3f, d: Vec3f, t: f32, axis: usize) -> LocalHit {
+        let p = o + d * t;
+        let h = self.half_size.0;
+        let sign = if p.0[axis] < 0f32 { -1f32 } else { 1f32 };
+        let (a, b) = ((axis + 1) % 3, (axis + 2) % 3);
+        let unit = |i: usize, length: f32| {
+            let mut v = Vec3f::default();
+            v.0[i] = length;
+            v
+        };
+        // Mirrored on the negative faces, so that textures read the same way from outside.
+        let u = 0.5 + sign * p.0[a] / (2f32 * h[a]);
+        LocalHit {
+            t,
+            normal: unit(axis, sign),
+            uv: [u, 0.5 + p.0[b] / (2f32 * h[b])],
+            dpdu: unit(a, sign * 2f32 * h[a]),
+            dpdv: unit(b, 2f32 * h[b]),
+        }
+    }
+
+    fn span(&self, orig: Vec3f, dir: Vec3f) -> Option<Span<'_>> {
+        let (o, d) = (self.frame.local_point(orig), self.frame.local_vector(dir));
+        let (mut near, mut far) = ((f32::NEG_INFINITY, 0), (f32::INFINITY, 0));
+        for axis in 0..3 {
+            let h = self.half_size.0[axis];
+            if d.0[axis] == 0f32 {
+                if o.0[axis].abs() > h {
+                    return None;
+                }
+                continue;
+            }
+            let t0 = (-h - o.0[axis]) / d.0[axis];
+            let t1 = (h - o.0[axis]) / d.0[axis];
+            let (t0, t1) = if t0 < t1 { (t0, t1) } else { (t1, t0) };
+            if t0 > near.0 {
+                near = (t0, axis);
+            }
+            if t1 < far.0 {
+                far = (t1, axis);
+            }
+        }
+        if near.0 > far.0 {
+            return None;
+        }
+        let hit = |(t, axis)| {
+            self.frame
+                .hit(orig, dir, self.face(o, d, t, axis), &self.material)
+        };
+        Some(Span {
+            enter: hit(near),
+            exit: hit(far),
+        })
+    }
+}
+
+impl Shape for Cuboid {
+    fn bounds(&self) -> Option<Aabb> {
+        Some(self.frame.bounds(-self.half_size, self.half_size))
+    }
+
+    fn intersect(&self, orig: Vec3f, dir: Vec3f, t_max: f32) -> Option<Hit<'_>> {
+        shape::nearest_boundary(self.span(orig, dir), t_max)
+    }
+
+    fn spans(&self, orig: Vec3f, dir: Vec3f) -> Option<Vec<Span<'_>>> {
+        Some(self.span(orig, dir).into_iter().collect())
+    }
+}
+
+/// Where a ray crosses the surface of a [`Frustum`].
+#[derive(Debug, Clone, Copy)]
+enum Part {
+    Side,
+    Base,
+    Top,
+}
+
+/// A solid of revolution whose radius changes linearly from the base at local z = 0 to the top
+/// at z = `height`, shared by cylinders and cones.
+#[derive(Debug, Clone)]
+struct Frustum {
+    frame: Frame,
+    height: f32,
+    radii: [f32; 2],
+}
+
+impl Frustum {
+    fn new(base: Vec3f, top: Vec3f, radii: [f32; 2]) -> Self {
+        Self {
+            frame: Frame::around(base, top - base),
+            height: (top - base).norm(),
+            radii,
+        }
+    }
+
+    fn bounds(&self) -> Aabb {
+        let r = self.radii[0].max(self.radii[1]);
+        self.frame
+            .bounds(Vec3f::new(-r, -r, 0f32), Vec3f::new(r, r, self.height))
+    }
+
+    /// The distances to where the line enters and leaves the solid, and the parts crossed there.
+    fn range(&self, o: Vec3f, d: Vec3f) -> Option<[(f32, Part); 2]> {
+        let h = self.height;
+        // Between the planes of the caps.
+        let (mut near, mut far) = if d.0[2] != 0f32 {
+            let base = (-o.0[2] / d.0[2], Part::Base);
+            let top = ((h - o.0[2]) / d.0[2], Part::Top);
+            if base.0 < top.0 {
+                (base, top)
+            } else {
+                (top, base)
+            }
+        } else if (0f32..=h).contains(&o.0[2]) {
+            ((f32::NEG_INFINITY, Part::Side), (f32::INFINITY, Part::Side))
+        } else {
+            return None;
+        };
+
+        // Inside the side where x² + y² - r(z)² is negative, r growing by `k` per unit of z.
+        let [r0, r1] = self.radii;
+        let k = (r1 - r0) / h;
+        let r = r0 + k * o.0[2];
+        let a = d.0[0] * d.0[0] + d.0[1] * d.0[1] - k * k * d.0[2] * d.0[2];
+        let b = 2f32 * (o.0[0] * d.0[0] + o.0[1] * d.0[1] - k * r * d.0[2]);
+        let c = o.0[0] * o.0[0] + o.0[1] * o.0[1] - r * r;
+        if a.abs() < 1e-9 {
+            // Parallel to the side, which is crossed once at most.
+            if b.abs() < 1e-9 {
+                if c > 0f32 {
+                    return None;
+                }
+            } else if b > 0f32 {
+                far = nearer(far, (-c / b, Part::Side));
+            } else {
+                near = farther(near, (-c / b, Part::Side));
+            }
+        } else {
+            let discriminant = b * b - 4f32 * a * c;
+            if discriminant < 0f32 {
+                // Missing the side entirely: outside for a cylinder, inside a double cone.
+                if a > 0f32 {
+                    return None;
+                }
+            } else {
+                let q = -0.5 * (b + discriminant.sqrt().copysign(b));
+                let (t0, t1) = (q / a, c / q);
+                let (t0, t1) = if t0 < t1 { (t0, t1) } else { (t1, t0) };
+                if a > 0f32 {
+                    near = farther(near, (t0, Part::Side));
+                    far = nearer(far, (t1, Part::Side));
+                } else if near.0 <= t0 {
+                    // Between the nappes of a double cone, only one of which lies between the
+                    // caps.
+                    far = nearer(far, (t0, Part::Side));
+                } else {
+                    near = farther(near, (t1, Part::Side));
+                }
+            }
+        }
+        if near.0 > far.0 {
+            None
+        } else {
+            Some([near, far])
+        }
+    }
+
+    fn local_hit(&self, o: Vec3f, d: Vec3f, (t, part): (f32, Part)) -> LocalHit {
+        let p = o + d * t;
+        let [x, y, z] = p.0;
+        match part {
+            Part::Side => {
+                let [r0, r1] = self.radii;
+                let k = (r1 - r0) / self.height;
+                let normal = Vec3f::new(x, y, -k * (r0 + k * z));
+                let normal = if normal * normal > 0f32 {
+                    normal
+                } else {
+                    // The apex of a cone.
+                    Vec3f::new(0f32, 0f32, 1f32)
+                };
+                let radius = (x * x + y * y).sqrt();
+                let (cos, sin) = if radius > 0f32 {
+                    (x / radius, y / radius)
+                } else {
+                    (0f32, 0f32)
+                };
+                LocalHit {
+                    t,
+                    normal,
+                    uv: [turn(x, y), z / self.height],
+                    dpdu: Vec3f::new(-y, x, 0f32) * (2f32 * PI),
+                    dpdv: Vec3f::new(cos * (r1 - r0), sin * (r1 - r0), self.height),
+                }
+            }
+            Part::Base | Part::Top => {
+                let (radius, sign) = match part {
+                    Part::Base => (self.radii[0], -1f32),
+                    _ => (self.radii[1], 1f32),
+                };
+                // Mapped across the diameter, mirrored on the base to read the same from outside.
+                let scale = 2f32 * radius.max(f32::MIN_POSITIVE);
+                LocalHit {
+                    t,
+                    normal: Vec3f::new(0f32, 0f32, sign),
+                    uv: [0.5 + sign * x / scale, 0.5 + y / scale],
+                    dpdu: Vec3f::new(sign * scale, 0f32, 0f32),
+                    dpdv: Vec3f::new(0f32, scale, 0f32),
+                }
+            }
+        }
+    }
+
+    fn span<'a>(&self, orig: Vec3f, dir: Vec3f, material: &'a Material) -> Option<Span<'a>> {
+        let (o, d) = (self.frame.local_point(orig), self.frame.local_vector(dir));
+        let [near, far] = self.range(o, d)?;
+        let hit = |boundary| {
+            self.frame
+                .hit(orig, dir, self.local_hit(o, d, boundary), material)
+        };
+        Some(Span {
+            enter: hit(near),
+            exit: hit(far),
+        })
+    }
+}
+
+fn nearer(a: (f32, Part), b: (f32, Part)) -> (f32, Part) {
+    if b.0 < a.0 {
+        b
+    } else {
+        a
+    }
+}
+
+fn farther(a: (f32, Part), b: (f32, Part)) -> (f32, Part) {
+    if b.0 > a.0 {
+        b
+    } else {
+        a
+    }
+}
+
+/// A cylinder closed by flat caps.
+///
+/// The side is textured by the angle around the axis and the height from 0 at the base to 1 at the
+/// top, the caps across their diameter.
+///
+/// ```
+/// use tinyraytracer::{Cylinder, Material, Shape, Vec3f};
+///
+/// let cylinder = Cylinder::new(
+///     Vec3f::new(0.0, -1.0, -5.0),
+///     Vec3f::new(0.0, 1.0, -5.0),
+///     1.0,
+///     Material::default(),
+/// );
+/// let hit = cylinder
+///     .intersect(Vec3f::default(), Vec3f::new(0.0, 0.0, -1.0), f32::MAX)
+///     .unwrap();
+/// assert!((hit.t - 4.0).abs() < 1e-5);
+/// assert!((hit.normal - Vec3f::new(0.0, 0.0, 1.0)).norm() < 1e-5);
+///
+/// // Looking down the axis hits the top cap.
+/// let hit = cylinder
+///     .intersect(Vec3f::new(0.0, 5.0, -5.0), Vec3f::new(0.0, -1.0, 0.0), f32::MAX)
+///     .unwrap();
+/// assert_eq!(hit.t, 4.0);
+/// assert_eq!(hit.normal, Vec3f::new(0.0, 1.0, 0.0));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Cylinder {
+    frustum: Frustum,
+    pub material: Material,
+}
+
+impl Cylinder {
+    /// The cylinder of `radius` around the axis from the center of the `base` cap to the center of
+    /// the `top` one.
+    pub fn new(base: Vec3f, top: Vec3f, radius: f32, material: Material) -> Self {
+        Self {
+            frustum: Frustum::new(base, top, [radius, radius]),
+            material,
+        }
+    }
+}
+
+impl Shape for Cylinder {
+    fn bounds(&self) -> Option<Aabb> {
+        Some(self.frustum.bounds())
+    }
+
+    fn intersect(&self, orig: Vec3f, dir: Vec3f, t_max: f32) -> Option<Hit<'_>> {
+        shape::nearest_boundary(self.frustum.span(orig, dir, &self.material), t_max)
+    }
+
+    fn spans(&self, orig: Vec3f, dir: Vec3f) -> Option<Vec<Span<'_>>> {
+        Some(
+            self.frustum
+                .span(orig, dir, &self.material)
+                .into_iter()
+                .collect(),
+        )
+    }
+}
+
+/// A cone closed by a flat base, or a frustum also closed at the top.
+///
+/// It is textured like a [`Cylinder`].
+///
+/// ```
+/// use tinyraytracer::{Cone, Material, Shape, Vec3f};
+///
+/// let cone = Cone::new(
+///     Vec3f::new(0.0, -1.0, -5.0),
+///     Vec3f::new(0.0, 1.0, -5.0),
+///     1.0,
+///     Material::default(),
+/// );
+/// // Halfway up, the cone is half as wide.
+/// let hit = cone
+///     .intersect(Vec3f::default(), Vec3f::new(0.0, 0.0, -1.0), f32::MAX)
+///     .unwrap();
+/// assert!((hit.t - 4.5).abs() < 1e-5);
+/// let slope = Vec3f::new(0.0, 1.0, 2.0).normalize();
+/// assert!((hit.normal - slope).norm() < 1e-5);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Cone {
+    frustum: Frustum,
+    pub material: Material,
+}
+
+impl Cone {
+    /// The cone with a base of `radius` centered on `base`, narrowing to a point at `apex`.
+    pub fn new(base: Vec3f, apex: Vec3f, radius: f32, material: Material) -> Self {
+        Self {
+            frustum: Frustum::new(base, apex, [radius, 0f32]),
+            material,
+        }
+    }
+
+    /// Cuts the cone off at the apex end where it is `radius` wide, closing it with a cap.
+    ///
+    /// `radius` must be narrower than the base for the cut to fall short of the apex.
+    pub fn with_top_radius(mut self, radius: f32) -> Self {
+        let [base, top] = &mut self.frustum.radii;
+        self.frustum.height *= 1f32 - radius / *base;
+        *top = radius;
+        self
+    }
+}
+
+impl Shape for Cone {
+    fn bounds(&self) -> Option<Aabb> {
+        Some(self.frustum.bounds())
+    }
+
+    fn intersect(&self, orig: Vec3f, dir: Vec3f, t_max: f32) -> Option<Hit<'_>> {
+        shape::nearest_boundary(self.frustum.span(orig, dir, &self.material), t_max)
+    }
+
+    fn spans(&self, orig: Vec3f, dir: Vec3f) -> Option<Vec<Span<'_>>> {
+        Some(
+            self.frustum
+                .span(orig, dir, &self.material)
+                .into_iter()
+                .collect(),
+        )
+    }
+}
+
+/// A flat disk facing along `normal`, textured across its diameter, with an optional hole in the
+/// middle.
+#[derive(Debug, Clone)]
+pub struct Disk {
+    frame: Frame,
+    radius: f32,
+    inner_radius: f32,
+    pub material: Material,
+}
+
+impl Disk {
+    pub fn new(center: Vec3f, normal: Vec3f, radius: f32, material: Material) -> Self {
+        Self {
+            frame: Frame::around(center, normal),
+            radius,
+            inner_radius: 0f32,
+            material,
+        }
+    }
+
+    /// Cuts a hole of `radius` out of the center, leaving a ring.
+    ///
+    /// `radius` must be narrower than the disk for anything to be left.
+    pub fn with_inner_radius(mut self, radius: f32) -> Self {
+        self.inner_radius = radius;
+        self
+    }
+}
+
+impl Shape for Disk {
+    fn bounds(&self) -> Option<Aabb> {
+        let r = self.radius;
+        Some(
+            self.frame
+                .bounds(Vec3f::new(-r, -r, 0f32), Vec3f::new(r, r, 0f32)),
+        )
+    }
+
+    fn intersect(&self, orig: Vec3f, dir: Vec3f, t_max: f32) -> Option<Hit<'_>> {
+        let (o, d) = (self.frame.local_point(orig), self.frame.local_vector(dir));
+        if d.0[2] == 0f32 {
+            return None;
+        }
+        let t = -o.0[2] / d.0[2];
+        if t <= 0f32 || t >= t_max {
+            return None;
+        }
+        let [x, y, _] = (o + d * t).0;
+        let r2 = x * x + y * y;
+        if r2 > self.radius * self.radius || r2 < self.inner_radius * self.inner_radius {
+            return None;
+        }
+        let scale = 2f32 * self.radius;
+        let local = LocalHit {
+            t,
+            normal: Vec3f::new(0f32, 0f32, 1f32),
+            uv: [0.5 + x / scale, 0.5 + y / scale],
+            dpdu: Vec3f::new(scale, 0f32, 0f32),
+            dpdv: Vec3f::new(0f32, scale, 0f32),
+        };
+        Some(self.frame.hit(orig, dir, local, &self.material))
+    }
+}
+
+/// A ring torus: a tube of `minor_radius` around a circle of `major_radius`, which should be the
+/// larger, about `axis`.
+///
+/// It is textured by the angle around the axis and the angle around the tube.
+///
+/// ```
+/// use tinyraytracer::{Material, Shape, Torus, Vec3f};
+///
+/// let torus = Torus::new(
+///     Vec3f::new(0.0, 0.0, -10.0),
+///     Vec3f::new(0.0, 1.0, 0.0),
+///     2.0,
+///     0.5,
+///     Material::default(),
+/// );
+/// // Through the hole, then through the tube on both sides.
+/// let down = Vec3f::new(0.0, -1.0, 0.0);
+/// assert!(torus.intersect(Vec3f::new(0.0, 5.0, -10.0), down, f32::MAX).is_none());
+/// let spans = torus.spans(Vec3f::new(-5.0, 0.0, -10.0), Vec3f::new(1.0, 0.0, 0.0)).unwrap();
+/// let ts: Vec<_> = spans.iter().flat_map(|s| [s.enter.t, s.exit.t]).collect();
+/// for (t, expected) in ts.iter().zip([2.5, 3.5, 6.5, 7.5]) {
+///     assert!((t - expected).abs() < 1e-4);
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Torus {
+    frame: Frame,
+    major_radius: f32,
+    minor_radius: f32,
+    pub material: Material,
+}
+
+impl Torus {
+    pub fn new(
+        center: Vec3f,
+        axis: Vec3f,
+        major_radius: f32,
+        minor_radius: f32,
+        material: Material,
+    ) -> Self {
+        Self {
+            frame: Frame::around(center, axis),
+            major_radius,
+            minor_radius,
+            material,
+        }
+    }
+
+    fn local_hit(&self, o: Vec3f, d: Vec3f, t: f32) -> LocalHit {
+        let [x, y, z] = (o + d * t).0;
+        let (major, minor) = (self.major_radius, self.minor_radius);
+        let radius = (x * x + y * y).sqrt();
+        // Towards the point from the nearest point on the center circle of the tube.
+        let normal = if radius > 0f32 {
+            Vec3f::new(x - major * x / radius, y - major * y / radius, z)
+        } else {
+            Vec3f::new(0f32, 0f32, z)
+        };
+        let tube = z.atan2(radius - major);
+        let (cos, sin) = if radius > 0f32 {
+            (x / radius, y / radius)
+        } else {
+            (1f32, 0f32)
+        };
+        LocalHit {
+            t,
+            normal,
+            uv: [turn(x, y), 0.5 + tube / (2f32 * PI)],
+            dpdu: Vec3f::new(-y, x, 0f32) * (2f32 * PI),
+            dpdv: Vec3f::new(-tube.sin() * cos, -tube.sin() * sin, tube.cos())
+                * (2f32 * PI * minor),
+        }
+    }
+}
+
+impl Shape for Torus {
+    fn bounds(&self) -> Option<Aabb> {
+        let (r, h) = (self.major_radius + self.minor_radius, self.minor_radius);
+        Some(
+            self.frame
+                .bounds(Vec3f::new(-r, -r, -h), Vec3f::new(r, r, h)),
+        )
+    }
+
+    fn intersect(&self, orig: Vec3f, dir: Vec3f, t_max: f32) -> Option<Hit<'_>> {
+        shape::nearest_boundary(self.spans(orig, dir)?, t_max)
+    }
+
+    fn spans(&self, orig: Vec3f, dir: Vec3f) -> Option<Vec<Span<'_>>> {
+        let (o, d) = (self.frame.local_point(orig), self.frame.local_vector(dir));
+        // Solve from the point of the line nearest to the center, where the coefficients of the
+        // quartic lose the least precision.
+        let shift = -f64::from(o * d);
+        let [ox, oy, oz] = o.0.map(f64::from);
+        let [dx, dy, dz] = d.0.map(f64::from);
+        let (ox, oy, oz) = (ox + dx * shift, oy + dy * shift, oz + dz * shift);
+        let major2 = f64::from(self.major_radius).powi(2);
+        let minor2 = f64::from(self.minor_radius).powi(2);
+
+        // (|p|² + R² - r²)² - 4R²(x² + y²), negative inside the tube, along p = o + t d.
+        let dd = dx * dx + dy * dy + dz * dz;
+        let od = ox * dx + oy * dy + oz * dz;
+        let g = ox * ox + oy * oy + oz * oz + major2 - minor2;
+        let coefficients = [
+            dd * dd,
+            4f64 * dd * od,
+            4f64 * od * od + 2f64 * dd * g - 4f64 * major2 * (dx * dx + dy * dy),
+            4f64 * od * g - 8f64 * major2 * (ox * dx + oy * dy),
+            g * g - 4f64 * major2 * (ox * ox + oy * oy),
+        ];
+        let mut roots = solve_quartic(coefficients);
+        roots.sort_by(f64::total_cmp);
+
+        let hit = |t: f64| {
+            let t = (t + shift) as f32;
+            self.frame
+                .hit(orig, dir, self.local_hit(o, d, t), &self.material)
+        };
+        Some(
+            roots
+                .windows(2)
+                .filter(|pair| evaluate(coefficients, 0.5 * (pair[0] + pair[1])) < 0f64)
+                .map(|pair| Span {
+                    enter: hit(pair[0]),
+                    exit: hit(pair[1]),
+                })
+                .collect(),
+        )
+    }
+}
+
+/// Evaluates the polynomial with `coefficients` from the highest power down at `x`.
+fn evaluate(coefficients: [f64; 5], x: f64) -> f64 {
+    coefficients.iter().fold(0f64, |sum, &c| sum * x + c)
+}
+
+/// The real roots of the quartic polynomial with `coefficients` from the highest power down.
+///
+/// Ferrari's method reduces the quartic to a cubic and two quadratics; the roots are then refined
+/// by Newton's method on the quartic itself.
+fn solve_quartic(coefficients: [f64; 5]) -> Vec<f64> {
+    let [c4, c3, c2, c1, c0] = coefficients;
+    if c4 == 0f64 {
+        return Vec::new();
+    }
+    let (a, b, c, d) = (c3 / c4, c2 / c4, c1 / c4, c0 / c4);
+
+    // Substituting x = y - a/4 leaves y⁴ + py² + qy + r.
+    let a2 = a * a;
+    let p = -3f64 / 8f64 * a2 + b;
+    let q = a2 * a / 8f64 - a * b / 2f64 + c;
+    let r = -3f64 / 256f64 * a2 * a2 + a2 * b / 16f64 - a * c / 4f64 + d;
+
+    let mut roots = if r == 0f64 {
+        // y(y³ + py + q)
+        let mut roots = solve_cubic(0f64, p, q);
+        roots.push(0f64);
+        roots
+    } else {
+        // The largest root of the resolvent cubic is at least p/2, which is needed to split the
+        // quartic into two real quadratics.
+        let z = solve_cubic(-p / 2f64, -r, r * p / 2f64 - q * q / 8f64)
+            .into_iter()
+            .max_by(f64::total_cmp)
+            .unwrap();
+        let u = z * z - r;
+        let v = 2f64 * z - p;
+        // Rounding leaves either slightly negative when it should be zero.
+        if u < -1e-12 * (z * z).max(r.abs()) || v < -1e-12 * z.abs().max(p.abs()) {
+            return Vec::new();
+        }
+        let (u, v) = (u.max(0f64).sqrt(), v.max(0f64).sqrt());
+        let v = if q < 0f64 { -v } else { v };
+        let mut roots = solve_quadratic(v, z - u);
+        roots.extend(solve_quadratic(-v, z + u));
+        roots
+    };
+
+    for x in &mut roots {
+        *x -= a / 4f64;
+        for _ in 0..2 {
+            let f = evaluate(coefficients, *x);
+            let df = ((4f64 * c4 * *x + 3f64 * c3) * *x + 2f64 * c2) * *x + c1;
+            if df != 0f64 {
+                *x -= f / df;
+            }
+        }
+    }
+    roots
+}
+
+/// The real roots of x³ + ax² + bx + c, of which there is always at least one.
+fn solve_cubic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    // Substituting x = y - a/3 leaves y³ + 3py + 2q.
+    let p = (b - a * a / 3f64) / 3f64;
+    let q = (2f64 / 27f64 * a * a * a - a * b / 3f64 + c) / 2f64;
+    let discriminant = q * q + p * p * p;
+    // Both terms cancel for a repeated root, so only their size says how near zero is.
+    let roots = if discriminant.abs() <= 1e-12 * (q * q).max((p * p * p).abs()) {
+        if q == 0f64 {
+            vec![0f64]
+        } else {
+            let u = (-q).cbrt();
+            vec![2f64 * u, -u]
+        }
+    } else if discriminant < 0f64 {
+        let phi = (-q / (-p * p * p).sqrt()).clamp(-1f64, 1f64).acos() / 3f64;
+        let t = 2f64 * (-p).sqrt();
+        let third = std::f64::consts::PI / 3f64;
+        vec![
+            t * phi.cos(),
+            -t * (phi + third).cos(),
+            -t * (phi - third).cos(),
+        ]
+    } else {
+        let sqrt = discriminant.sqrt();
+        vec![(sqrt - q).cbrt() - (sqrt + q).cbrt()]
+    };
+    roots.into_iter().map(|y| y - a / 3f64).collect()
+}
+
+/// The real roots of x² + px + q.
+fn solve_quadratic(p: f64, q: f64) -> Vec<f64> {
+    let discriminant = p * p / 4f64 - q;
+    if discriminant < 0f64 {
+        Vec::new()
+    } else {
+        let sqrt = discriminant.sqrt();
+        vec![-p / 2f64 - sqrt, -p / 2f64 + sqrt]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Asserts that `roots` are `expected`, in any order, with repeated roots found at least once.
+    fn assert_roots(mut roots: Vec<f64>, expected: &[f64]) {
+        roots.sort_by(f64::total_cmp);
+        roots.dedup_by(|a, b| (*a - *b).abs() < 1e-6);
+        assert_eq!(roots.len(), expected.len(), "{:?} != {:?}", roots, expected);
+        for (root, expected) in roots.iter().zip(expected) {
+            assert!(
+                (root - expected).abs() < 1e-6,
+                "{:?} != {:?}",
+                roots,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn cubic_with_three_roots() {
+        // (x - 1)(x - 2)(x - 3)
+        assert_roots(solve_cubic(-6.0, 11.0, -6.0), &[1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn cubic_with_one_root() {
+        // (x - 2)(x² + 1)
+        assert_roots(solve_cubic(-2.0, 1.0, -2.0), &[2.0]);
+    }
+
+    #[test]
+    fn cubic_with_double_root() {
+        // (x - 1)²(x + 2)
+        assert_roots(solve_cubic(0.0, -3.0, 2.0), &[-2.0, 1.0]);
+    }
+
+    #[test]
+    fn cubic_with_small_roots() {
+        // (x - 0.001)(x - 0.002)(x - 0.003), whose discriminant is tiny without vanishing.
+        let roots = solve_cubic(-6e-3, 11e-6, -6e-9);
+        assert_roots(roots.iter().map(|x| x * 1e3).collect(), &[1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn cubic_with_triple_root() {
+        // (x - 2)³
+        assert_roots(solve_cubic(-6.0, 12.0, -8.0), &[2.0]);
+    }
+
+    #[test]
+    fn quartic_with_four_roots() {
+        // (x - 1)(x - 2)(x - 3)(x - 4)
+        assert_roots(
+            solve_quartic([1.0, -10.0, 35.0, -50.0, 24.0]),
+            &[1.0, 2.0, 3.0, 4.0],
+        );
+        // 2(x + 3)(x + 1)(x - 2)(x - 5)
+        assert_roots(
+            solve_quartic([2.0, -6.0, -30.0, 38.0, 60.0]),
+            &[-3.0, -1.0, 2.0, 5.0],
+        );
+    }
+
+    #[test]
+    fn quartic_with_double_roots() {
+        // (x - 1)²(x - 3)²
+        assert_roots(solve_quartic([1.0, -8.0, 22.0, -24.0, 9.0]), &[1.0, 3.0]);
+        // (x - 1)²(x² + 1)
+        assert_roots(solve_quartic([1.0, -2.0, 2.0, -2.0, 1.0]), &[1.0]);
+    }
+
+    #[test]
+    fn quartic_whose_resolvent_has_a_double_root() {
+        // (x + 1)²(x² - 2x + 3): the resolvent cubic (z + 1)²(z - 2) has a double root, and only
+        // its largest root splits the quartic into real quadratics.
+        assert_roots(solve_quartic([1.0, 0.0, 0.0, 4.0, 3.0]), &[-1.0]);
+    }
+
+    #[test]
+    fn quartic_without_roots() {
+        // (x² + 1)(x² + 4)
+        assert_roots(solve_quartic([1.0, 0.0, 5.0, 0.0, 4.0]), &[]);
+    }
+
+    #[test]
+    fn cut_cone_keeps_the_slope_of_the_side() {
+        let cone = Cone::new(
+            Vec3f::default(),
+            Vec3f::new(0.0, 2.0, 0.0),
+            1.0,
+            Material::default(),
+        )
+        .with_top_radius(0.25);
+        // The cut falls where the cone is a quarter as wide, three quarters of the way up.
+        let hit = cone
+            .intersect(
+                Vec3f::new(0.1, 5.0, 0.0),
+                Vec3f::new(0.0, -1.0, 0.0),
+                f32::MAX,
+            )
+            .unwrap();
+        assert!((hit.point.0[1] - 1.5).abs() < 1e-5);
+        assert_eq!(hit.normal, Vec3f::new(0.0, 1.0, 0.0));
+        // Just below the cap the silhouette is still that of the uncut cone.
+        let (orig, dir) = (Vec3f::new(-5.0, 1.4, 0.0), Vec3f::new(1.0, 0.0, 0.0));
+        let hit = cone.intersect(orig, dir, f32::MAX).unwrap();
+        assert!((hit.point.0[0] + 0.3).abs() < 1e-5);
+        // Above the cap nothing is left.
+        assert!(cone
+            .intersect(Vec3f::new(-5.0, 1.6, 0.0), dir, f32::MAX)
+            .is_none());
+    }
+
+    fn disk() -> Disk {
+        Disk::new(
+            Vec3f::default(),
+            Vec3f::new(0.0, 0.0, 1.0),
+            2.0,
+            Material::default(),
+        )
+    }
+
+    #[test]
+    fn ray_through_the_disk() {
+        let disk = disk();
+        let down = Vec3f::new(0.0, 0.0, -1.0);
+        let hit = disk
+            .intersect(Vec3f::new(1.0, 0.0, 5.0), down, f32::MAX)
+            .unwrap();
+        assert!((hit.t - 5.0).abs() < 1e-5);
+        assert!((hit.point - Vec3f::new(1.0, 0.0, 0.0)).norm() < 1e-5);
+        assert!((hit.uv[0] - 0.75).abs() < 1e-5 && (hit.uv[1] - 0.5).abs() < 1e-5);
+        // Beyond the rim, short of `t_max` and parallel to the disk, the ray misses.
+        assert!(disk
+            .intersect(Vec3f::new(2.1, 0.0, 5.0), down, f32::MAX)
+            .is_none());
+        assert!(disk
+            .intersect(Vec3f::new(1.0, 0.0, 5.0), down, 4.0)
+            .is_none());
+        assert!(disk
+            .intersect(
+                Vec3f::new(-5.0, 0.0, 0.0),
+                Vec3f::new(1.0, 0.0, 0.0),
+                f32::MAX
+            )
+            .is_none());
+        // Nor does it hit a disk behind its origin.
+        assert!(disk
+            .intersect(Vec3f::new(1.0, 0.0, -5.0), down, f32::MAX)
+            .is_none());
+    }
+
+    #[test]
+    fn ray_from_behind_the_disk() {
+        let disk = disk();
+        let hit = disk
+            .intersect(
+                Vec3f::new(0.0, 1.0, -5.0),
+                Vec3f::new(0.0, 0.0, 1.0),
+                f32::MAX,
+            )
+            .unwrap();
+        assert!((hit.t - 5.0).abs() < 1e-5);
+        // The normal keeps facing along the disk's normal, towards the other side.
+        assert!((hit.normal - Vec3f::new(0.0, 0.0, 1.0)).norm() < 1e-5);
+        assert_eq!(hit.normal, hit.geometric_normal);
+    }
+
+    #[test]
+    fn normal_follows_a_tilted_disk() {
+        let normal = Vec3f::new(0.0, 1.0, 1.0).normalize();
+        let disk = Disk::new(
+            Vec3f::new(0.0, 0.0, -10.0),
+            normal,
+            1.0,
+            Material::default(),
+        );
+        let hit = disk
+            .intersect(Vec3f::default(), Vec3f::new(0.0, 0.0, -1.0), f32::MAX)
+            .unwrap();
+        assert!((hit.t - 10.0).abs() < 1e-4);
+        assert!((hit.normal - normal).norm() < 1e-5);
+        assert!((hit.dpdu * normal).abs() < 1e-5 && (hit.dpdv * normal).abs() < 1e-5);
+    }
+
+    #[test]
+    fn ray_through_the_hole_of_a_ring() {
+        let ring = disk().with_inner_radius(1.0);
+        let down = Vec3f::new(0.0, 0.0, -1.0);
+        for x in [0.0, 0.5, 0.99] {
+            assert!(ring
+                .intersect(Vec3f::new(x, 0.0, 5.0), down, f32::MAX)
+                .is_none());
+        }
+        for x in [1.01, 1.5, 1.99] {
+            let hit = ring
+                .intersect(Vec3f::new(0.0, x, 5.0), down, f32::MAX)
+                .unwrap();
+            assert!((hit.point.0[1] - x).abs() < 1e-5);
+        }
+        assert!(ring
+            .intersect(Vec3f::new(0.0, 2.01, 5.0), down, f32::MAX)
+            .is_none());
+    }
+
+    fn torus() -> Torus {
+        Torus::new(
+            Vec3f::default(),
+            Vec3f::new(0.0, 0.0, 1.0),
+            2.0,
+            0.5,
+            Material::default(),
+        )
+    }
+
+    #[test]
+    fn ray_through_both_sides_of_the_tube() {
+        let torus = torus();
+        let spans = torus
+            .spans(Vec3f::new(-5.0, 0.0, 0.0), Vec3f::new(1.0, 0.0, 0.0))
+            .unwrap();
+        let ts = spans
+            .iter()
+            .flat_map(|span| [span.enter.t, span.exit.t])
+            .collect::<Vec<_>>();
+        assert_eq!(ts.len(), 4);
+        for (t, expected) in ts.iter().zip([2.5, 3.5, 6.5, 7.5]) {
+            assert!((t - expected).abs() < 1e-4, "{:?}", ts);
+        }
+    }
+
+    #[test]
+    fn ray_through_the_hole() {
+        let torus = torus();
+        let (orig, dir) = (Vec3f::new(0.3, 0.2, 5.0), Vec3f::new(0.0, 0.0, -1.0));
+        assert!(torus.spans(orig, dir).unwrap().is_empty());
+        assert!(torus.intersect(orig, dir, f32::MAX).is_none());
+    }
+
+    #[test]
+    fn ray_along_the_axis_of_the_tube() {
+        let torus = torus();
+        let hit = torus
+            .intersect(
+                Vec3f::new(2.0, 0.0, 5.0),
+                Vec3f::new(0.0, 0.0, -1.0),
+                f32::MAX,
+            )
+            .unwrap();
+        assert!((hit.t - 4.5).abs() < 1e-4);
+        assert!((hit.normal - Vec3f::new(0.0, 0.0, 1.0)).norm() < 1e-4);
+    }
+
+    #[test]
+    fn ray_grazing_the_top_of_the_tube() {
+        // Tangent to the top of the tube along its center circle's tangent line at (2, 0).
+        let torus = torus();
+        let spans = torus
+            .spans(Vec3f::new(2.0, -5.0, 0.5), Vec3f::new(0.0, 1.0, 0.0))
+            .unwrap();
+        for span in spans {
+            assert!((span.enter.point.0[2] - 0.5).abs() < 1e-2);
+        }
+    }
+}
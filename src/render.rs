@@ -0,0 +1,715 @@
+use crate::bsdf::{self, Microfacet};
+use crate::camera::Camera;
+use crate::environment::Environment;
+use crate::framebuffer::Framebuffer;
+use crate::geometry::Vec3f;
+use crate::sampling::{self, BoxFilter, Filter, Grid, Rng, Sampler};
+use crate::scene::{Light, Model, Scene};
+use crate::shape::Hit;
+use std::f32::consts::PI;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+/// A rectangular block of pixels rendered as one unit of work.
+#[derive(Debug, Clone, Copy)]
+struct Tile {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+}
+
+fn tiles(width: usize, height: usize, size: usize) -> Vec<Tile> {
+    let mut tiles = Vec::new();
+    for y in (0..height).step_by(size) {
+        for x in (0..width).step_by(size) {
+            tiles.push(Tile {
+                x,
+                y,
+                width: size.min(width - x),
+                height: size.min(height - y),
+            });
+        }
+    }
+    tiles
+}
+
+/// How the light arriving along a camera ray is computed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Integrator {
+    /// Whitted-style ray tracing: Phong shading from the lights, blended with exactly one
+    /// reflected and one refracted ray per hit.
+    #[default]
+    Whitted,
+    /// Unbiased Monte Carlo path tracing, which also picks up light bouncing between diffuse
+    /// surfaces and from the background. Needs many samples per pixel to converge.
+    Path,
+}
+
+/// Bounces after which paths are randomly terminated according to their throughput.
+const ROULETTE_DEPTH: usize = 3;
+
+/// Least share of the summed absolute filter weights that a pixel is normalized by.
+const MIN_FILTER_WEIGHT: f32 = 0.25;
+
+/// Ray tracer rendering scenes with one of the [`Integrator`]s.
+#[derive(Debug, Clone)]
+pub struct Renderer {
+    /// How the radiance of camera rays is computed.
+    pub integrator: Integrator,
+    /// Maximum number of reflection/refraction bounces, or path segments for path tracing.
+    pub max_depth: usize,
+    /// Number of worker threads, `0` to use all available cores.
+    pub threads: usize,
+    /// Edge length in pixels of the square tiles handed out to the workers.
+    pub tile_size: usize,
+    /// Number of camera rays traced per pixel.
+    pub samples: usize,
+    /// Places the samples of each pixel.
+    pub sampler: Arc<dyn Sampler>,
+    /// Combines the samples around each pixel into its color.
+    pub filter: Arc<dyn Filter>,
+    /// Number of shadow rays per area light and towards the environment map, `0` to use their
+    /// own `samples`.
+    pub shadow_samples: usize,
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+        Self {
+            integrator: Integrator::Whitted,
+            max_depth: 4,
+            threads: 0,
+            tile_size: 32,
+            samples: 1,
+            sampler: Arc::new(Grid),
+            filter: Arc::new(BoxFilter::default()),
+            shadow_samples: 0,
+        }
+    }
+}
+
+impl Renderer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn thread_count(&self) -> usize {
+        if self.threads > 0 {
+            self.threads
+        } else {
+            thread::available_parallelism().map_or(1, |n| n.get())
+        }
+    }
+
+    /// Renders `scene` as seen from `camera` into a new framebuffer.
+    ///
+    /// The image is split into tiles which are distributed over the worker threads. Every pixel
+    /// is traced independently, so the result does not depend on the number of threads.
+    ///
+    /// ```
+    /// use tinyraytracer::{Camera, Integrator, Light, Material, Renderer, Scene, Sphere, Vec3f};
+    ///
+    /// let mut scene = Scene::new();
+    /// scene.add(Sphere::new(Vec3f::new(0.0, 0.0, -8.0), 2.0, Material::default()));
+    /// scene.add_light(Light::new(Vec3f::new(10.0, 10.0, 10.0), 1.0));
+    /// let camera = Camera::new(40, 30);
+    ///
+    /// let mut renderer = Renderer::new();
+    /// renderer.tile_size = 16;
+    /// renderer.threads = 1;
+    /// let single = renderer.render(&scene, &camera);
+    /// renderer.threads = 3;
+    /// let multi = renderer.render(&scene, &camera);
+    ///
+    /// assert_eq!((single.width(), single.height()), (40, 30));
+    /// assert_eq!(single.pixels(), multi.pixels());
+    ///
+    /// // Random numbers are seeded per pixel, so the same holds for path tracing.
+    /// renderer.integrator = Integrator::Path;
+    /// renderer.samples = 4;
+    /// let multi = renderer.render(&scene, &camera);
+    /// renderer.threads = 1;
+    /// assert_eq!(renderer.render(&scene, &camera).pixels(), multi.pixels());
+    /// ```
+    pub fn render(&self, scene: &Scene, camera: &Camera) -> Framebuffer {
+        let mut framebuffer = Framebuffer::new(camera.width, camera.height);
+        let tiles = tiles(camera.width, camera.height, self.tile_size.max(1));
+        let next = AtomicUsize::new(0);
+        let workers = self.thread_count().min(tiles.len()).max(1);
+
+        let rendered = thread::scope(|s| {
+            let handles = (0..workers)
+                .map(|_| {
+                    s.spawn(|| {
+                        let mut rendered = Vec::new();
+                        loop {
+                            let index = next.fetch_add(1, Ordering::Relaxed);
+                            match tiles.get(index) {
+                                Some(tile) => {
+                                    rendered.push((*tile, self.render_tile(scene, camera, tile)))
+                                }
+                                None => return rendered,
+                            }
+                        }
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .flat_map(|h| h.join().expect("render worker panicked"))
+                .collect::<Vec<_>>()
+        });
+
+        for (tile, pixels) in rendered {
+            for (row, line) in pixels.chunks(tile.width).enumerate() {
+                framebuffer[tile.y + row][tile.x..tile.x + tile.width].copy_from_slice(line);
+            }
+        }
+        framebuffer
+    }
+
+    fn render_tile(&self, scene: &Scene, camera: &Camera, tile: &Tile) -> Vec<Vec3f> {
+        let mut pixels = Vec::with_capacity(tile.width * tile.height);
+        let mut samples = Vec::with_capacity(self.samples);
+        for i in tile.y..tile.y + tile.height {
+            for j in tile.x..tile.x + tile.width {
+                samples.clear();
+                let mut rng = Rng::for_pixel(j, i);
+                self.sampler
+                    .pixel_samples(self.samples.max(1), &mut rng, &mut samples);
+                pixels.push(self.render_pixel(scene, camera, j, i, &samples, &mut rng));
+            }
+        }
+        pixels
+    }
+
+    /// Filters the radiance of the samples spread over the filter's support around a pixel.
+    fn render_pixel(
+        &self,
+        scene: &Scene,
+        camera: &Camera,
+        x: usize,
+        y: usize,
+        samples: &[[f32; 2]],
+        rng: &mut Rng,
+    ) -> Vec3f {
+        let radius = self.filter.radius();
+        let mut color = Vec3f::default();
+        let mut weight = 0f32;
+        let mut total = 0f32;
+        for sample in samples {
+            let dx = (2f32 * sample[0] - 1f32) * radius;
+            let dy = (2f32 * sample[1] - 1f32) * radius;
+            let w = self.filter.evaluate(dx, dy);
+            if w == 0f32 {
+                continue;
+            }
+            let (orig, dir) = camera.ray(x as f32 + 0.5 + dx, y as f32 + 0.5 + dy);
+            let radiance = match self.integrator {
+                Integrator::Whitted => self.cast_ray(scene, orig, dir, Some(0), rng),
+                Integrator::Path => self.trace_path(scene, orig, dir, rng),
+            };
+            color = color + radiance * w;
+            weight += w;
+            total += w.abs();
+        }
+        if total == 0f32 {
+            return color;
+        }
+        // Filters with negative lobes, such as Mitchell-Netravali, may leave few samples summing
+        // to next to nothing or less, whose quotient would blow up or flip the sign of the pixel.
+        (color * (1f32 / weight.max(MIN_FILTER_WEIGHT * total))).max(Vec3f::default())
+    }
+
+    fn cast_ray(
+        &self,
+        scene: &Scene,
+        orig: Vec3f,
+        dir: Vec3f,
+        depth: Option<usize>,
+        rng: &mut Rng,
+    ) -> Vec3f {
+        if let Some(hit) = depth.and_then(|_| scene.intersect(orig, dir)) {
+            if let Some(bsdf) = hit.material.microfacet(hit.uv, hit.point) {
+                return self.shade_microfacet(scene, &hit, dir, depth, &bsdf, rng);
+            }
+            if let Model::Dielectric { absorption } = hit.material.model {
+                return self.shade_dielectric(scene, &hit, dir, depth, absorption, rng);
+            }
+            let (point, n, material) = (hit.point, hit.normal, hit.material);
+            let surface = material.phong_at(hit.uv, point);
+            let reflect_dir = dir.reflect(n).normalize();
+            let reflect_color = self.cast_ray(
+                scene,
+                offset(&hit, reflect_dir),
+                reflect_dir,
+                depth.map(|d| d + 1).filter(|d| *d <= self.max_depth),
+                rng,
+            );
+            let refract_color =
+                match dir.refract(refraction_normal(&hit, dir), material.refractive_index) {
+                    Some(refract_dir) => self.cast_ray(
+                        scene,
+                        offset(&hit, refract_dir),
+                        refract_dir,
+                        depth.map(|d| d + 1).filter(|d| *d <= self.max_depth),
+                        rng,
+                    ),
+                    // Total internal reflection sends the refracted share along the reflection.
+                    None => reflect_color,
+                };
+            // Lights and the environment both shade the side the ray arrives from.
+            let facing_n = facing(&hit, dir);
+            let mut diffuse_light_intensity = Vec3f::default();
+            let mut specular_light_intensity = Vec3f::default();
+            let mut add_light = |light_dir: Vec3f, intensity: Vec3f| {
+                diffuse_light_intensity =
+                    diffuse_light_intensity + intensity * 0f32.max(light_dir * facing_n);
+                specular_light_intensity = specular_light_intensity
+                    + intensity
+                        * 0f32
+                            .max(-(-light_dir).reflect(facing_n) * dir)
+                            .powf(surface.specular_exponent);
+            };
+            self.light_samples(scene, point, rng, |light_dir, light_distance, intensity| {
+                if !scene.occluded(offset(&hit, light_dir), light_dir, light_distance) {
+                    add_light(light_dir, intensity);
+                }
+            });
+            // A light of intensity I shines like a radiance of I/π arriving from all directions.
+            self.environment_samples(scene, &hit, facing_n, rng, |light_dir, _, radiance, pdf| {
+                add_light(light_dir, radiance * (1f32 / (PI * pdf)));
+            });
+            let [diffuse, specular, reflection, refraction] = surface.albedo;
+            surface
+                .diffuse_color
+                .component_mul(diffuse_light_intensity)
+                .component_mul(diffuse)
+                + specular_light_intensity.component_mul(specular)
+                + reflect_color.component_mul(reflection)
+                + refract_color.component_mul(refraction)
+        } else {
+            scene.background_radiance(dir)
+        }
+    }
+
+    /// Whitted shading of a metallic-roughness surface: the BSDF lit by the lights, plus a
+    /// single mirror reflection weighted by the Fresnel reflectance. As one ray cannot stand in
+    /// for a blurry reflection, the reflection fades out as the surface gets rougher.
+    fn shade_microfacet(
+        &self,
+        scene: &Scene,
+        hit: &Hit,
+        dir: Vec3f,
+        depth: Option<usize>,
+        bsdf: &Microfacet,
+        rng: &mut Rng,
+    ) -> Vec3f {
+        let n = facing(hit, dir);
+        let wo = -dir;
+        let mut color = Vec3f::default();
+        self.visible_light_samples(scene, hit, n, rng, |light_dir, cos, intensity| {
+            color = color + bsdf.evaluate(n, wo, light_dir).component_mul(intensity) * (PI * cos);
+        });
+        self.environment_samples(scene, hit, n, rng, |light_dir, cos, radiance, pdf| {
+            color = color + bsdf.evaluate(n, wo, light_dir).component_mul(radiance) * (cos / pdf);
+        });
+        let reflect_dir = dir.reflect(n).normalize();
+        let reflect_color = self.cast_ray(
+            scene,
+            offset(hit, reflect_dir),
+            reflect_dir,
+            depth.map(|d| d + 1).filter(|d| *d <= self.max_depth),
+            rng,
+        );
+        let smoothness = (1f32 - bsdf.alpha.sqrt()).powi(2);
+        color + reflect_color.component_mul(bsdf.fresnel(n * wo)) * smoothness
+    }
+
+    /// Whitted shading of a dielectric: the reflected and refracted rays weighted by the Fresnel
+    /// equations, attenuated by the medium if the ray has been travelling inside it.
+    fn shade_dielectric(
+        &self,
+        scene: &Scene,
+        hit: &Hit,
+        dir: Vec3f,
+        depth: Option<usize>,
+        absorption: Vec3f,
+        rng: &mut Rng,
+    ) -> Vec3f {
+        let n = facing(hit, dir);
+        let depth = depth.map(|d| d + 1).filter(|d| *d <= self.max_depth);
+        let reflect_dir = dir.reflect(n).normalize();
+        let reflect_color = self.cast_ray(scene, offset(hit, reflect_dir), reflect_dir, depth, rng);
+        let refraction = dir.refract(refraction_normal(hit, dir), hit.material.refractive_index);
+        let color = match refraction {
+            Some(refract_dir) => {
+                let reflectance = bsdf::fresnel_dielectric(-(dir * n), relative_eta(hit, dir));
+                let refract_dir = refract_dir.normalize();
+                let refract_color =
+                    self.cast_ray(scene, offset(hit, refract_dir), refract_dir, depth, rng);
+                reflect_color * reflectance + refract_color * (1f32 - reflectance)
+            }
+            None => reflect_color,
+        };
+        color.component_mul(medium_transmittance(hit, dir, absorption))
+    }
+
+    /// Calls `f` with the direction, distance and colored intensity of each shadow ray towards
+    /// the lights from `point`. Area lights are sampled with several rays which share their
+    /// intensity.
+    fn light_samples(
+        &self,
+        scene: &Scene,
+        point: Vec3f,
+        rng: &mut Rng,
+        mut f: impl FnMut(Vec3f, f32, Vec3f),
+    ) {
+        for light in scene.lights() {
+            let light = if self.shadow_samples > 0 {
+                Light {
+                    samples: self.shadow_samples,
+                    ..*light
+                }
+            } else {
+                *light
+            };
+            for index in 0..light.sample_count() {
+                let sample = light.sample(point, index, rng);
+                f(sample.direction, sample.distance, sample.intensity);
+            }
+        }
+    }
+
+    /// Like [`light_samples`](Self::light_samples), but only for the shadow rays which reach
+    /// their light from above the surface at `hit` with shading normal `n`, passing the cosine to
+    /// the normal instead of the distance.
+    fn visible_light_samples(
+        &self,
+        scene: &Scene,
+        hit: &Hit,
+        n: Vec3f,
+        rng: &mut Rng,
+        mut f: impl FnMut(Vec3f, f32, Vec3f),
+    ) {
+        self.light_samples(scene, hit.point, rng, |light_dir, distance, intensity| {
+            let cos = light_dir * n;
+            if cos > 0f32 && !scene.occluded(offset(hit, light_dir), light_dir, distance) {
+                f(light_dir, cos, intensity);
+            }
+        });
+    }
+
+    /// Calls `f` with the direction, cosine to the normal, radiance and density of each shadow
+    /// ray towards the environment map which leaves the surface at `hit` with shading normal `n`
+    /// unoccluded. The density is that of all rays together, so that each contributes its
+    /// radiance divided by it.
+    fn environment_samples(
+        &self,
+        scene: &Scene,
+        hit: &Hit,
+        n: Vec3f,
+        rng: &mut Rng,
+        mut f: impl FnMut(Vec3f, f32, Vec3f, f32),
+    ) {
+        let environment = match &scene.environment {
+            Some(environment) => environment,
+            None => return,
+        };
+        let count = self.environment_sample_count(environment);
+        for index in 0..count {
+            let u = sampling::stratum(index, count, rng);
+            if let Some((light_dir, radiance, pdf)) = environment.sample(u) {
+                let cos = light_dir * n;
+                if cos > 0f32 && !scene.occluded(offset(hit, light_dir), light_dir, f32::INFINITY) {
+                    f(light_dir, cos, radiance, pdf * count as f32);
+                }
+            }
+        }
+    }
+
+    fn environment_sample_count(&self, environment: &Environment) -> usize {
+        if self.shadow_samples > 0 {
+            self.shadow_samples
+        } else {
+            environment.samples.max(1)
+        }
+    }
+
+    /// Follows one random path from the camera, gathering light from the lights at every
+    /// vertex (next-event estimation) and from the background where the path escapes.
+    ///
+    /// The diffuse, reflected and refracted albedo weights are the probabilities of continuing
+    /// with a Lambertian, mirror or refracted bounce. Direct light is shaded as by the Whitted
+    /// integrator, including the Phong highlights, so both agree on directly lit surfaces.
+    ///
+    /// An environment map is reached both by shadow rays and by escaping paths, whose
+    /// contributions are combined by multiple importance sampling.
+    fn trace_path(&self, scene: &Scene, mut orig: Vec3f, mut dir: Vec3f, rng: &mut Rng) -> Vec3f {
+        let mut radiance = Vec3f::default();
+        let mut throughput = Vec3f::new(1.0, 1.0, 1.0);
+        // Density of the last bounce, `None` for camera rays and perfectly specular bounces.
+        let mut bounce_pdf = None;
+        for depth in 0..=self.max_depth {
+            let hit = match scene.intersect(orig, dir) {
+                Some(hit) => hit,
+                None => {
+                    let mut weight = 1f32;
+                    if let (Some(environment), Some(pdf)) = (&scene.environment, bounce_pdf) {
+                        let count = self.environment_sample_count(environment) as f32;
+                        weight = power_heuristic(pdf, count * environment.pdf(dir));
+                    }
+                    radiance = radiance
+                        + throughput.component_mul(scene.background_radiance(dir)) * weight;
+                    break;
+                }
+            };
+            let (point, material) = (hit.point, hit.material);
+            let n = facing(&hit, dir);
+
+            if let Some(bsdf) = material.microfacet(hit.uv, point) {
+                let wo = -dir;
+                self.visible_light_samples(scene, &hit, n, rng, |light_dir, cos, intensity| {
+                    radiance = radiance
+                        + throughput
+                            .component_mul(bsdf.evaluate(n, wo, light_dir))
+                            .component_mul(intensity)
+                            * (PI * cos);
+                });
+                self.environment_samples(scene, &hit, n, rng, |light_dir, cos, light, pdf| {
+                    let weight = power_heuristic(pdf, bsdf.pdf(n, wo, light_dir));
+                    radiance = radiance
+                        + throughput
+                            .component_mul(bsdf.evaluate(n, wo, light_dir))
+                            .component_mul(light)
+                            * (cos * weight / pdf);
+                });
+                let u = [rng.next_f32(), rng.next_f32()];
+                match bsdf.sample(n, wo, rng.next_f32(), u) {
+                    Some((wi, weight)) => {
+                        bounce_pdf = Some(bsdf.pdf(n, wo, wi));
+                        dir = wi;
+                        orig = offset(&hit, wi);
+                        throughput = throughput.component_mul(weight);
+                    }
+                    None => break,
+                }
+            } else if let Model::Dielectric { absorption } = material.model {
+                bounce_pdf = None;
+                throughput = throughput.component_mul(medium_transmittance(&hit, dir, absorption));
+                let refracted =
+                    dir.refract(refraction_normal(&hit, dir), material.refractive_index);
+                let reflectance = refracted.map_or(1f32, |_| {
+                    bsdf::fresnel_dielectric(-(dir * n), relative_eta(&hit, dir))
+                });
+                dir = match refracted {
+                    Some(refracted) if rng.next_f32() >= reflectance => refracted.normalize(),
+                    _ => dir.reflect(n).normalize(),
+                };
+                orig = offset(&hit, dir);
+            } else {
+                let surface = material.phong_at(hit.uv, point);
+                let albedo = surface.albedo.map(|a| a.max(Vec3f::default()));
+                // Bounces are picked by the largest channel of each weight and keep its tint.
+                let [diffuse, _, mirror, refraction] = albedo.map(Vec3f::max_component);
+                let total = diffuse + mirror + refraction;
+                let tint = |weight: Vec3f, probability: f32| weight * (1f32 / probability);
+                let diffuse_color = surface.diffuse_color;
+                let brdf = diffuse_color.component_mul(albedo[0]) * (1f32 / PI);
+                let specular = |light_dir: Vec3f| {
+                    albedo[1]
+                        * 0f32
+                            .max(-(-light_dir).reflect(n) * dir)
+                            .powf(surface.specular_exponent)
+                };
+                self.visible_light_samples(scene, &hit, n, rng, |light_dir, cos, intensity| {
+                    radiance = radiance
+                        + throughput.component_mul(brdf).component_mul(intensity) * (PI * cos)
+                        + throughput
+                            .component_mul(intensity)
+                            .component_mul(specular(light_dir));
+                });
+                self.environment_samples(scene, &hit, n, rng, |light_dir, cos, light, pdf| {
+                    let diffuse_pdf = if total > 0f32 {
+                        diffuse / total * cos / PI
+                    } else {
+                        0f32
+                    };
+                    let weight = power_heuristic(pdf, diffuse_pdf);
+                    radiance = radiance
+                        + throughput.component_mul(brdf).component_mul(light)
+                            * (cos * weight / pdf)
+                        + throughput
+                            .component_mul(light)
+                            .component_mul(specular(light_dir))
+                            * (1f32 / (PI * pdf));
+                });
+
+                if total <= 0f32 {
+                    break;
+                }
+                let pick = rng.next_f32() * total;
+                let u = [rng.next_f32(), rng.next_f32()];
+                if pick < diffuse {
+                    dir = sampling::cosine_hemisphere(n, u);
+                    orig = offset(&hit, dir);
+                    throughput = throughput
+                        .component_mul(diffuse_color)
+                        .component_mul(tint(albedo[0], diffuse))
+                        * total;
+                    bounce_pdf = Some(diffuse / total * (dir * n) / PI);
+                } else {
+                    bounce_pdf = None;
+                    let refracted =
+                        dir.refract(refraction_normal(&hit, dir), material.refractive_index);
+                    let weight = if pick < diffuse + mirror {
+                        tint(albedo[2], mirror)
+                    } else {
+                        tint(albedo[3], refraction)
+                    };
+                    // Total internal reflection sends the refracted share along the reflection.
+                    dir = match refracted {
+                        Some(refracted) if pick >= diffuse + mirror => refracted.normalize(),
+                        _ => dir.reflect(n).normalize(),
+                    };
+                    orig = offset(&hit, dir);
+                    throughput = throughput.component_mul(weight) * total;
+                }
+            }
+
+            if depth >= ROULETTE_DEPTH {
+                let survival = throughput.max_component().min(0.95);
+                if rng.next_f32() >= survival {
+                    break;
+                }
+                throughput = throughput * (1f32 / survival);
+            }
+        }
+        radiance
+    }
+}
+
+/// The weight of a sample with density `f` when the same light could also have been sampled with
+/// density `g`, by the power heuristic.
+fn power_heuristic(f: f32, g: f32) -> f32 {
+    if f <= 0f32 {
+        return 0f32;
+    }
+    let (f2, g2) = (f * f, g * g);
+    f2 / (f2 + g2)
+}
+
+/// The shading normal flipped to the side the ray `dir` arrives from.
+///
+/// The geometric normal decides the side, as a tilted shading normal may lean away from rays at
+/// grazing angles.
+fn facing(hit: &Hit, dir: Vec3f) -> Vec3f {
+    if hit.geometric_normal * dir > 0f32 {
+        -hit.normal
+    } else {
+        hit.normal
+    }
+}
+
+/// A point just off the surface at `hit` on the side `dir` points to, from which a ray along
+/// `dir` does not hit the surface again.
+fn offset(hit: &Hit, dir: Vec3f) -> Vec3f {
+    let n = hit.geometric_normal;
+    if dir * n < 0f32 {
+        hit.point - n * 1e-3
+    } else {
+        hit.point + n * 1e-3
+    }
+}
+
+/// The normal to refract the ray `dir` about at `hit`: the shading normal, unless it leans so far
+/// that it puts the ray on the other side of the surface than the geometric normal does.
+fn refraction_normal(hit: &Hit, dir: Vec3f) -> Vec3f {
+    if (hit.normal * dir > 0f32) == (hit.geometric_normal * dir > 0f32) {
+        hit.normal
+    } else {
+        hit.geometric_normal
+    }
+}
+
+/// The refractive index behind the surface relative to that in front of it, for a ray hitting a
+/// dielectric from either side.
+fn relative_eta(hit: &Hit, dir: Vec3f) -> f32 {
+    if hit.geometric_normal * dir > 0f32 {
+        1f32 / hit.material.refractive_index
+    } else {
+        hit.material.refractive_index
+    }
+}
+
+/// Attenuation of a ray that reached `hit` from inside an absorbing medium, i.e. through the back
+/// of its surface.
+fn medium_transmittance(hit: &Hit, dir: Vec3f, absorption: Vec3f) -> Vec3f {
+    if hit.geometric_normal * dir > 0f32 {
+        bsdf::transmittance(absorption, hit.t * dir.norm())
+    } else {
+        Vec3f::new(1.0, 1.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampling::MitchellFilter;
+    use crate::scene::Material;
+    use crate::shape::Sphere;
+
+    /// Places the samples of every pixel at fixed points.
+    #[derive(Debug)]
+    struct Fixed(Vec<[f32; 2]>);
+
+    impl Sampler for Fixed {
+        fn pixel_samples(&self, count: usize, _rng: &mut Rng, samples: &mut Vec<[f32; 2]>) {
+            samples.extend(self.0.iter().cycle().take(count));
+        }
+    }
+
+    /// Renders the single pixel of a camera looking down -z with a Mitchell-Netravali filter,
+    /// from a white background and a black sphere seen `offset` pixels right of the center.
+    fn render_pixel_with(offset: f32, samples: Vec<[f32; 2]>) -> Vec3f {
+        let mut scene = Scene::new();
+        scene.background = Vec3f::new(1.0, 1.0, 1.0);
+        scene.add(Sphere::new(
+            Vec3f::new(2.0 * offset, 0.0, -1.0),
+            0.3,
+            Material::default(),
+        ));
+        let renderer = Renderer {
+            threads: 1,
+            samples: samples.len(),
+            sampler: Arc::new(Fixed(samples)),
+            filter: Arc::new(MitchellFilter::default()),
+            ..Renderer::new()
+        };
+        renderer.render(&scene, &Camera::new(1, 1)).pixels()[0]
+    }
+
+    #[test]
+    fn negative_filter_lobes_do_not_blow_up_pixels() {
+        // The center sample sees the background and 25 samples 1.5 pixels to the right, in the
+        // negative lobe, see the sphere, leaving a summed weight of almost zero.
+        let mut samples = vec![[0.5, 0.5]];
+        samples.extend([[0.875, 0.5]; 25]);
+        let color = render_pixel_with(1.5, samples);
+        assert!(
+            color.max_component() < 1f32 / MIN_FILTER_WEIGHT,
+            "{:?}",
+            color
+        );
+    }
+
+    #[test]
+    fn negative_filter_lobes_do_not_flip_pixels() {
+        // The center sample sees the sphere, and only a sample in the negative lobe the
+        // background.
+        let color = render_pixel_with(0.0, vec![[0.5, 0.5], [0.875, 0.5]]);
+        assert_eq!(color.0, [0.0; 3]);
+    }
+}
@@ -0,0 +1,574 @@
+//! Random numbers, pixel sample patterns, reconstruction filters and direction sampling.
+
+use crate::geometry::Vec3f;
+use std::f32::consts::PI;
+use std::fmt;
+use std::sync::OnceLock;
+
+/// A small, fast PCG32 random number generator.
+///
+/// The renderer seeds one per pixel so that images do not depend on scheduling.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+    inc: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64, stream: u64) -> Self {
+        let mut rng = Self {
+            state: 0,
+            inc: (stream << 1) | 1,
+        };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+        rng
+    }
+
+    /// A generator for pixel (`x`, `y`), independent of every other pixel.
+    pub fn for_pixel(x: usize, y: usize) -> Self {
+        Self::new(x as u64, y as u64)
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(self.inc);
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        xorshifted.rotate_right((old >> 59) as u32)
+    }
+
+    /// Returns a uniformly distributed number in [0, 1).
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 * (1f32 / (1u32 << 24) as f32)
+    }
+}
+
+/// Wraps a coordinate shifted by a random offset back into [0, 1).
+fn rotate(x: f32, offset: f32) -> f32 {
+    let x = x + offset;
+    if x >= 1f32 {
+        x - 1f32
+    } else {
+        x
+    }
+}
+
+/// A strategy for placing samples within a pixel.
+pub trait Sampler: fmt::Debug + Send + Sync {
+    /// Appends `count` points of the unit square to `samples`.
+    ///
+    /// `rng` is seeded for the pixel being sampled and may be used to decorrelate pixels.
+    fn pixel_samples(&self, count: usize, rng: &mut Rng, samples: &mut Vec<[f32; 2]>);
+}
+
+/// The cell of sample `index` out of `count` in a grid of equal-area cells tiling the unit
+/// square, as its corner and size.
+///
+/// Counts that are not perfect squares spread the leftover samples over the rows, which then hold
+/// one more or one fewer sample and are as tall as their share of samples.
+fn grid_cell(index: usize, count: usize) -> ([f32; 2], [f32; 2]) {
+    let rows = ((count as f32).sqrt() as usize).max(1);
+    // Row `r` holds the samples from `start(r)` up to `start(r + 1)`.
+    let start = |row: usize| count * row / rows;
+    let row = ((index + 1) * rows).div_ceil(count) - 1;
+    let (first, end) = (start(row), start(row + 1));
+    let columns = end - first;
+    (
+        [
+            (index - first) as f32 / columns as f32,
+            first as f32 / count as f32,
+        ],
+        [1f32 / columns as f32, columns as f32 / count as f32],
+    )
+}
+
+/// Samples at the centers of the cells of a grid, the pixel center for a single sample.
+///
+/// Rows hold one sample more than others if `count` is not a perfect square, see [`stratum`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Grid;
+
+impl Sampler for Grid {
+    fn pixel_samples(&self, count: usize, _rng: &mut Rng, samples: &mut Vec<[f32; 2]>) {
+        samples.extend((0..count).map(|k| {
+            let ([x, y], [width, height]) = grid_cell(k, count);
+            [x + 0.5 * width, y + 0.5 * height]
+        }));
+    }
+}
+
+/// A random point in cell `index` of the grid [`Stratified`] divides the unit square into for
+/// `count` samples.
+///
+/// The cells are equally large and tile the square even if `count` is not a perfect square, in
+/// which case rows hold one sample more than others and are taller to match.
+pub fn stratum(index: usize, count: usize, rng: &mut Rng) -> [f32; 2] {
+    let ([x, y], [width, height]) = grid_cell(index, count);
+    [x + rng.next_f32() * width, y + rng.next_f32() * height]
+}
+
+/// One random sample in each cell of the grid described by [`stratum`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Stratified;
+
+impl Sampler for Stratified {
+    fn pixel_samples(&self, count: usize, rng: &mut Rng, samples: &mut Vec<[f32; 2]>) {
+        samples.extend((0..count).map(|k| stratum(k, count, rng)));
+    }
+}
+
+fn radical_inverse(base: u32, mut i: u32) -> f32 {
+    let inv_base = 1f32 / base as f32;
+    let mut factor = inv_base;
+    let mut value = 0f32;
+    while i > 0 {
+        value += (i % base) as f32 * factor;
+        i /= base;
+        factor *= inv_base;
+    }
+    value.min(1f32 - f32::EPSILON / 2f32)
+}
+
+/// The Halton sequence in bases 2 and 3, randomly shifted per pixel.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Halton;
+
+impl Sampler for Halton {
+    fn pixel_samples(&self, count: usize, rng: &mut Rng, samples: &mut Vec<[f32; 2]>) {
+        let offset = [rng.next_f32(), rng.next_f32()];
+        samples.extend((0..count as u32).map(|i| {
+            [
+                rotate(radical_inverse(2, i), offset[0]),
+                rotate(radical_inverse(3, i), offset[1]),
+            ]
+        }));
+    }
+}
+
+/// The first two dimensions of the Sobol sequence, scrambled per pixel by a random digit shift.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Sobol;
+
+impl Sampler for Sobol {
+    fn pixel_samples(&self, count: usize, rng: &mut Rng, samples: &mut Vec<[f32; 2]>) {
+        let scramble = [rng.next_u32(), rng.next_u32()];
+        samples.extend((0..count as u32).map(|i| {
+            let x = i.reverse_bits();
+            let mut y = 0u32;
+            let mut v = 1u32 << 31;
+            let mut bits = i;
+            while bits > 0 {
+                if bits & 1 != 0 {
+                    y ^= v;
+                }
+                bits >>= 1;
+                v ^= v >> 1;
+            }
+            let to_unit = |b: u32| (b >> 8) as f32 * (1f32 / (1u32 << 24) as f32);
+            [to_unit(x ^ scramble[0]), to_unit(y ^ scramble[1])]
+        }));
+    }
+}
+
+/// Samples from a progressive best-candidate pattern, which keeps points evenly spaced without
+/// visible structure. The pattern is shifted randomly per pixel.
+#[derive(Debug, Default)]
+pub struct BlueNoise {
+    pattern: OnceLock<Vec<[f32; 2]>>,
+}
+
+impl BlueNoise {
+    /// Number of distinct points; larger counts repeat the pattern with a different shift.
+    const PATTERN_SIZE: usize = 256;
+    const CANDIDATES: usize = 16;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn pattern(&self) -> &[[f32; 2]] {
+        self.pattern.get_or_init(|| {
+            let mut rng = Rng::new(0x5eed, 0);
+            let mut points: Vec<[f32; 2]> = Vec::with_capacity(Self::PATTERN_SIZE);
+            let toroidal = |a: [f32; 2], b: [f32; 2]| {
+                let dx = (a[0] - b[0]).abs();
+                let dy = (a[1] - b[1]).abs();
+                let (dx, dy) = (dx.min(1f32 - dx), dy.min(1f32 - dy));
+                dx * dx + dy * dy
+            };
+            while points.len() < Self::PATTERN_SIZE {
+                let mut best = ([0f32; 2], -1f32);
+                for _ in 0..Self::CANDIDATES * (points.len() + 1) / 4 + 1 {
+                    let candidate = [rng.next_f32(), rng.next_f32()];
+                    let distance = points
+                        .iter()
+                        .map(|&p| toroidal(p, candidate))
+                        .fold(f32::INFINITY, f32::min);
+                    if distance > best.1 {
+                        best = (candidate, distance);
+                    }
+                }
+                points.push(best.0);
+            }
+            points
+        })
+    }
+}
+
+impl Sampler for BlueNoise {
+    fn pixel_samples(&self, count: usize, rng: &mut Rng, samples: &mut Vec<[f32; 2]>) {
+        let pattern = self.pattern();
+        for chunk in 0..count.div_ceil(pattern.len()) {
+            let offset = [rng.next_f32(), rng.next_f32()];
+            let n = (count - chunk * pattern.len()).min(pattern.len());
+            samples.extend(
+                pattern[..n]
+                    .iter()
+                    .map(|p| [rotate(p[0], offset[0]), rotate(p[1], offset[1])]),
+            );
+        }
+    }
+}
+
+/// A pixel reconstruction filter.
+///
+/// Each pixel is the filter-weighted average of the samples taken within `radius` of its center.
+pub trait Filter: fmt::Debug + Send + Sync {
+    /// Half the width of the filter's support, in pixels.
+    fn radius(&self) -> f32;
+
+    /// The weight of a sample at offset (`x`, `y`) from the pixel center.
+    fn evaluate(&self, x: f32, y: f32) -> f32;
+}
+
+/// Weights every sample equally.
+#[derive(Debug, Clone, Copy)]
+pub struct BoxFilter {
+    pub radius: f32,
+}
+
+impl Default for BoxFilter {
+    fn default() -> Self {
+        Self { radius: 0.5 }
+    }
+}
+
+impl Filter for BoxFilter {
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn evaluate(&self, _x: f32, _y: f32) -> f32 {
+        1f32
+    }
+}
+
+/// Weights fall off linearly from the center.
+#[derive(Debug, Clone, Copy)]
+pub struct TentFilter {
+    pub radius: f32,
+}
+
+impl Default for TentFilter {
+    fn default() -> Self {
+        Self { radius: 1f32 }
+    }
+}
+
+impl Filter for TentFilter {
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f32, y: f32) -> f32 {
+        (self.radius - x.abs()).max(0f32) * (self.radius - y.abs()).max(0f32)
+    }
+}
+
+/// A Gaussian with standard deviation `sigma`, shifted to reach zero at `radius`.
+#[derive(Debug, Clone, Copy)]
+pub struct GaussianFilter {
+    pub radius: f32,
+    pub sigma: f32,
+}
+
+impl Default for GaussianFilter {
+    fn default() -> Self {
+        Self {
+            radius: 1.5,
+            sigma: 0.5,
+        }
+    }
+}
+
+impl GaussianFilter {
+    fn gaussian(&self, x: f32) -> f32 {
+        let g = |x: f32| (-x * x / (2f32 * self.sigma * self.sigma)).exp();
+        (g(x) - g(self.radius)).max(0f32)
+    }
+}
+
+impl Filter for GaussianFilter {
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f32, y: f32) -> f32 {
+        self.gaussian(x) * self.gaussian(y)
+    }
+}
+
+/// The Mitchell-Netravali cubic, with the recommended `b = c = 1/3` by default.
+#[derive(Debug, Clone, Copy)]
+pub struct MitchellFilter {
+    pub radius: f32,
+    pub b: f32,
+    pub c: f32,
+}
+
+impl Default for MitchellFilter {
+    fn default() -> Self {
+        Self {
+            radius: 2f32,
+            b: 1f32 / 3f32,
+            c: 1f32 / 3f32,
+        }
+    }
+}
+
+impl MitchellFilter {
+    /// The one-dimensional cubic over [-2, 2].
+    fn mitchell(&self, x: f32) -> f32 {
+        let (b, c) = (self.b, self.c);
+        let x = x.abs();
+        if x < 1f32 {
+            ((12f32 - 9f32 * b - 6f32 * c) * x * x * x
+                + (-18f32 + 12f32 * b + 6f32 * c) * x * x
+                + (6f32 - 2f32 * b))
+                / 6f32
+        } else if x < 2f32 {
+            ((-b - 6f32 * c) * x * x * x
+                + (6f32 * b + 30f32 * c) * x * x
+                + (-12f32 * b - 48f32 * c) * x
+                + (8f32 * b + 24f32 * c))
+                / 6f32
+        } else {
+            0f32
+        }
+    }
+}
+
+impl Filter for MitchellFilter {
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f32, y: f32) -> f32 {
+        let scale = 2f32 / self.radius;
+        self.mitchell(x * scale) * self.mitchell(y * scale)
+    }
+}
+
+/// Maps a point of the unit square to the unit disk, preserving stratification.
+pub fn concentric_disk(u: [f32; 2]) -> [f32; 2] {
+    let (x, y) = (2f32 * u[0] - 1f32, 2f32 * u[1] - 1f32);
+    if x == 0f32 && y == 0f32 {
+        return [0f32, 0f32];
+    }
+    let (r, theta) = if x.abs() > y.abs() {
+        (x, PI / 4f32 * (y / x))
+    } else {
+        (y, PI / 2f32 - PI / 4f32 * (x / y))
+    };
+    [r * theta.cos(), r * theta.sin()]
+}
+
+/// Samples a direction around the unit vector `normal` with density `cos(theta) / PI`.
+pub fn cosine_hemisphere(normal: Vec3f, u: [f32; 2]) -> Vec3f {
+    let [x, y] = concentric_disk(u);
+    let z = (1f32 - x * x - y * y).max(0f32).sqrt();
+    let (t, b) = normal.basis();
+    t * x + b * y + normal * z
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid_cells_tile_the_square() {
+        for count in 1..=40 {
+            let cells = (0..count).map(|k| grid_cell(k, count)).collect::<Vec<_>>();
+            let mut area = 0f32;
+            for ([x, y], [width, height]) in &cells {
+                assert!((width * height - 1f32 / count as f32).abs() < 1e-6);
+                assert!(*x >= 0f32 && x + width <= 1f32 + 1e-6);
+                assert!(*y >= 0f32 && y + height <= 1f32 + 1e-6);
+                area += width * height;
+            }
+            assert!((area - 1f32).abs() < 1e-5, "{} samples", count);
+            // No two cells overlap.
+            for (i, ([x0, y0], [w0, h0])) in cells.iter().enumerate() {
+                for ([x1, y1], [w1, h1]) in &cells[i + 1..] {
+                    let overlap_x = (x0 + w0).min(x1 + w1) - x0.max(*x1);
+                    let overlap_y = (y0 + h0).min(y1 + h1) - y0.max(*y1);
+                    assert!(overlap_x <= 1e-6 || overlap_y <= 1e-6, "{} samples", count);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn grid_of_a_square_count_is_regular() {
+        let mut samples = Vec::new();
+        Grid.pixel_samples(4, &mut Rng::new(0, 0), &mut samples);
+        assert_eq!(
+            samples,
+            [[0.25, 0.25], [0.75, 0.25], [0.25, 0.75], [0.75, 0.75]]
+        );
+    }
+
+    #[test]
+    fn grid_spreads_leftover_samples_over_the_rows() {
+        // Rows of two and three samples, as tall as their share of them.
+        let mut samples = Vec::new();
+        Grid.pixel_samples(5, &mut Rng::new(0, 0), &mut samples);
+        let expected = [
+            [0.25, 0.2],
+            [0.75, 0.2],
+            [1.0 / 6.0, 0.7],
+            [0.5, 0.7],
+            [5.0 / 6.0, 0.7],
+        ];
+        for (sample, expected) in samples.iter().zip(expected) {
+            assert!((sample[0] - expected[0]).abs() < 1e-6, "{:?}", samples);
+            assert!((sample[1] - expected[1]).abs() < 1e-6, "{:?}", samples);
+        }
+    }
+
+    /// The samples of `sampler` for one pixel.
+    fn samples(sampler: &dyn Sampler, count: usize) -> Vec<[f32; 2]> {
+        let mut samples = Vec::new();
+        sampler.pixel_samples(count, &mut Rng::for_pixel(3, 7), &mut samples);
+        assert_eq!(samples.len(), count);
+        for [x, y] in &samples {
+            assert!((0f32..1f32).contains(x) && (0f32..1f32).contains(y));
+        }
+        samples
+    }
+
+    /// How many of `samples` fall in each of `n` equal intervals along `axis`.
+    fn histogram(samples: &[[f32; 2]], axis: usize, n: usize) -> Vec<usize> {
+        let mut counts = vec![0; n];
+        for sample in samples {
+            counts[(sample[axis] * n as f32) as usize] += 1;
+        }
+        counts
+    }
+
+    #[test]
+    fn halton_projections_are_evenly_spread() {
+        assert_eq!(
+            (0..4).map(|i| radical_inverse(2, i)).collect::<Vec<_>>(),
+            [0.0, 0.5, 0.25, 0.75]
+        );
+        // The first 16 · 9 points fall once in every interval of the van der Corput sequences,
+        // and the random shift moves at most one point into a neighboring interval.
+        let samples = samples(&Halton, 144);
+        for count in histogram(&samples, 0, 16) {
+            assert!((8..=10).contains(&count), "{:?}", samples);
+        }
+        for count in histogram(&samples, 1, 9) {
+            assert!((15..=17).contains(&count), "{:?}", samples);
+        }
+    }
+
+    #[test]
+    fn sobol_points_form_a_net() {
+        // Scrambling keeps exactly one of 16 points in every elementary interval of area 1/16.
+        let samples = samples(&Sobol, 16);
+        for columns in [1, 2, 4, 8, 16] {
+            let rows = 16 / columns;
+            let mut cells = [0; 16];
+            for [x, y] in &samples {
+                let cell = (x * columns as f32) as usize * rows + (y * rows as f32) as usize;
+                cells[cell] += 1;
+            }
+            assert!(cells.iter().all(|&n| n == 1), "{}x{}", columns, rows);
+        }
+    }
+
+    #[test]
+    fn blue_noise_keeps_points_apart() {
+        let samples = samples(&BlueNoise::new(), 64);
+        let closest = samples
+            .iter()
+            .enumerate()
+            .flat_map(|(i, a)| samples[i + 1..].iter().map(move |b| (a, b)))
+            .map(|(a, b)| {
+                let dx = (a[0] - b[0]).abs();
+                let dy = (a[1] - b[1]).abs();
+                let (dx, dy) = (dx.min(1f32 - dx), dy.min(1f32 - dy));
+                (dx * dx + dy * dy).sqrt()
+            })
+            .fold(f32::INFINITY, f32::min);
+        // Uniformly random points would likely have a pair a few times closer.
+        assert!(closest > 0.5 / 8f32, "closest pair {} apart", closest);
+        for count in histogram(&samples, 0, 4)
+            .into_iter()
+            .chain(histogram(&samples, 1, 4))
+        {
+            assert!((12..=20).contains(&count), "{:?}", samples);
+        }
+    }
+
+    /// The integral of `filter` over its support by the midpoint rule.
+    fn integral(filter: &dyn Filter) -> f32 {
+        let n = 200;
+        let step = 2f32 * filter.radius() / n as f32;
+        let at = |i: usize| -filter.radius() + (i as f32 + 0.5) * step;
+        (0..n)
+            .flat_map(|i| (0..n).map(move |j| (at(i), at(j))))
+            .map(|(x, y)| f64::from(filter.evaluate(x, y) * step * step))
+            .sum::<f64>() as f32
+    }
+
+    #[test]
+    fn filters_peak_at_the_center_and_vanish_at_the_edge() {
+        let filters: [&dyn Filter; 3] = [
+            &TentFilter::default(),
+            &GaussianFilter::default(),
+            &MitchellFilter::default(),
+        ];
+        for filter in filters {
+            let r = filter.radius();
+            let center = filter.evaluate(0f32, 0f32);
+            for (x, y) in [(0.3, 0.1), (-0.5, 0.2), (0.1, -0.7), (0.5, 0.5)] {
+                let (x, y) = (x * r, y * r);
+                let w = filter.evaluate(x, y);
+                assert!(w < center, "{:?}", filter);
+                assert_eq!(w, filter.evaluate(-x, y), "{:?}", filter);
+                assert_eq!(w, filter.evaluate(y, x), "{:?}", filter);
+            }
+            for (x, y) in [(r, 0f32), (0f32, -r), (1.5 * r, 0.2)] {
+                assert!(filter.evaluate(x, y).abs() < 1e-6, "{:?}", filter);
+            }
+        }
+    }
+
+    #[test]
+    fn filter_integrals() {
+        let r = 1.5f32;
+        let expected = (2f32 * r).powi(2);
+        assert!((integral(&BoxFilter { radius: r }) - expected).abs() < 1e-3);
+        assert!((integral(&TentFilter { radius: r }) - r.powi(4)).abs() < 1e-3);
+        // Mitchell-Netravali is normalized over its default support, despite its negative lobes.
+        let mitchell = MitchellFilter::default();
+        assert!((integral(&mitchell) - 1f32).abs() < 1e-3);
+        assert!(mitchell.evaluate(1.5, 0f32) < 0f32);
+        assert!(integral(&GaussianFilter::default()) > 0f32);
+    }
+}
@@ -0,0 +1,631 @@
+use crate::bsdf::Microfacet;
+use crate::environment::Environment;
+use crate::geometry::Vec3f;
+use crate::instance::Group;
+use crate::sampling::{self, Rng};
+use crate::shape::{Hit, Shape};
+use crate::texture::Texture;
+use std::sync::{Arc, OnceLock};
+
+/// The shape of a light source. Larger lights cast softer shadows.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum LightShape {
+    #[default]
+    Point,
+    /// A sphere of `radius` around the light's position.
+    Sphere { radius: f32 },
+    /// A parallelogram centered on the light's position and spanned by the edges `u` and `v`.
+    Rectangle { u: Vec3f, v: Vec3f },
+    /// A disk of `radius` centered on the light's position, perpendicular to `normal`.
+    Disk { normal: Vec3f, radius: f32 },
+    /// A distant light such as the sun, shining in `direction` everywhere. The light's position
+    /// is unused.
+    Directional { direction: Vec3f },
+}
+
+/// Restricts a light to a cone around `direction`, fading out between the `inner` and `outer`
+/// angles from its axis, in radians.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Spot {
+    pub direction: Vec3f,
+    pub inner: f32,
+    pub outer: f32,
+}
+
+impl Spot {
+    /// The fraction of light emitted in `direction`.
+    fn falloff(&self, direction: Vec3f) -> f32 {
+        let cos = self.direction.normalize() * direction;
+        let (cos_inner, cos_outer) = (self.inner.cos(), self.outer.cos());
+        if cos_inner <= cos_outer {
+            return if cos >= cos_outer { 1f32 } else { 0f32 };
+        }
+        let t = ((cos - cos_outer) / (cos_inner - cos_outer)).clamp(0f32, 1f32);
+        t * t * (3f32 - 2f32 * t)
+    }
+}
+
+/// A light source.
+///
+/// Lights do not dim with distance unless `inverse_square` is set. An area light shines as
+/// brightly as a point light of the same `intensity` would, spread over its surface. It is
+/// sampled with `samples` stratified shadow rays.
+#[derive(Debug, Clone, Copy)]
+pub struct Light {
+    pub position: Vec3f,
+    pub intensity: f32,
+    /// Tint of the light, multiplied by `intensity`.
+    pub color: Vec3f,
+    pub shape: LightShape,
+    /// Number of shadow rays for area lights.
+    pub samples: usize,
+    pub spot: Option<Spot>,
+    /// Whether the light falls off with the squared distance, as real lights do. The intensity
+    /// then needs to be much higher.
+    pub inverse_square: bool,
+}
+
+/// A shadow ray towards a light, see [`Light::sample`].
+#[derive(Debug, Clone, Copy)]
+pub struct LightSample {
+    /// Unit vector from the lit point towards the light.
+    pub direction: Vec3f,
+    /// Distance to the light, infinite for directional lights.
+    pub distance: f32,
+    /// The colored intensity arriving along the ray if nothing is in the way.
+    pub intensity: Vec3f,
+}
+
+impl Light {
+    /// Shadow rays traced towards area lights unless configured otherwise.
+    pub const DEFAULT_SAMPLES: usize = 16;
+
+    /// A white point light.
+    pub fn new(position: Vec3f, intensity: f32) -> Self {
+        Self {
+            position,
+            intensity,
+            color: Vec3f::new(1.0, 1.0, 1.0),
+            shape: LightShape::Point,
+            samples: Self::DEFAULT_SAMPLES,
+            spot: None,
+            inverse_square: false,
+        }
+    }
+
+    /// An area light of the given shape, centered on `position`.
+    pub fn area(position: Vec3f, intensity: f32, shape: LightShape) -> Self {
+        Self {
+            shape,
+            ..Self::new(position, intensity)
+        }
+    }
+
+    /// A distant light shining in `direction`.
+    pub fn directional(direction: Vec3f, intensity: f32) -> Self {
+        Self::area(
+            Vec3f::default(),
+            intensity,
+            LightShape::Directional {
+                direction: direction.normalize(),
+            },
+        )
+    }
+
+    pub fn with_color(mut self, color: Vec3f) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Turns the light into a spot light, see [`Spot`].
+    ///
+    /// ```
+    /// use tinyraytracer::sampling::Rng;
+    /// use tinyraytracer::{Light, Vec3f};
+    ///
+    /// let down = Vec3f::new(0.0, -1.0, 0.0);
+    /// let spot = Light::new(Vec3f::new(0.0, 10.0, 0.0), 1.0).with_spot(down, 0.2, 0.3);
+    /// let mut rng = Rng::new(0, 0);
+    ///
+    /// assert_eq!(spot.sample(Vec3f::default(), 0, &mut rng).intensity.0, [1.0; 3]);
+    /// let outside = spot.sample(Vec3f::new(10.0, 0.0, 0.0), 0, &mut rng);
+    /// assert_eq!(outside.intensity.0, [0.0; 3]);
+    /// ```
+    pub fn with_spot(mut self, direction: Vec3f, inner: f32, outer: f32) -> Self {
+        self.spot = Some(Spot {
+            direction,
+            inner,
+            outer,
+        });
+        self
+    }
+
+    /// The number of shadow rays to trace towards the light, one unless it is an area light.
+    pub fn sample_count(&self) -> usize {
+        match self.shape {
+            LightShape::Point | LightShape::Directional { .. } => 1,
+            _ => self.samples.max(1),
+        }
+    }
+
+    /// Samples shadow ray `index` of [`sample_count`](Self::sample_count) from `from`, aimed at
+    /// a point jittered within its stratum of the light's surface. Each ray carries its share
+    /// of the intensity.
+    ///
+    /// Spheres are sampled over the disk they appear as from `from`.
+    pub fn sample(&self, from: Vec3f, index: usize, rng: &mut Rng) -> LightSample {
+        let count = self.sample_count();
+        let intensity = self.color * (self.intensity / count as f32);
+        let disk = |normal: Vec3f, radius: f32, rng: &mut Rng| {
+            let [x, y] = sampling::concentric_disk(sampling::stratum(index, count, rng));
+            let (t, b) = normal.basis();
+            self.position + (t * x + b * y) * radius
+        };
+        let point = match self.shape {
+            LightShape::Point => self.position,
+            LightShape::Sphere { radius } => disk((from - self.position).normalize(), radius, rng),
+            LightShape::Rectangle { u, v } => {
+                let [x, y] = sampling::stratum(index, count, rng);
+                self.position + u * (x - 0.5) + v * (y - 0.5)
+            }
+            LightShape::Disk { normal, radius } => disk(normal.normalize(), radius, rng),
+            LightShape::Directional { direction } => {
+                return LightSample {
+                    direction: -direction.normalize(),
+                    distance: f32::INFINITY,
+                    intensity,
+                };
+            }
+        };
+        let to_light = point - from;
+        let distance = to_light.norm();
+        let direction = to_light * (1f32 / distance);
+        let mut scale = 1f32;
+        if let Some(spot) = &self.spot {
+            scale *= spot.falloff(-direction);
+        }
+        if self.inverse_square {
+            scale /= distance * distance;
+        }
+        LightSample {
+            direction,
+            distance,
+            intensity: intensity * scale,
+        }
+    }
+}
+
+/// How a [`Material`] scatters light.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Model {
+    /// Phong shading plus mirror reflection and refraction, weighted by the material's `albedo`.
+    #[default]
+    Phong,
+    /// A physically based GGX microfacet surface whose base color is the material's
+    /// `diffuse_color`; `albedo`, `refractive_index` and `specular_exponent` are unused.
+    ///
+    /// `metallic` blends from a dielectric to a metal and `roughness` from a mirror-like to a
+    /// matte finish, both from 0 to 1.
+    MetallicRoughness { metallic: f32, roughness: f32 },
+    /// A smooth transparent surface such as glass or water, which reflects and refracts in the
+    /// proportions given by the Fresnel equations for its `refractive_index`; `albedo`,
+    /// `diffuse_color` and `specular_exponent` are unused.
+    ///
+    /// Light travelling inside is attenuated by `exp(-absorption * distance)`, which tints thick
+    /// parts of the object more than thin ones.
+    Dielectric { absorption: Vec3f },
+}
+
+/// Surface detail that tilts the shading normal without changing the geometry.
+///
+/// ```
+/// use std::sync::Arc;
+/// use tinyraytracer::{Bump, Material, Plane, Scene, Vec3f};
+///
+/// // A constant normal map leaning towards the plane's tangent, its first texture direction.
+/// let tilted = Vec3f::new(0.5 + 0.5 * 0.6, 0.5, 0.5 + 0.5 * 0.8);
+/// let material = Material::default().with_bump(Bump::Normal(Arc::new(tilted)));
+/// let up = Vec3f::new(0.0, 1.0, 0.0);
+/// let mut scene = Scene::new();
+/// scene.add(Plane::new(Vec3f::new(0.0, -1.0, 0.0), up, material));
+///
+/// let hit = scene.intersect(Vec3f::default(), Vec3f::new(0.0, -1.0, 0.0)).unwrap();
+/// let (tangent, _) = up.basis();
+/// assert!((hit.normal * up - 0.8).abs() < 1e-5);
+/// assert!((hit.normal * tangent - 0.6).abs() < 1e-5);
+/// ```
+#[derive(Debug, Clone)]
+pub enum Bump {
+    /// A tangent-space normal map: the channels of the texture, mapped from [0, 1] to [-1, 1],
+    /// are the components of the normal along the tangent, the bitangent and the geometric
+    /// normal. The texture should hold linear values.
+    Normal(Arc<dyn Texture>),
+    /// A height field raising the surface along its normal by the luminance of the texture times
+    /// `scale`, in scene units.
+    Height {
+        texture: Arc<dyn Texture>,
+        scale: f32,
+    },
+}
+
+impl Bump {
+    /// Height in scene units of the white parts of height fields, unless configured otherwise.
+    pub const DEFAULT_SCALE: f32 = 0.05;
+}
+
+/// Texture coordinate offset for the finite differences of bump maps.
+const BUMP_STEP: f32 = 5e-4;
+
+/// Textures varying the parameters of a [`Material`] besides its diffuse color over the surface.
+///
+/// Each multiplies the parameter it is named after, tinting the [`Phong`](Model::Phong) weights
+/// by its color and scaling scalar parameters by its luminance. The refractive index and the
+/// absorption of dielectrics, which describe the inside of an object, stay constant.
+///
+/// ```
+/// use std::sync::Arc;
+/// use tinyraytracer::{Maps, Material, Vec3f};
+///
+/// let mut material = Material::new(1.0, [0.6, 0.3, 0.5, 0.0], Vec3f::default(), 50.0);
+/// material.maps = Maps {
+///     reflection: Some(Arc::new(Vec3f::new(1.0, 0.5, 0.0))),
+///     specular_exponent: Some(Arc::new(Vec3f::new(0.5, 0.5, 0.5))),
+///     ..Maps::default()
+/// };
+/// let surface = material.phong_at([0.0, 0.0], Vec3f::default());
+/// assert_eq!(surface.albedo[1].0, [0.3; 3]);
+/// assert_eq!(surface.albedo[2].0, [0.5, 0.25, 0.0]);
+/// assert!((surface.specular_exponent - 25.0).abs() < 1e-4);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Maps {
+    /// Tints the specular highlights, weighted by `albedo[1]`.
+    pub specular: Option<Arc<dyn Texture>>,
+    /// Tints the mirror reflection, weighted by `albedo[2]`.
+    pub reflection: Option<Arc<dyn Texture>>,
+    /// Tints the refracted light, weighted by `albedo[3]`.
+    pub refraction: Option<Arc<dyn Texture>>,
+    /// Scales the `specular_exponent` of Phong highlights.
+    pub specular_exponent: Option<Arc<dyn Texture>>,
+    /// Scales `metallic` of the [`MetallicRoughness`](Model::MetallicRoughness) model.
+    pub metallic: Option<Arc<dyn Texture>>,
+    /// Scales `roughness` of the [`MetallicRoughness`](Model::MetallicRoughness) model.
+    pub roughness: Option<Arc<dyn Texture>>,
+}
+
+impl Maps {
+    /// `value` scaled by the luminance of `map` at texture coordinates `uv` of `point`.
+    fn scale(map: &Option<Arc<dyn Texture>>, value: f32, uv: [f32; 2], point: Vec3f) -> f32 {
+        match map {
+            Some(texture) => value * texture.evaluate(uv, point).luminance(),
+            None => value,
+        }
+    }
+}
+
+/// The parameters of a [`Phong`](Model::Phong) material at a surface point, with its textures
+/// applied, see [`Material::phong_at`].
+#[derive(Debug, Clone, Copy)]
+pub struct PhongSurface {
+    pub diffuse_color: Vec3f,
+    /// The weights of the diffuse, specular, reflected and refracted contributions, per color
+    /// channel.
+    pub albedo: [Vec3f; 4],
+    pub specular_exponent: f32,
+}
+
+/// Surface material.
+///
+/// With the [`Phong`](Model::Phong) model, `albedo` weights the diffuse, specular, reflected and
+/// refracted contributions, in that order.
+#[derive(Debug, Clone)]
+pub struct Material {
+    pub model: Model,
+    pub albedo: [f32; 4],
+    pub diffuse_color: Vec3f,
+    /// Varies the diffuse color over the surface, multiplying `diffuse_color`.
+    pub texture: Option<Arc<dyn Texture>>,
+    /// Vary the other parameters over the surface.
+    pub maps: Maps,
+    pub bump: Option<Bump>,
+    pub refractive_index: f32,
+    pub specular_exponent: f32,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            model: Model::Phong,
+            albedo: [1f32, 0f32, 0f32, 0f32],
+            diffuse_color: Vec3f::default(),
+            texture: None,
+            maps: Maps::default(),
+            bump: None,
+            refractive_index: 1f32,
+            specular_exponent: 0f32,
+        }
+    }
+}
+
+impl Material {
+    pub fn new(r: f32, albedo: [f32; 4], color: Vec3f, spec: f32) -> Self {
+        Self {
+            model: Model::Phong,
+            albedo,
+            diffuse_color: color,
+            texture: None,
+            maps: Maps::default(),
+            bump: None,
+            refractive_index: r,
+            specular_exponent: spec,
+        }
+    }
+
+    pub fn with_texture<T: Texture + 'static>(mut self, texture: T) -> Self {
+        self.texture = Some(Arc::new(texture));
+        self
+    }
+
+    pub fn with_bump(mut self, bump: Bump) -> Self {
+        self.bump = Some(bump);
+        self
+    }
+
+    /// The normal to shade `hit` with, tilted by the material's [`Bump`] map if it has one.
+    pub fn shading_normal(&self, hit: &Hit) -> Vec3f {
+        let n = hit.normal;
+        let normal = match &self.bump {
+            None => return n,
+            Some(Bump::Normal(texture)) => {
+                let [x, y, z] = texture
+                    .evaluate(hit.uv, hit.point)
+                    .0
+                    .map(|c| 2f32 * c - 1f32);
+                let (tangent, bitangent) = hit.tangent_frame();
+                tangent * x + bitangent * y + n * z
+            }
+            Some(Bump::Height { texture, scale }) => {
+                let [u, v] = hit.uv;
+                let height = |du: f32, dv: f32| {
+                    let p = hit.point + hit.dpdu * du + hit.dpdv * dv;
+                    texture.evaluate([u + du, v + dv], p).luminance() * scale
+                };
+                let dhdu =
+                    (height(BUMP_STEP, 0f32) - height(-BUMP_STEP, 0f32)) / (2f32 * BUMP_STEP);
+                let dhdv =
+                    (height(0f32, BUMP_STEP) - height(0f32, -BUMP_STEP)) / (2f32 * BUMP_STEP);
+                let (tangent, bitangent) = hit.tangent_frame();
+                // The displaced surface's derivatives, falling back to the unit frame where the
+                // texture coordinates are degenerate.
+                let dpdu = if hit.dpdu * hit.dpdu > 0f32 {
+                    hit.dpdu
+                } else {
+                    tangent
+                };
+                let dpdv = if hit.dpdv * hit.dpdv > 0f32 {
+                    hit.dpdv
+                } else {
+                    bitangent
+                };
+                let normal = (dpdu + n * dhdu).cross(dpdv + n * dhdv);
+                if normal * n < 0f32 {
+                    -normal
+                } else {
+                    normal
+                }
+            }
+        };
+        if normal * normal > 0f32 && normal * n > 0f32 {
+            normal.normalize()
+        } else {
+            n
+        }
+    }
+
+    /// The diffuse color at texture coordinates `uv` of the surface point `point`.
+    pub fn diffuse_at(&self, uv: [f32; 2], point: Vec3f) -> Vec3f {
+        match &self.texture {
+            Some(texture) => self
+                .diffuse_color
+                .component_mul(texture.evaluate(uv, point)),
+            None => self.diffuse_color,
+        }
+    }
+
+    /// The parameters of a [`Phong`](Model::Phong) material at texture coordinates `uv` of the
+    /// surface point `point`.
+    pub fn phong_at(&self, uv: [f32; 2], point: Vec3f) -> PhongSurface {
+        let weight = |albedo: f32, map: &Option<Arc<dyn Texture>>| {
+            let weight = Vec3f::new(albedo, albedo, albedo);
+            match map {
+                Some(texture) => weight.component_mul(texture.evaluate(uv, point)),
+                None => weight,
+            }
+        };
+        PhongSurface {
+            diffuse_color: self.diffuse_at(uv, point),
+            albedo: [
+                weight(self.albedo[0], &None),
+                weight(self.albedo[1], &self.maps.specular),
+                weight(self.albedo[2], &self.maps.reflection),
+                weight(self.albedo[3], &self.maps.refraction),
+            ],
+            specular_exponent: Maps::scale(
+                &self.maps.specular_exponent,
+                self.specular_exponent,
+                uv,
+                point,
+            ),
+        }
+    }
+
+    /// A physically based material, see [`Model::MetallicRoughness`].
+    pub fn metallic_roughness(base_color: Vec3f, metallic: f32, roughness: f32) -> Self {
+        Self {
+            model: Model::MetallicRoughness {
+                metallic,
+                roughness,
+            },
+            diffuse_color: base_color,
+            ..Self::default()
+        }
+    }
+
+    /// A smooth dielectric, see [`Model::Dielectric`].
+    pub fn dielectric(refractive_index: f32, absorption: Vec3f) -> Self {
+        Self {
+            model: Model::Dielectric { absorption },
+            refractive_index,
+            ..Self::default()
+        }
+    }
+
+    /// The microfacet BSDF of a metallic-roughness material at a surface point, see
+    /// [`diffuse_at`](Self::diffuse_at).
+    pub fn microfacet(&self, uv: [f32; 2], point: Vec3f) -> Option<Microfacet> {
+        match self.model {
+            Model::Phong | Model::Dielectric { .. } => None,
+            Model::MetallicRoughness {
+                metallic,
+                roughness,
+            } => {
+                let scale = |map, value| Maps::scale(map, value, uv, point).clamp(0f32, 1f32);
+                Some(Microfacet::new(
+                    self.diffuse_at(uv, point),
+                    scale(&self.maps.metallic, metallic),
+                    scale(&self.maps.roughness, roughness),
+                ))
+            }
+        }
+    }
+}
+
+/// Hits further away than this are ignored.
+const MAX_DISTANCE: f32 = 1000f32;
+
+/// The objects and lights to be rendered.
+#[derive(Debug, Clone)]
+pub struct Scene {
+    /// Color of rays that miss every object, unless there is an `environment`.
+    pub background: Vec3f,
+    /// Surrounds the scene with an image, which is seen by rays that miss every object and lights
+    /// the scene like a light source.
+    pub environment: Option<Arc<Environment>>,
+    objects: Vec<Arc<dyn Shape>>,
+    lights: Vec<Light>,
+    /// The objects with a bounding volume hierarchy over them, built on first use after the
+    /// scene has changed.
+    accel: OnceLock<Group>,
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Self {
+            background: Vec3f::new(0.2, 0.7, 0.8),
+            environment: None,
+            objects: Vec::new(),
+            lights: Vec::new(),
+            accel: OnceLock::new(),
+        }
+    }
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn objects(&self) -> &[Arc<dyn Shape>] {
+        &self.objects
+    }
+
+    pub fn lights(&self) -> &[Light] {
+        &self.lights
+    }
+
+    /// Adds an object to the scene, returning its index.
+    pub fn add<S: Shape + 'static>(&mut self, shape: S) -> usize {
+        self.add_shared(Arc::new(shape))
+    }
+
+    /// Adds an object that may also be referenced from elsewhere, returning its index.
+    pub fn add_shared(&mut self, shape: Arc<dyn Shape>) -> usize {
+        self.objects.push(shape);
+        self.accel = OnceLock::new();
+        self.objects.len() - 1
+    }
+
+    /// Removes the object at `index`, shifting the indices of the objects after it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn remove(&mut self, index: usize) -> Arc<dyn Shape> {
+        self.accel = OnceLock::new();
+        self.objects.remove(index)
+    }
+
+    pub fn add_light(&mut self, light: Light) {
+        self.lights.push(light);
+    }
+
+    /// The light arriving along a ray in `direction` that misses every object.
+    pub fn background_radiance(&self, direction: Vec3f) -> Vec3f {
+        match &self.environment {
+            Some(environment) => environment.radiance(direction),
+            None => self.background,
+        }
+    }
+
+    fn accel(&self) -> &Group {
+        self.accel.get_or_init(|| Group::new(self.objects.clone()))
+    }
+
+    /// Finds the nearest surface hit by the ray, with its shading normal tilted by any bump map
+    /// and its geometric normal left as the shape reported it.
+    pub fn intersect(&self, orig: Vec3f, direction: Vec3f) -> Option<Hit<'_>> {
+        self.accel()
+            .intersect(orig, direction, MAX_DISTANCE)
+            .map(|mut hit| {
+                hit.normal = hit.material.shading_normal(&hit);
+                hit
+            })
+    }
+
+    /// Checks whether anything blocks the ray closer than `distance`.
+    pub fn occluded(&self, orig: Vec3f, direction: Vec3f, distance: f32) -> bool {
+        self.accel()
+            .occluded(orig, direction, distance.min(MAX_DISTANCE))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn area_light_without_samples_traces_one_ray() {
+        let shapes = [
+            LightShape::Sphere { radius: 1.0 },
+            LightShape::Rectangle {
+                u: Vec3f::new(1.0, 0.0, 0.0),
+                v: Vec3f::new(0.0, 0.0, 1.0),
+            },
+            LightShape::Disk {
+                normal: Vec3f::new(0.0, -1.0, 0.0),
+                radius: 1.0,
+            },
+        ];
+        let mut rng = Rng::new(0, 0);
+        for shape in shapes {
+            let light = Light {
+                samples: 0,
+                ..Light::area(Vec3f::new(0.0, 10.0, 0.0), 2.0, shape)
+            };
+            assert_eq!(light.sample_count(), 1);
+            let sample = light.sample(Vec3f::default(), 0, &mut rng);
+            assert_eq!(sample.intensity.0, [2.0; 3]);
+            assert!(sample.distance.is_finite());
+        }
+    }
+}
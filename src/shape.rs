@@ -0,0 +1,355 @@
+//! Geometric primitives.
+
+use crate::geometry::{Aabb, Vec3f};
+use crate::scene::Material;
+use crate::texture::Pattern;
+use std::f32::consts::PI;
+use std::fmt;
+
+/// A ray-surface intersection.
+#[derive(Debug, Clone, Copy)]
+pub struct Hit<'a> {
+    /// Distance along the ray.
+    pub t: f32,
+    pub point: Vec3f,
+    /// Unit shading normal, which smooth normals and bump maps may tilt.
+    pub normal: Vec3f,
+    /// Unit normal of the surface itself, on the same side as `normal`. It decides which side of
+    /// the surface a ray is on and where rays leaving the surface start.
+    pub geometric_normal: Vec3f,
+    /// Surface texture coordinates.
+    pub uv: [f32; 2],
+    /// Derivatives of `point` with respect to the texture coordinates, spanning the tangent plane.
+    pub dpdu: Vec3f,
+    pub dpdv: Vec3f,
+    pub material: &'a Material,
+}
+
+impl Hit<'_> {
+    /// A unit tangent following increasing `u` and a unit bitangent, perpendicular to each other
+    /// and to `normal`. The bitangent is flipped to follow increasing `v` for mirrored texture
+    /// coordinates.
+    pub fn tangent_frame(&self) -> (Vec3f, Vec3f) {
+        let n = self.normal;
+        let tangent = self.dpdu - n * (n * self.dpdu);
+        if tangent * tangent <= 1e-12 * (self.dpdu * self.dpdu) {
+            // Degenerate texture coordinates, such as at the poles of a sphere.
+            return n.basis();
+        }
+        let tangent = tangent.normalize();
+        let bitangent = n.cross(tangent);
+        if bitangent * self.dpdv < 0f32 {
+            (tangent, -bitangent)
+        } else {
+            (tangent, bitangent)
+        }
+    }
+}
+
+/// A stretch of a line inside a solid, from the surface where the line enters it to the surface
+/// where it leaves.
+#[derive(Debug, Clone, Copy)]
+pub struct Span<'a> {
+    pub enter: Hit<'a>,
+    pub exit: Hit<'a>,
+}
+
+/// The first boundary of `spans` in front of the ray origin, if it is closer than `t_max`: where a
+/// solid is hit by a ray that enters it or starts inside it.
+pub(crate) fn nearest_boundary<'a>(
+    spans: impl IntoIterator<Item = Span<'a>>,
+    t_max: f32,
+) -> Option<Hit<'a>> {
+    spans
+        .into_iter()
+        .flat_map(|span| [span.enter, span.exit])
+        .find(|hit| hit.t > 0f32)
+        .filter(|hit| hit.t < t_max)
+}
+
+/// Anything that can be intersected by a ray.
+pub trait Shape: fmt::Debug + Send + Sync {
+    /// Bounding box of the shape, `None` if it is unbounded.
+    fn bounds(&self) -> Option<Aabb>;
+
+    /// Finds the nearest intersection in front of `orig` closer than `t_max`. `dir` is a unit
+    /// vector.
+    fn intersect(&self, orig: Vec3f, dir: Vec3f, t_max: f32) -> Option<Hit<'_>>;
+
+    /// Checks whether the ray hits the shape closer than `t_max`.
+    fn occluded(&self, orig: Vec3f, dir: Vec3f, t_max: f32) -> bool {
+        self.intersect(orig, dir, t_max).is_some()
+    }
+
+    /// The spans of the whole line through `orig` along `dir`, behind `orig` as well as in front
+    /// of it, that lie inside the shape, in order and without overlaps. `None` if the shape is a
+    /// surface that does not enclose a volume.
+    ///
+    /// Constructive solid geometry combines solids through their spans.
+    fn spans(&self, _orig: Vec3f, _dir: Vec3f) -> Option<Vec<Span<'_>>> {
+        None
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Sphere {
+    pub center: Vec3f,
+    pub radius: f32,
+    pub material: Material,
+}
+
+impl Sphere {
+    pub fn new(center: Vec3f, radius: f32, material: Material) -> Self {
+        Self {
+            center,
+            radius,
+            material,
+        }
+    }
+
+    /// Returns the distance along `direction` to the nearest intersection in front of `p`.
+    pub fn ray_intersect(&self, p: Vec3f, direction: Vec3f) -> Option<f32> {
+        let (t0, t1) = self.roots(p, direction)?;
+        if t0 >= 0f32 {
+            Some(t0)
+        } else if t1 >= 0f32 {
+            Some(t1)
+        } else {
+            None
+        }
+    }
+
+    /// Distances along `direction` from `p` to where the line enters and leaves the sphere.
+    fn roots(&self, p: Vec3f, direction: Vec3f) -> Option<(f32, f32)> {
+        let vcp = self.center - p;
+        let tca = vcp * direction;
+        let d2 = vcp * vcp - tca * tca;
+        if d2 > self.radius * self.radius {
+            return None;
+        }
+        let thc = (self.radius * self.radius - d2).sqrt();
+        Some((tca - thc, tca + thc))
+    }
+
+    fn hit(&self, orig: Vec3f, dir: Vec3f, t: f32) -> Hit<'_> {
+        let point = orig + dir * t;
+        let normal = (point - self.center).normalize();
+        // Longitude and latitude, with the seam facing -Z.
+        let [x, y, z] = normal.0;
+        let uv = [
+            0.5 + x.atan2(z) / (2f32 * PI),
+            0.5 + y.clamp(-1f32, 1f32).asin() / PI,
+        ];
+        // Distance from the axis, towards which the latitude circles shrink.
+        let axis = (x * x + z * z).sqrt();
+        let dpdu = Vec3f::new(z, 0f32, -x) * (2f32 * PI * self.radius);
+        let dpdv = if axis > 0f32 {
+            Vec3f::new(-x * y / axis, axis, -z * y / axis) * (PI * self.radius)
+        } else {
+            Vec3f::default()
+        };
+        Hit {
+            t,
+            point,
+            normal,
+            geometric_normal: normal,
+            uv,
+            dpdu,
+            dpdv,
+            material: &self.material,
+        }
+    }
+}
+
+impl Shape for Sphere {
+    fn bounds(&self) -> Option<Aabb> {
+        let r = Vec3f::new(self.radius, self.radius, self.radius);
+        Some(Aabb::new(self.center - r, self.center + r))
+    }
+
+    fn intersect(&self, orig: Vec3f, dir: Vec3f, t_max: f32) -> Option<Hit<'_>> {
+        let t = self.ray_intersect(orig, dir).filter(|&t| t < t_max)?;
+        Some(self.hit(orig, dir, t))
+    }
+
+    fn occluded(&self, orig: Vec3f, dir: Vec3f, t_max: f32) -> bool {
+        self.ray_intersect(orig, dir).is_some_and(|t| t < t_max)
+    }
+
+    fn spans(&self, orig: Vec3f, dir: Vec3f) -> Option<Vec<Span<'_>>> {
+        Some(
+            self.roots(orig, dir)
+                .map(|(t0, t1)| Span {
+                    enter: self.hit(orig, dir, t0),
+                    exit: self.hit(orig, dir, t1),
+                })
+                .into_iter()
+                .collect(),
+        )
+    }
+}
+
+/// Alternates a second material with the material of a flat surface in a checkerboard of
+/// `size` by `size` squares.
+///
+/// Unlike a [`Pattern::Checker`] texture, which only varies the color, this swaps the whole
+/// material.
+#[derive(Debug, Clone)]
+pub struct Checker {
+    pub material: Material,
+    pub size: f32,
+}
+
+impl Checker {
+    fn select<'a>(
+        checker: &'a Option<Self>,
+        material: &'a Material,
+        s: f32,
+        t: f32,
+    ) -> &'a Material {
+        match checker {
+            Some(c)
+                if Pattern::Checker.evaluate([s / c.size, t / c.size], Vec3f::default())
+                    != 0f32 =>
+            {
+                &c.material
+            }
+            _ => material,
+        }
+    }
+}
+
+/// An infinite plane through `point`, facing along `normal`.
+#[derive(Debug, Clone)]
+pub struct Plane {
+    pub point: Vec3f,
+    pub normal: Vec3f,
+    pub material: Material,
+    pub checker: Option<Checker>,
+}
+
+impl Plane {
+    pub fn new(point: Vec3f, normal: Vec3f, material: Material) -> Self {
+        Self {
+            point,
+            normal: normal.normalize(),
+            material,
+            checker: None,
+        }
+    }
+
+    pub fn with_checker(mut self, material: Material, size: f32) -> Self {
+        self.checker = Some(Checker { material, size });
+        self
+    }
+}
+
+impl Shape for Plane {
+    fn bounds(&self) -> Option<Aabb> {
+        None
+    }
+
+    fn intersect(&self, orig: Vec3f, dir: Vec3f, t_max: f32) -> Option<Hit<'_>> {
+        let denom = dir * self.normal;
+        if denom.abs() < 1e-6 {
+            return None;
+        }
+        let t = (self.point - orig) * self.normal / denom;
+        if t <= 0f32 || t >= t_max {
+            return None;
+        }
+        let point = orig + dir * t;
+        let (tangent, bitangent) = self.normal.basis();
+        let d = point - self.point;
+        let uv = [d * tangent, d * bitangent];
+        Some(Hit {
+            t,
+            point,
+            normal: self.normal,
+            geometric_normal: self.normal,
+            uv,
+            dpdu: tangent,
+            dpdv: bitangent,
+            material: Checker::select(&self.checker, &self.material, uv[0], uv[1]),
+        })
+    }
+}
+
+/// A parallelogram spanned by the edges `u` and `v` from the corner `origin`.
+///
+/// It faces along `u × v`; texture coordinates run from 0 to 1 along both edges.
+#[derive(Debug, Clone)]
+pub struct Rectangle {
+    pub origin: Vec3f,
+    pub u: Vec3f,
+    pub v: Vec3f,
+    pub material: Material,
+    pub checker: Option<Checker>,
+}
+
+impl Rectangle {
+    pub fn new(origin: Vec3f, u: Vec3f, v: Vec3f, material: Material) -> Self {
+        Self {
+            origin,
+            u,
+            v,
+            material,
+            checker: None,
+        }
+    }
+
+    /// Lays a checkerboard over the rectangle, with squares measured from `origin`.
+    pub fn with_checker(mut self, material: Material, size: f32) -> Self {
+        self.checker = Some(Checker { material, size });
+        self
+    }
+}
+
+impl Shape for Rectangle {
+    fn bounds(&self) -> Option<Aabb> {
+        Some(
+            Aabb::empty()
+                .grow(self.origin)
+                .grow(self.origin + self.u)
+                .grow(self.origin + self.v)
+                .grow(self.origin + self.u + self.v),
+        )
+    }
+
+    fn intersect(&self, orig: Vec3f, dir: Vec3f, t_max: f32) -> Option<Hit<'_>> {
+        let n = self.u.cross(self.v);
+        let denom = dir * n;
+        if denom == 0f32 {
+            return None;
+        }
+        let t = (self.origin - orig) * n / denom;
+        if t <= 0f32 || t >= t_max {
+            return None;
+        }
+        let point = orig + dir * t;
+        let d = point - self.origin;
+        let w = n * (1f32 / (n * n));
+        let a = w * d.cross(self.v);
+        let b = w * self.u.cross(d);
+        if !(0f32..=1f32).contains(&a) || !(0f32..=1f32).contains(&b) {
+            return None;
+        }
+        let material = Checker::select(
+            &self.checker,
+            &self.material,
+            a * self.u.norm(),
+            b * self.v.norm(),
+        );
+        let normal = n.normalize();
+        Some(Hit {
+            t,
+            point,
+            normal,
+            geometric_normal: normal,
+            uv: [a, b],
+            dpdu: self.u,
+            dpdv: self.v,
+            material,
+        })
+    }
+}
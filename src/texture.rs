@@ -0,0 +1,321 @@
+//! Textures varying material colors over a surface.
+
+use crate::framebuffer::Framebuffer;
+use crate::geometry::Vec3f;
+use crate::hdr::{self, Error};
+use crate::noise;
+use crate::tonemap::Encoding;
+use std::f32::consts::PI;
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::Arc;
+
+/// A color varying over a surface, looked up by texture coordinates or position.
+pub trait Texture: fmt::Debug + Send + Sync {
+    /// The linear RGB color at texture coordinates `uv` of the surface point `point`.
+    fn evaluate(&self, uv: [f32; 2], point: Vec3f) -> Vec3f;
+}
+
+/// A constant color.
+impl Texture for Vec3f {
+    fn evaluate(&self, _: [f32; 2], _: Vec3f) -> Vec3f {
+        *self
+    }
+}
+
+/// How texture coordinates outside of the unit square are treated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Wrap {
+    /// Tile the image.
+    #[default]
+    Repeat,
+    /// Extend the edge texels.
+    Clamp,
+}
+
+/// How colors between texel centers are reconstructed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Interpolation {
+    Nearest,
+    /// Blend the four nearest texels.
+    #[default]
+    Bilinear,
+}
+
+/// A texture looked up in an image, which covers texture coordinates from 0 to 1 with `v`
+/// running upwards.
+///
+/// ```
+/// use tinyraytracer::texture::{ImageTexture, Interpolation, Texture, Wrap};
+/// use tinyraytracer::{Framebuffer, Vec3f};
+///
+/// let mut image = Framebuffer::new(2, 1);
+/// image[0][1] = Vec3f::new(1.0, 1.0, 1.0);
+/// let mut texture = ImageTexture::new(image);
+/// let p = Vec3f::default();
+///
+/// // Halfway between the texel centers.
+/// assert_eq!(texture.evaluate([0.5, 0.5], p).0, [0.5; 3]);
+/// // Repeating blends across the seam, clamping does not.
+/// assert_eq!(texture.evaluate([0.0, 0.5], p).0, [0.5; 3]);
+/// texture.wrap = Wrap::Clamp;
+/// assert_eq!(texture.evaluate([0.0, 0.5], p).0, [0.0; 3]);
+/// texture.interpolation = Interpolation::Nearest;
+/// assert_eq!(texture.evaluate([0.7, 0.5], p).0, [1.0; 3]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ImageTexture {
+    /// Linear RGB texels.
+    image: Framebuffer,
+    pub wrap: Wrap,
+    pub interpolation: Interpolation,
+    /// Multiplies the texture coordinates, repeating the image `scale` times per unit.
+    pub scale: [f32; 2],
+}
+
+impl ImageTexture {
+    pub fn new(image: Framebuffer) -> Self {
+        Self {
+            image,
+            wrap: Wrap::default(),
+            interpolation: Interpolation::default(),
+            scale: [1f32, 1f32],
+        }
+    }
+
+    /// Reads a PNG, Radiance `.hdr` or `.pfm` image. PNG values are converted from `encoding` to
+    /// linear; the high dynamic range formats are linear already.
+    pub fn load<P: AsRef<Path>>(path: P, encoding: Encoding) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let is_png = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| e.eq_ignore_ascii_case("png"));
+        let image = if is_png {
+            read_png(path, encoding)?
+        } else {
+            hdr::load(path)?
+        };
+        Ok(Self::new(image))
+    }
+
+    fn texel(&self, x: i64, y: i64) -> Vec3f {
+        let (width, height) = (self.image.width() as i64, self.image.height() as i64);
+        let (x, y) = match self.wrap {
+            Wrap::Repeat => (x.rem_euclid(width), y.rem_euclid(height)),
+            Wrap::Clamp => (x.clamp(0, width - 1), y.clamp(0, height - 1)),
+        };
+        self.image[y as usize][x as usize]
+    }
+}
+
+impl Texture for ImageTexture {
+    fn evaluate(&self, uv: [f32; 2], _: Vec3f) -> Vec3f {
+        // Position in texels, with the first row at the top of the image.
+        let x = uv[0] * self.scale[0] * self.image.width() as f32;
+        let y = (1f32 - uv[1] * self.scale[1]) * self.image.height() as f32;
+        if !(x.is_finite() && y.is_finite()) {
+            return Vec3f::default();
+        }
+        match self.interpolation {
+            Interpolation::Nearest => self.texel(x.floor() as i64, y.floor() as i64),
+            Interpolation::Bilinear => {
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (x0, y0) = (x.floor(), y.floor());
+                let (fx, fy) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as i64, y0 as i64);
+                let top = self.texel(x0, y0) * (1f32 - fx) + self.texel(x0 + 1, y0) * fx;
+                let bottom = self.texel(x0, y0 + 1) * (1f32 - fx) + self.texel(x0 + 1, y0 + 1) * fx;
+                top * (1f32 - fy) + bottom * fy
+            }
+        }
+    }
+}
+
+/// A procedural pattern, a value between 0 and 1 that varies over texture coordinates or through
+/// space.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pattern {
+    /// Alternating squares in texture coordinates.
+    Checker,
+    /// Alternating cubes in space, which fit together across edges and curved surfaces.
+    SolidChecker,
+    /// Bands across the `u` texture coordinate, alternating along `u`.
+    Stripes,
+    /// Smooth [Perlin noise](noise::perlin).
+    Noise,
+    /// Noise with detail at `octaves` scales, see [`noise::fbm`].
+    Fbm { octaves: u32 },
+    /// Noise with creases, see [`noise::turbulence`].
+    Turbulence { octaves: u32 },
+    /// Veins along the x axis, bent by turbulence of the given strength.
+    Marble { turbulence: f32 },
+    /// Rings around the y axis, one per unit, distorted by noise of the given strength.
+    Wood { turbulence: f32 },
+    /// The distance to the nearest of randomly scattered points, giving a cellular look. See
+    /// [`noise::worley`].
+    Worley,
+}
+
+impl Pattern {
+    /// The value of the pattern at texture coordinates `uv` and point `p`, both in units of the
+    /// pattern's features.
+    pub fn evaluate(&self, uv: [f32; 2], p: Vec3f) -> f32 {
+        let parity = |coordinates: &[f32]| {
+            let sum: f32 = coordinates.iter().map(|c| c.floor()).sum();
+            sum.rem_euclid(2f32)
+        };
+        match *self {
+            Pattern::Checker => parity(&uv),
+            Pattern::SolidChecker => parity(&p.0),
+            Pattern::Stripes => parity(&uv[..1]),
+            Pattern::Noise => 0.5 + 0.5 * noise::perlin(p),
+            Pattern::Fbm { octaves } => 0.5 + 0.5 * noise::fbm(p, octaves),
+            Pattern::Turbulence { octaves } => noise::turbulence(p, octaves),
+            Pattern::Marble { turbulence } => {
+                let t = noise::turbulence(p, noise::DEFAULT_OCTAVES);
+                0.5 + 0.5 * (PI * (p.0[0] + turbulence * t)).sin()
+            }
+            Pattern::Wood { turbulence } => {
+                let [x, _, z] = p.0;
+                let r = (x * x + z * z).sqrt() + turbulence * noise::perlin(p);
+                r - r.floor()
+            }
+            Pattern::Worley => noise::worley(p),
+        }
+    }
+}
+
+/// Blends two textures by a [`Pattern`], showing `a` where the pattern is 0 and `b` where it is 1.
+///
+/// Either texture may be another procedural texture, an image or a constant color.
+///
+/// ```
+/// use std::sync::Arc;
+/// use tinyraytracer::texture::{Pattern, Procedural, Texture};
+/// use tinyraytracer::Vec3f;
+///
+/// let white = Vec3f::new(1.0, 1.0, 1.0);
+/// let black = Vec3f::default();
+/// let checker = Procedural::new(Pattern::Checker, white, black).with_size(0.5);
+/// assert_eq!(checker.evaluate([0.25, 0.25], black), white);
+/// assert_eq!(checker.evaluate([0.75, 0.25], black), black);
+/// // Squares of any size continue across zero.
+/// assert_eq!(checker.evaluate([-0.25, 0.25], black), black);
+///
+/// // Cubes of red and blue stripes, alternating with white ones.
+/// let red = Vec3f::new(1.0, 0.0, 0.0);
+/// let stripes = Procedural::new(Pattern::Stripes, red, Vec3f::new(0.0, 0.0, 1.0));
+/// let cubes = Procedural::new(Pattern::SolidChecker, stripes, white);
+/// assert_eq!(cubes.evaluate([0.5, 0.0], Vec3f::new(0.5, 0.5, 0.5)), red);
+/// assert_eq!(cubes.evaluate([0.5, 0.0], Vec3f::new(1.5, 0.5, 0.5)), white);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Procedural {
+    pub pattern: Pattern,
+    pub a: Arc<dyn Texture>,
+    pub b: Arc<dyn Texture>,
+    /// Size of the features of the pattern, in texture coordinates or scene units.
+    pub size: f32,
+}
+
+impl Procedural {
+    pub fn new<A, B>(pattern: Pattern, a: A, b: B) -> Self
+    where
+        A: Texture + 'static,
+        B: Texture + 'static,
+    {
+        Self {
+            pattern,
+            a: Arc::new(a),
+            b: Arc::new(b),
+            size: 1f32,
+        }
+    }
+
+    pub fn with_size(mut self, size: f32) -> Self {
+        self.size = size;
+        self
+    }
+}
+
+impl Texture for Procedural {
+    fn evaluate(&self, uv: [f32; 2], point: Vec3f) -> Vec3f {
+        let scale = 1f32 / self.size;
+        let t = self
+            .pattern
+            .evaluate([uv[0] * scale, uv[1] * scale], point * scale)
+            .clamp(0f32, 1f32);
+        // Hard-edged patterns pick one texture rather than evaluating both.
+        if t == 0f32 {
+            self.a.evaluate(uv, point)
+        } else if t == 1f32 {
+            self.b.evaluate(uv, point)
+        } else {
+            self.a.evaluate(uv, point) * (1f32 - t) + self.b.evaluate(uv, point) * t
+        }
+    }
+}
+
+/// Decodes a PNG image of any color type into linear RGB, dropping the alpha channel.
+fn read_png(path: &Path, encoding: Encoding) -> Result<Framebuffer, Error> {
+    use png::HasParameters;
+
+    let mut decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+    decoder.set(png::Transformations::EXPAND);
+    let (info, mut reader) = decoder.read_info().map_err(png_error)?;
+    let mut data = vec![0u8; info.buffer_size()];
+    reader.next_frame(&mut data).map_err(png_error)?;
+
+    let (width, height) = (info.width as usize, info.height as usize);
+    let channels = info.color_type.samples();
+    let bytes = info.line_size / width;
+    let sample_max = if bytes >= 2 * channels {
+        65535f32
+    } else {
+        255f32
+    };
+    let sample = |i: usize| {
+        let raw = if bytes >= 2 * channels {
+            u16::from_be_bytes([data[2 * i], data[2 * i + 1]]) as f32
+        } else {
+            data[i] as f32
+        };
+        let value = raw / sample_max;
+        match encoding {
+            Encoding::Linear => value,
+            Encoding::Srgb => srgb_decode(value),
+        }
+    };
+    let mut image = Framebuffer::new(width, height);
+    for y in 0..height {
+        for (x, pixel) in image[y].iter_mut().enumerate() {
+            let first = (y * info.line_size) / (bytes / channels) + x * channels;
+            *pixel = if channels >= 3 {
+                Vec3f::new(sample(first), sample(first + 1), sample(first + 2))
+            } else {
+                let v = sample(first);
+                Vec3f::new(v, v, v)
+            };
+        }
+    }
+    Ok(image)
+}
+
+fn png_error(e: png::DecodingError) -> Error {
+    match e {
+        png::DecodingError::IoError(e) => e.into(),
+        e => Error::Invalid(e.to_string()),
+    }
+}
+
+/// The inverse of the sRGB transfer function, from encoded values to linear ones.
+fn srgb_decode(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
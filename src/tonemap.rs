@@ -0,0 +1,141 @@
+//! Conversion of linear radiance into 8-bit display values: exposure, a tone mapping operator
+//! compressing the dynamic range, the transfer encoding and quantization.
+
+use crate::framebuffer::Framebuffer;
+use crate::geometry::Vec3f;
+use crate::sampling::Rng;
+
+/// Sets the dithering noise apart from the random numbers used to render each pixel.
+const DITHER_STREAM: u64 = 1 << 63;
+
+/// Maps radiance of any brightness into the displayable range from 0 to 1.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Operator {
+    /// Scales down colors brighter than 1 by their largest channel, preserving their hue. Colors
+    /// within range are left alone.
+    #[default]
+    Normalize,
+    /// Clips each channel at 1.
+    Clamp,
+    /// Reinhard's global operator `L / (1 + L)` on the luminance, which compresses highlights
+    /// smoothly but never reaches white.
+    Reinhard,
+    /// Reinhard's operator extended to map the luminance `white` to 1.
+    ExtendedReinhard { white: f32 },
+    /// Narkowicz's fit of the ACES filmic curve, with a toe in the shadows and saturated
+    /// highlights.
+    Aces,
+}
+
+/// How the tone mapped values are encoded into the image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Encoding {
+    /// The values themselves, which display darker than intended.
+    #[default]
+    Linear,
+    /// The sRGB transfer function expected by displays and image viewers.
+    Srgb,
+}
+
+/// The steps turning a rendered framebuffer into an 8-bit image.
+///
+/// The default reproduces the plain linear output of earlier versions.
+///
+/// ```
+/// use tinyraytracer::tonemap::{Encoding, Operator, ToneMapping};
+/// use tinyraytracer::Vec3f;
+///
+/// let tone_mapping = ToneMapping {
+///     operator: Operator::Aces,
+///     exposure: 1.0,
+///     encoding: Encoding::Srgb,
+///     ..ToneMapping::default()
+/// };
+/// let bright = tone_mapping.map(Vec3f::new(100.0, 10.0, 1.0));
+/// assert!(bright.0.iter().all(|&c| c > 0.9 && c <= 1.0));
+/// assert_eq!(tone_mapping.map(Vec3f::default()).0, [0.0; 3]);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ToneMapping {
+    pub operator: Operator,
+    /// Exposure adjustment in stops: the radiance is multiplied by `2^exposure` first.
+    pub exposure: f32,
+    pub encoding: Encoding,
+    /// Whether to add noise of one quantization step before truncating to 8 bits, which hides
+    /// banding in smooth gradients.
+    pub dither: bool,
+}
+
+impl ToneMapping {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The display value of `radiance`, with channels between 0 and 1.
+    pub fn map(&self, radiance: Vec3f) -> Vec3f {
+        let c = Vec3f(radiance.0.map(|c| c.max(0f32))) * self.exposure.exp2();
+        let scale_luminance = |f: &dyn Fn(f32) -> f32| {
+            let luminance = c.luminance();
+            if luminance > 0f32 {
+                c * (f(luminance) / luminance)
+            } else {
+                c
+            }
+        };
+        let mapped = match self.operator {
+            Operator::Normalize => {
+                let max = c.max_component();
+                if max > 1f32 {
+                    c * (1f32 / max)
+                } else {
+                    c
+                }
+            }
+            Operator::Clamp => c,
+            Operator::Reinhard => scale_luminance(&|l| l / (1f32 + l)),
+            Operator::ExtendedReinhard { white } => {
+                scale_luminance(&|l| l * (1f32 + l / (white * white)) / (1f32 + l))
+            }
+            Operator::Aces => Vec3f(c.0.map(|x| {
+                // The fit is too bright for scene-referred values without this scale.
+                let x = 0.6 * x;
+                x * (2.51 * x + 0.03) / (x * (2.43 * x + 0.59) + 0.14)
+            })),
+        };
+        Vec3f(mapped.0.map(|c| {
+            let c = c.clamp(0f32, 1f32);
+            match self.encoding {
+                Encoding::Linear => c,
+                Encoding::Srgb => srgb_encode(c),
+            }
+        }))
+    }
+
+    /// Quantizes the framebuffer to 8-bit RGB.
+    ///
+    /// The dithering noise is seeded per pixel, so the image is reproducible.
+    pub fn to_rgb8(&self, framebuffer: &Framebuffer) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(framebuffer.pixels().len() * 3);
+        for y in 0..framebuffer.height() {
+            for (x, &pixel) in framebuffer[y].iter().enumerate() {
+                let p = self.map(pixel);
+                if self.dither {
+                    let mut rng = Rng::new(x as u64, y as u64 ^ DITHER_STREAM);
+                    bytes.extend(p.0.map(|c| (c * 255f32 + rng.next_f32()).min(255f32) as u8));
+                } else {
+                    bytes.extend(p.as_bytes());
+                }
+            }
+        }
+        bytes
+    }
+}
+
+/// The sRGB transfer function, from linear values to encoded ones.
+fn srgb_encode(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        12.92 * c
+    } else {
+        1.055 * c.powf(1f32 / 2.4) - 0.055
+    }
+}